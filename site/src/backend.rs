@@ -1,6 +1,6 @@
 use std::{
     any::Any,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::Cursor,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -24,6 +24,7 @@ pub struct WebBackend {
     pub files: Mutex<HashMap<String, Vec<u8>>>,
     next_thread_id: AtomicU64,
     thread_results: Mutex<HashMap<Handle, UiuaResult<Vec<Value>>>>,
+    channels: Mutex<HashMap<Handle, VecDeque<Value>>>,
 }
 
 impl Default for WebBackend {
@@ -35,6 +36,7 @@ impl Default for WebBackend {
             files: HashMap::new().into(),
             next_thread_id: 0.into(),
             thread_results: HashMap::new().into(),
+            channels: HashMap::new().into(),
         }
     }
 }
@@ -144,6 +146,42 @@ impl SysBackend for WebBackend {
             None => Err(Err("Invalid thread handle".into())),
         }
     }
+    fn wait_timeout(
+        &self,
+        handle: Handle,
+        _timeout: f64,
+    ) -> Result<Option<Vec<Value>>, Result<UiuaError, String>> {
+        // Threads run to completion when spawned, so they are always finished
+        self.wait(handle).map(Some)
+    }
+    fn channel(&self) -> Result<Handle, String> {
+        let handle = Handle(self.next_thread_id.fetch_add(1, Ordering::SeqCst));
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(handle, VecDeque::new());
+        Ok(handle)
+    }
+    fn channel_send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        match self.channels.lock().unwrap().get_mut(&handle) {
+            Some(queue) => {
+                queue.push_back(value);
+                Ok(())
+            }
+            None => Err("Invalid channel handle".into()),
+        }
+    }
+    fn channel_receive(&self, handle: Handle) -> Result<Value, String> {
+        // Threads run to completion when spawned, so an empty channel will never be filled
+        self.channel_try_receive(handle)?
+            .ok_or_else(|| "Channel is empty and would never receive a value".into())
+    }
+    fn channel_try_receive(&self, handle: Handle) -> Result<Option<Value>, String> {
+        match self.channels.lock().unwrap().get_mut(&handle) {
+            Some(queue) => Ok(queue.pop_front()),
+            None => Err("Invalid channel handle".into()),
+        }
+    }
     fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
         let code: String = if args.len() > 0 {
             format!("{}({})", command, args.join(","))
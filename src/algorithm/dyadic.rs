@@ -6,9 +6,9 @@ use ecow::EcoVec;
 use tinyvec::tiny_vec;
 
 use crate::{
-    algorithm::max_shape,
+    algorithm::{max_shape, pervade::is_ne},
     array::*,
-    cowslice::{cowslice, CowSlice},
+    cowslice::{check_allocation, cowslice, CowSlice},
     function::Function,
     value::Value,
     Uiua, UiuaResult,
@@ -497,6 +497,7 @@ impl Value {
 
 impl<T: ArrayValue> Array<T> {
     pub fn reshape_scalar(&mut self, count: usize) {
+        check_allocation::<T>(count.saturating_mul(self.data.len()));
         self.data.modify(|data| {
             if count == 0 {
                 data.clear();
@@ -583,6 +584,7 @@ impl<T: ArrayValue> Array<T> {
             }
         };
         let target_len: usize = shape.iter().product();
+        check_allocation::<T>(target_len);
         if self.data.len() < target_len {
             if let Some(fill) = env.fill::<T>() {
                 let start = self.data.len();
@@ -634,6 +636,41 @@ impl Value {
             }
         })
     }
+    /// Equivalent to [`Value::is_ne`] followed by [`Value::keep`]
+    ///
+    /// Comparing a list with a scalar builds the keep counts directly.
+    pub fn keep_ne(self, other: Self, kept: Self, env: &Uiua) -> UiuaResult<Self> {
+        fn counts<T: Copy>(scalar: T, list: &[T], ne: impl Fn(T, T) -> u8) -> Vec<usize> {
+            list.iter().map(|&x| ne(scalar, x) as usize).collect()
+        }
+        let counts = match (&self, &other) {
+            (Value::Num(a), Value::Num(b)) if a.rank() == 0 && b.rank() == 1 => {
+                counts(a.data[0], &b.data, is_ne::num_num)
+            }
+            (Value::Num(a), Value::Num(b)) if a.rank() == 1 && b.rank() == 0 => {
+                counts(b.data[0], &a.data, is_ne::num_num)
+            }
+            (Value::Byte(a), Value::Byte(b)) if a.rank() == 0 && b.rank() == 1 => {
+                counts(a.data[0], &b.data, is_ne::generic)
+            }
+            (Value::Byte(a), Value::Byte(b)) if a.rank() == 1 && b.rank() == 0 => {
+                counts(b.data[0], &a.data, is_ne::generic)
+            }
+            (Value::Char(a), Value::Char(b)) if a.rank() == 0 && b.rank() == 1 => {
+                counts(a.data[0], &b.data, is_ne::generic)
+            }
+            (Value::Char(a), Value::Char(b)) if a.rank() == 1 && b.rank() == 0 => {
+                counts(b.data[0], &a.data, is_ne::generic)
+            }
+            _ => return self.is_ne(other, env)?.keep(kept, env),
+        };
+        Ok(match kept {
+            Value::Num(a) => a.list_keep(&counts, env)?.into(),
+            Value::Byte(a) => a.list_keep(&counts, env)?.into(),
+            Value::Char(a) => a.list_keep(&counts, env)?.into(),
+            Value::Func(a) => a.list_keep(&counts, env)?.into(),
+        })
+    }
     pub fn unkeep(self, kept: Self, into: Self, env: &Uiua) -> UiuaResult<Self> {
         let counts = self.as_naturals(
             env,
@@ -688,6 +725,7 @@ impl<T: ArrayValue> Array<T> {
         // Keep ≥2 is a repeat
         self.shape[0] *= count;
         let old_data = self.data.clone();
+        check_allocation::<T>(old_data.len().saturating_mul(count));
         self.data.modify(|data| {
             data.reserve(data.len() * count);
             for _ in 1..count {
@@ -1,6 +1,7 @@
 //! Algorithms for forking modifiers
 
 use crate::{
+    coverage::BranchKind,
     run::{ArrayArg, FunctionArg},
     value::Value,
     Uiua, UiuaResult,
@@ -93,6 +94,7 @@ pub fn iff(env: &mut Uiua) -> UiuaResult {
         let if_true_sig = if_true.signature();
         let if_false_sig = if_false.signature();
         if if_true_sig.args == if_false_sig.args || if_true_sig.is_compatible_with(if_false_sig) {
+            env.record_branch(BranchKind::If, 1 - condition);
             if condition == 1 {
                 env.call(if_true)?;
             } else {
@@ -104,6 +106,7 @@ pub fn iff(env: &mut Uiua) -> UiuaResult {
             for i in 0..arg_count {
                 args.push(env.pop(ArrayArg(i + 1))?);
             }
+            env.record_branch(BranchKind::If, 1 - condition);
             if condition == 1 {
                 for arg in args.into_iter().take(if_true_sig.args).rev() {
                     env.push(arg);
@@ -155,6 +158,7 @@ pub fn iff(env: &mut Uiua) -> UiuaResult {
                 }
                 let mut new_rows = Vec::with_capacity(condition.len());
                 for (con, x) in condition.into_iter().zip(xs.into_rows()) {
+                    env.record_branch(BranchKind::If, 1 - con);
                     env.push(x);
                     if con == 1 {
                         env.call(if_true.clone())?;
@@ -180,6 +184,7 @@ pub fn iff(env: &mut Uiua) -> UiuaResult {
                 }
                 let mut new_rows = Vec::with_capacity(condition.len());
                 for (con, (a, b)) in condition.into_iter().zip(a.into_rows().zip(b.into_rows())) {
+                    env.record_branch(BranchKind::If, 1 - con);
                     if con == 1 {
                         if if_true_sig.args == 2 {
                             env.push(b);
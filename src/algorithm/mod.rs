@@ -12,9 +12,12 @@ pub mod fork;
 pub(crate) mod invert;
 pub mod loops;
 mod monadic;
+mod parallel;
 pub mod pervade;
+pub mod prop;
 pub mod reduce;
 pub mod table;
+pub mod windows;
 pub mod zip;
 
 fn max_shape(a: &[usize], b: &[usize]) -> Shape {
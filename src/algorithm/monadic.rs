@@ -14,7 +14,7 @@ use tinyvec::tiny_vec;
 
 use crate::{
     array::*,
-    cowslice::{cowslice, CowSlice},
+    cowslice::{check_allocation, cowslice, CowSlice},
     function::Signature,
     value::Value,
     Uiua, UiuaResult,
@@ -83,6 +83,7 @@ fn range(shape: &[usize], env: &Uiua) -> UiuaResult<CowSlice<f64>> {
         }
         len = new;
     }
+    check_allocation::<f64>(len);
     let mut data: EcoVec<f64> = EcoVec::with_capacity(len);
     let mut curr = vec![0; shape.len()];
     loop {
@@ -270,6 +271,22 @@ impl Value {
         self.generic_ref_env_deep(Array::fall, Array::fall, Array::fall, Array::fall, env)
             .map(Self::from_iter)
     }
+    /// Equivalent to [`Value::rise`] followed by [`Value::first`], without sorting
+    pub fn first_min_index(&self, env: &Uiua) -> UiuaResult<Self> {
+        let index = self.generic_ref_env_deep(
+            Array::first_min_index,
+            Array::first_min_index,
+            Array::first_min_index,
+            Array::first_min_index,
+            env,
+        )?;
+        Self::from_iter(index).first(env)
+    }
+    /// Equivalent to [`Value::rise`] followed by [`Value::reverse`]
+    pub fn reverse_rise(&self, env: &Uiua) -> UiuaResult<Self> {
+        self.generic_ref_env_deep(Array::rise, Array::rise, Array::rise, Array::rise, env)
+            .map(|indices| indices.into_iter().rev().collect())
+    }
     pub fn classify(&self, env: &Uiua) -> UiuaResult<Self> {
         self.generic_ref_env_deep(
             Array::classify,
@@ -309,6 +326,31 @@ impl<T: ArrayValue> Array<T> {
         });
         Ok(indices)
     }
+    /// Get the first index that [`Array::rise`] would return, if any
+    pub fn first_min_index(&self, env: &Uiua) -> UiuaResult<Option<usize>> {
+        if self.rank() == 0 {
+            return Err(env.error("Cannot rise a scalar"));
+        }
+        if self.flat_len() == 0 {
+            return Ok(None);
+        }
+        let cmp = |a: usize, b: usize| {
+            self.row_slice(a)
+                .iter()
+                .zip(self.row_slice(b))
+                .map(|(a, b)| a.array_cmp(b))
+                .find(|x| x != &Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+        // Only a strictly smaller row replaces the minimum, matching the stable sort
+        Ok((0..self.row_count()).reduce(|min, i| {
+            if cmp(i, min) == Ordering::Less {
+                i
+            } else {
+                min
+            }
+        }))
+    }
     pub fn fall(&self, env: &Uiua) -> UiuaResult<Vec<usize>> {
         if self.rank() == 0 {
             return Err(env.error("Cannot fall a scalar"));
@@ -492,6 +534,7 @@ impl Value {
     pub fn wher(&self, env: &Uiua) -> UiuaResult<Self> {
         let counts = self.as_naturals(env, "Argument to where must be a list of naturals")?;
         let total: usize = counts.iter().fold(0, |acc, &b| acc.saturating_add(b));
+        check_allocation::<f64>(total);
         let mut data = EcoVec::with_capacity(total);
         for (i, &b) in counts.iter().enumerate() {
             for _ in 0..b {
@@ -509,6 +552,7 @@ impl Value {
             .zip(indices.iter().skip(1))
             .all(|(&a, &b)| a <= b);
         let size = indices.iter().max().map(|&i| i + 1).unwrap_or(0);
+        check_allocation::<u8>(size);
         let mut data = EcoVec::with_capacity(size);
         if is_sorted {
             let mut j = 0;
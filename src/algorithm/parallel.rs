@@ -0,0 +1,92 @@
+//! Parallel execution of iterating modifiers
+
+use rayon::prelude::*;
+
+use crate::{value::Value, Uiua, UiuaResult};
+
+/// The minimum number of calls for an iterating modifier to be split across threads
+pub(crate) const PARALLEL_THRESHOLD: usize = 1024;
+
+/// Check if calling a function a number of times on the given arguments should be done in parallel
+///
+/// The function, as well as any functions in the arguments, must be pure.
+pub(crate) fn should_parallelize(f: &Value, args: &[&Value], calls: usize, env: &Uiua) -> bool {
+    calls >= PARALLEL_THRESHOLD
+        && env.parallel_enabled()
+        && rayon::current_num_threads() > 1
+        && [f].iter().chain(args).all(|val| match val {
+            Value::Func(fs) => fs.data.iter().all(|f| f.is_pure()),
+            _ => true,
+        })
+}
+
+/// Call a function once for each group of arguments, splitting the calls across threads
+///
+/// The first argument in each group is on top of the stack when the function is called.
+/// The function must have exactly 1 output. The outputs are returned in order.
+pub(crate) fn par_call(f: &Value, groups: Vec<Vec<Value>>, env: &Uiua) -> UiuaResult<Vec<Value>> {
+    let call_count = groups.len();
+    let chunk_len = call_count.div_ceil(rayon::current_num_threads()).max(1);
+    let mut groups = groups.into_iter();
+    let mut jobs = Vec::new();
+    loop {
+        let chunk: Vec<_> = groups.by_ref().take(chunk_len).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        jobs.push((env.thread_env(Vec::new()), chunk));
+    }
+    let results: Vec<UiuaResult<Vec<Value>>> = jobs
+        .into_par_iter()
+        .map(|(mut env, chunk)| {
+            let mut outputs = Vec::with_capacity(chunk.len());
+            for args in chunk {
+                for arg in args.into_iter().rev() {
+                    env.push(arg);
+                }
+                env.call(f.clone())?;
+                outputs.push(env.pop("iterated function result")?);
+            }
+            Ok(outputs)
+        })
+        .collect();
+    let mut outputs = Vec::with_capacity(call_count);
+    for result in results {
+        outputs.extend(result?);
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let code = "\
+            ≡(/+×.) ⊞+⇡40⇡40\n\
+            ∵(×2) ⇡2000\n\
+            ∵+ ⇡2000 ⇡2000\n\
+            ≡⊂ ⇡2000 ⇡2000\n\
+            ≡(□⇡) ⇡1100\n\
+            ≡(⊂⊂) ⇡2000 ⇡2000 ⇡2000\n\
+            ⊞(+×2) ⇡50 ⇡50";
+        let run = |parallel| {
+            let mut env = Uiua::with_native_sys().parallel(parallel);
+            env.load_str(code).unwrap();
+            env.take_stack()
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let (sequential, parallel) = pool.install(|| (run(false), run(true)));
+        assert_eq!(sequential, parallel);
+        let error = pool.install(|| {
+            (Uiua::with_native_sys())
+                .load_str("≡(⍤\"bad\" ≠1500.) ⇡2000")
+                .unwrap_err()
+        });
+        assert!(error.to_string().contains("bad"));
+    }
+}
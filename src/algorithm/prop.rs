@@ -0,0 +1,224 @@
+//! Property-based testing
+
+use std::sync::Arc;
+
+use rand::prelude::*;
+
+use crate::{
+    array::{Array, Shape},
+    cowslice::CowSlice,
+    function::Function,
+    run::{ArrayArg, FunctionArg},
+    value::Value,
+    Uiua, UiuaResult,
+};
+
+/// The number of cases to try before a property is considered to hold
+const CASES: usize = 100;
+/// The maximum number of candidates to try when shrinking a counterexample
+const MAX_SHRINKS: usize = 1000;
+/// The maximum number of boxes in an array whose contents are shrunk
+const MAX_SHRUNK_BOXES: usize = 8;
+
+pub fn prop(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let f = env.pop(FunctionArg(1))?;
+    let seed = env
+        .pop(ArrayArg(1))?
+        .as_num(env, "Prop's seed must be a number")?;
+    let arg_count = f.signature().args;
+    let mut rng = SmallRng::seed_from_u64(seed.to_bits());
+    for case in 0..CASES {
+        // Cases start small and grow
+        let size = 1 + case / 10;
+        let args: Vec<Value> = (0..arg_count)
+            .map(|_| random_value(&mut rng, size))
+            .collect();
+        if let Err(reason) = check(env, &f, &args) {
+            let (args, reason, steps) = shrink(env, &f, args, reason);
+            let mut message = format!(
+                "Property failed on case {} of {CASES} with seed {seed}\n\
+                Counterexample (shrunk in {steps} steps):",
+                case + 1
+            );
+            for arg in &args {
+                for line in arg.show().lines() {
+                    message.push_str("\n  ");
+                    message.push_str(line);
+                }
+            }
+            message.push_str("\nFailure: ");
+            message.push_str(&reason);
+            return Err(env.error(message));
+        }
+    }
+    Ok(())
+}
+
+/// Run the predicate on some arguments
+///
+/// The predicate fails if it errors or if its result is not `1`.
+fn check(env: &mut Uiua, f: &Value, args: &[Value]) -> Result<(), String> {
+    let bottom = env.stack_size();
+    for arg in args.iter().rev() {
+        env.push(arg.clone());
+    }
+    let res = match env.call(f.clone()) {
+        Ok(()) if f.signature().outputs == 0 => Ok(()),
+        Ok(()) => match env.pop("property result") {
+            Ok(res) if res.as_nat(env, "").is_ok_and(|n| n == 1) => Ok(()),
+            Ok(res) => Err(format!("the predicate returned {}", res.show())),
+            Err(e) => Err(e.message()),
+        },
+        Err(e) => Err(e.message()),
+    };
+    env.truncate_stack(bottom);
+    res
+}
+
+/// Repeatedly replace arguments with simpler ones that still fail
+fn shrink(
+    env: &mut Uiua,
+    f: &Value,
+    mut args: Vec<Value>,
+    mut reason: String,
+) -> (Vec<Value>, String, usize) {
+    let mut tries = 0;
+    let mut steps = 0;
+    'outer: while tries < MAX_SHRINKS {
+        for i in 0..args.len() {
+            for candidate in shrink_candidates(&args[i]) {
+                tries += 1;
+                let mut new_args = args.clone();
+                new_args[i] = candidate;
+                if let Err(new_reason) = check(env, f, &new_args) {
+                    args = new_args;
+                    reason = new_reason;
+                    steps += 1;
+                    continue 'outer;
+                }
+                if tries >= MAX_SHRINKS {
+                    break 'outer;
+                }
+            }
+        }
+        break;
+    }
+    (args, reason, steps)
+}
+
+fn shrink_candidates(value: &Value) -> Vec<Value> {
+    let mut candidates = Vec::new();
+    // Fewer rows
+    if value.rank() > 0 && value.row_count() > 0 {
+        let rows: Vec<Value> = value.clone().into_rows().collect();
+        let n = rows.len();
+        if n > 1 {
+            candidates.push(Value::from_row_values_infallible(rows[..n / 2].to_vec()));
+            candidates.push(Value::from_row_values_infallible(rows[1..].to_vec()));
+            candidates.push(Value::from_row_values_infallible(rows[..n - 1].to_vec()));
+        }
+        candidates.push(rows[0].clone());
+    }
+    // Simpler elements
+    match value {
+        Value::Num(arr) => {
+            candidates.push(arr.clone().convert_with(|_| 0.0).into());
+            candidates.push(arr.clone().convert_with(f64::trunc).into());
+            candidates.push(arr.clone().convert_with(|n| (n / 2.0).trunc()).into());
+        }
+        Value::Byte(arr) => {
+            candidates.push(arr.clone().convert_with(|_| 0u8).into());
+            candidates.push(arr.clone().convert_with(|b| b / 2).into());
+        }
+        Value::Char(arr) => {
+            candidates.push(arr.clone().convert_with(|_| 'a').into());
+            candidates.push(
+                (arr.clone())
+                    .convert_with(|c| if c.is_ascii() { c } else { 'a' })
+                    .into(),
+            );
+        }
+        Value::Func(arr) => {
+            if let Some(value) = arr.data.first().and_then(|f| f.as_boxed()) {
+                candidates.push(value.clone());
+            }
+            // Shrink the contents of each box
+            for (i, f) in arr.data.iter().enumerate().take(MAX_SHRUNK_BOXES) {
+                let Some(inner) = f.as_boxed() else {
+                    continue;
+                };
+                for inner in shrink_candidates(inner) {
+                    let mut arr = arr.clone();
+                    arr.data
+                        .modify(|data| data.make_mut()[i] = Arc::new(Function::boxed(inner)));
+                    candidates.push(arr.into());
+                }
+            }
+        }
+    }
+    candidates.retain(|candidate| candidate != value);
+    candidates
+}
+
+fn random_value(rng: &mut SmallRng, size: usize) -> Value {
+    let rank = rng.gen_range(0..=size.min(3));
+    let shape: Shape = (0..rank).map(|_| rng.gen_range(0..=size)).collect();
+    let len: usize = shape.iter().product();
+    match rng.gen_range(0..4) {
+        0 => {
+            let data: CowSlice<f64> = (0..len).map(|_| random_num(rng, size)).collect();
+            Array::new(shape, data).into()
+        }
+        1 => {
+            let boolean = rng.gen_bool(0.5);
+            let data: CowSlice<u8> = (0..len)
+                .map(|_| {
+                    if boolean {
+                        rng.gen_range(0..=1)
+                    } else {
+                        rng.gen()
+                    }
+                })
+                .collect();
+            Array::new(shape, data).into()
+        }
+        2 => {
+            let data: CowSlice<char> = (0..len).map(|_| random_char(rng)).collect();
+            Array::new(shape, data).into()
+        }
+        _ => {
+            let inner_size = size.saturating_sub(1).max(1);
+            let data: CowSlice<Arc<Function>> = (0..len)
+                .map(|_| {
+                    let value = if size > 1 {
+                        random_value(rng, inner_size)
+                    } else {
+                        random_num(rng, 1).into()
+                    };
+                    Arc::new(Function::boxed(value))
+                })
+                .collect();
+            Array::new(shape, data).into()
+        }
+    }
+}
+
+fn random_num(rng: &mut SmallRng, size: usize) -> f64 {
+    let size = size as f64 * 2.0;
+    match rng.gen_range(0..50) {
+        0 => f64::INFINITY,
+        1 => f64::NEG_INFINITY,
+        2..=14 => rng.gen_range(-size..=size),
+        _ => rng.gen_range(-size..=size).round(),
+    }
+}
+
+fn random_char(rng: &mut SmallRng) -> char {
+    const SPECIAL: [char; 6] = [' ', '\n', '\t', 'é', 'λ', '👍'];
+    if rng.gen_bool(0.1) {
+        SPECIAL[rng.gen_range(0..SPECIAL.len())]
+    } else {
+        rng.gen_range(' '..='~')
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     algorithm::{loops::flip, pervade::*},
     array::{Array, ArrayValue, Shape},
     cowslice::cowslice,
+    function::{Function, FunctionId, Instr, Signature},
     primitive::Primitive,
     run::{ArrayArg, FunctionArg},
     value::Value,
@@ -57,6 +58,27 @@ pub fn reduce(env: &mut Uiua) -> UiuaResult {
     Ok(())
 }
 
+/// Fused [`Primitive::Reduce`] [`Primitive::Add`]
+pub fn sum(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let xs = env.pop(ArrayArg(1))?;
+    match xs {
+        Value::Num(nums) => env.push(fast_reduce(nums, 0.0, add::num_num)),
+        Value::Byte(bytes) => env.push(fast_reduce(bytes.convert(), 0.0, add::num_num)),
+        xs => {
+            let add = Function::new(
+                FunctionId::Primitive(Primitive::Add),
+                vec![Instr::Prim(Primitive::Add, env.span_index())],
+                Signature::new(2, 1),
+            );
+            env.push(xs);
+            env.push(add);
+            return reduce(env);
+        }
+    }
+    Ok(())
+}
+
 pub fn fast_reduce<T>(mut arr: Array<T>, identity: T, f: impl Fn(T, T) -> T) -> Array<T>
 where
     T: ArrayValue + Copy,
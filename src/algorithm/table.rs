@@ -4,8 +4,12 @@ use ecow::EcoVec;
 use tinyvec::tiny_vec;
 
 use crate::{
-    algorithm::pervade::*,
+    algorithm::{
+        parallel::{par_call, should_parallelize},
+        pervade::*,
+    },
     array::{Array, ArrayValue, Shape},
+    cowslice::check_allocation,
     primitive::Primitive,
     run::{ArrayArg, FunctionArg},
     value::Value,
@@ -104,6 +108,7 @@ fn fast_table<A: ArrayValue, B: ArrayValue, C: ArrayValue>(
     b: Array<B>,
     f: impl Fn(A, B) -> C,
 ) -> Array<C> {
+    check_allocation::<C>(a.data.len().saturating_mul(b.data.len()));
     let mut new_data = EcoVec::with_capacity(a.data.len() * b.data.len());
     for x in a.data {
         for y in b.data.iter().cloned() {
@@ -116,6 +121,7 @@ fn fast_table<A: ArrayValue, B: ArrayValue, C: ArrayValue>(
 }
 
 fn fast_table_join_or_couple<T: ArrayValue>(a: Array<T>, b: Array<T>, flipped: bool) -> Array<T> {
+    check_allocation::<T>(a.data.len().saturating_mul(b.data.len()).saturating_mul(2));
     let mut new_data = EcoVec::with_capacity(a.data.len() * b.data.len() * 2);
     if flipped {
         for x in a.data {
@@ -147,17 +153,29 @@ fn generic_table(f: Value, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
     }
     let mut new_shape = Shape::from(xs.shape());
     new_shape.extend_from_slice(ys.shape());
-    let mut items = Value::builder(xs.flat_len() * ys.flat_len());
-    let y_values = ys.into_flat_values().collect::<Vec<_>>();
-    for x in xs.into_flat_values() {
-        for y in y_values.iter().cloned() {
-            env.push(y);
-            env.push(x.clone());
-            env.call_error_on_break(f.clone(), "break is not allowed in table")?;
-            let item = env.pop("tabled function result")?;
+    let call_count = xs.flat_len() * ys.flat_len();
+    let mut items = Value::builder(call_count);
+    if should_parallelize(&f, &[&xs, &ys], call_count, env) {
+        let y_values = ys.into_flat_values().collect::<Vec<_>>();
+        let groups = (xs.into_flat_values())
+            .flat_map(|x| y_values.iter().map(move |y| vec![x.clone(), y.clone()]))
+            .collect();
+        for item in par_call(&f, groups, env)? {
             item.validate_shape();
             items.add_row(item, &env)?;
         }
+    } else {
+        let y_values = ys.into_flat_values().collect::<Vec<_>>();
+        for x in xs.into_flat_values() {
+            for y in y_values.iter().cloned() {
+                env.push(y);
+                env.push(x.clone());
+                env.call_error_on_break(f.clone(), "break is not allowed in table")?;
+                let item = env.pop("tabled function result")?;
+                item.validate_shape();
+                items.add_row(item, &env)?;
+            }
+        }
     }
     let mut tabled = items.finish();
     new_shape.extend_from_slice(&tabled.shape()[1..]);
@@ -0,0 +1,237 @@
+//! Algorithms for modifiers applied to windows
+//!
+//! `≡f◫` and `/f◫` with a single window size are compiled to fused
+//! primitives that iterate over the windows without materializing them.
+
+use std::collections::VecDeque;
+
+use crate::{
+    algorithm::{pervade::*, reduce, zip},
+    array::{Array, ArrayValue},
+    cowslice::cowslice,
+    function::{Function, Instr},
+    primitive::Primitive,
+    run::FunctionArg,
+    value::Value,
+    Uiua, UiuaResult,
+};
+
+/// Fused [`Primitive::Rows`] of [`Primitive::Windows`]
+pub fn rows_windows(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let f = env.pop(FunctionArg(1))?;
+    let size = env.pop(1)?;
+    let xs = env.pop(2)?;
+    let Some(size) = row_window_size(&size, &xs, env)? else {
+        env.push(size.windows(&xs, env)?);
+        env.push(f);
+        return zip::rows(env);
+    };
+    let count = xs.row_count() + 1 - size;
+    let reduction = f.as_function().and_then(|f| fast_reduction(f));
+    match (reduction, &xs) {
+        (Some(prim), Value::Num(arr)) => env.push(sliding_reduce(prim, arr, size, count)),
+        (Some(prim), Value::Byte(arr)) => env.push(sliding_reduce(prim, arr, size, count)),
+        _ => {
+            let mut new_rows = Value::builder(count);
+            let mut windows = xs.row_windows(size);
+            for window in windows.by_ref() {
+                env.push(window);
+                let broke = env.call_catch_break(f.clone())?;
+                new_rows.add_row(env.pop("rows' function result")?, &env)?;
+                if broke {
+                    for window in windows {
+                        new_rows.add_row(window, &env)?;
+                    }
+                    break;
+                }
+            }
+            env.push(new_rows.finish());
+        }
+    }
+    Ok(())
+}
+
+/// Fused [`Primitive::Reduce`] of [`Primitive::Windows`]
+pub fn reduce_windows(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let f = env.pop(FunctionArg(1))?;
+    let size = env.pop(1)?;
+    let xs = env.pop(2)?;
+    let size = match row_window_size(&size, &xs, env)? {
+        Some(size) if !matches!(prim_reduction(&f), Some(None)) => size,
+        _ => {
+            env.push(size.windows(&xs, env)?);
+            env.push(f);
+            return reduce::reduce(env);
+        }
+    };
+    // Reducing the windows combines the same row of every window,
+    // which is itself a window over the rows
+    let count = xs.row_count() + 1 - size;
+    match (prim_reduction(&f).flatten(), &xs) {
+        (Some(prim), Value::Num(arr)) => env.push(sliding_reduce(prim, arr, count, size)),
+        (Some(prim), Value::Byte(arr)) => env.push(sliding_reduce(prim, arr, count, size)),
+        _ => {
+            let mut windows = xs.row_windows(size);
+            let mut acc = windows.next().unwrap();
+            for window in windows {
+                env.push(window);
+                env.push(acc);
+                let should_break = env.call_catch_break(f.clone())?;
+                acc = env.pop("reduced function result")?;
+                if should_break {
+                    break;
+                }
+            }
+            env.push(acc);
+        }
+    }
+    Ok(())
+}
+
+/// Get the window size if the windows can be iterated over without materializing them
+///
+/// Edge cases like empty arrays or windows are left to the normal implementation.
+fn row_window_size(size: &Value, xs: &Value, env: &Uiua) -> UiuaResult<Option<usize>> {
+    let size_spec = size.as_naturals(env, "Window size must be a list of natural numbers")?;
+    Ok(match *size_spec.as_slice() {
+        [size] if xs.rank() > 0 && xs.row_len() > 0 && (1..=xs.row_count()).contains(&size) => {
+            Some(size)
+        }
+        _ => None,
+    })
+}
+
+/// Get the primitive a function reduces a window with, if it has a sliding kernel
+fn fast_reduction(f: &Function) -> Option<Primitive> {
+    let prim = match f.instrs.as_slice() {
+        [Instr::Prim(Primitive::Sum, _)] => Primitive::Add,
+        [Instr::Push(g), Instr::Prim(Primitive::Reduce, _)] => {
+            match g.as_function()?.as_flipped_primitive()? {
+                (prim, false) => prim,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    has_kernel(prim).then_some(prim)
+}
+
+/// Get how a reducing function is handled
+///
+/// `None` means the function is not a primitive, `Some(None)` means
+/// that reduce has its own special handling for it, and `Some(Some(prim))`
+/// means it has a sliding kernel.
+fn prim_reduction(f: &Value) -> Option<Option<Primitive>> {
+    let (prim, flipped) = f.as_function()?.as_flipped_primitive()?;
+    Some((!flipped && has_kernel(prim)).then_some(prim))
+}
+
+fn has_kernel(prim: Primitive) -> bool {
+    matches!(prim, Primitive::Add | Primitive::Max | Primitive::Min)
+}
+
+/// Reduce windows of rows with a primitive, sliding the window along the rows
+///
+/// The results are the same as reducing each window on its own.
+fn sliding_reduce<T>(prim: Primitive, arr: &Array<T>, window: usize, count: usize) -> Array<f64>
+where
+    T: ArrayValue + Copy + Into<f64>,
+{
+    let f = match prim {
+        Primitive::Add => add::num_num,
+        Primitive::Max => max::num_num,
+        Primitive::Min => min::num_num,
+        _ => unreachable!("{prim} has no sliding kernel"),
+    };
+    let row_len = arr.row_len();
+    let x = |row: usize, i: usize| -> f64 { arr.data[row * row_len + i].into() };
+    // Sliding only gives the same results as reducing each window
+    // if the order of operations does not matter
+    let values_behave = arr.data.iter().all(|&x| {
+        let x: f64 = x.into();
+        !(x.is_nan() || x == 0.0 && x.is_sign_negative())
+    });
+    let can_slide = values_behave
+        && (prim != Primitive::Add
+            || arr.data.iter().all(|&x| {
+                let x: f64 = x.into();
+                x.fract() == 0.0 && x.abs() * (window + 1) as f64 <= MAX_EXACT_INT
+            }));
+    let mut data = cowslice![0.0; count * row_len];
+    let out = data.as_mut_slice();
+    for i in 0..row_len {
+        if !can_slide {
+            for j in 0..count {
+                let mut acc = x(j, i);
+                for k in 1..window {
+                    acc = f(acc, x(j + k, i));
+                }
+                out[j * row_len + i] = acc;
+            }
+        } else if prim == Primitive::Add {
+            let mut acc = x(0, i);
+            for k in 1..window {
+                acc = f(acc, x(k, i));
+            }
+            out[i] = acc;
+            for j in 1..count {
+                acc = acc + x(j + window - 1, i) - x(j - 1, i);
+                out[j * row_len + i] = acc;
+            }
+        } else {
+            // Rows that may still be the extreme value of some later window
+            let mut candidates = VecDeque::with_capacity(window);
+            for row in 0..count + window - 1 {
+                let val = x(row, i);
+                while (candidates.back()).is_some_and(|&back| f(val, x(back, i)) == val) {
+                    candidates.pop_back();
+                }
+                candidates.push_back(row);
+                if row + 1 >= window {
+                    let j = row + 1 - window;
+                    while candidates[0] < j {
+                        candidates.pop_front();
+                    }
+                    out[j * row_len + i] = x(candidates[0], i);
+                }
+            }
+        }
+    }
+    let mut shape = arr.shape.clone();
+    shape[0] = count;
+    Array::new(shape, data)
+}
+
+/// The largest magnitude below which all integers can be represented exactly
+const MAX_EXACT_INT: f64 = (1u64 << 53) as f64;
+
+impl Value {
+    /// Iterate over the windows of some size along the first axis
+    fn row_windows(&self, size: usize) -> Box<dyn ExactSizeIterator<Item = Self> + '_> {
+        match self {
+            Self::Num(array) => Box::new(array.row_windows(size).map(Value::from)),
+            Self::Byte(array) => Box::new(array.row_windows(size).map(Value::from)),
+            Self::Char(array) => Box::new(array.row_windows(size).map(Value::from)),
+            Self::Func(array) => Box::new(array.row_windows(size).map(Value::from)),
+        }
+    }
+}
+
+impl<T: ArrayValue> Array<T> {
+    /// Iterate over the windows of some size along the first axis
+    ///
+    /// The windows share this array's data rather than copying it.
+    fn row_windows(&self, size: usize) -> impl ExactSizeIterator<Item = Self> + '_ {
+        let row_len = self.row_len();
+        let mut shape = self.shape.clone();
+        shape[0] = size;
+        (0..self.row_count() + 1 - size).map(move |i| {
+            Array::new(
+                shape.clone(),
+                self.data.slice(i * row_len..(i + size) * row_len),
+            )
+        })
+    }
+}
@@ -1,7 +1,11 @@
 //! Algorithms for zipping modifiers
 
 use crate::{
-    algorithm::{loops::rank_to_depth, pervade::bin_pervade_generic},
+    algorithm::{
+        loops::rank_to_depth,
+        parallel::{par_call, should_parallelize},
+        pervade::bin_pervade_generic,
+    },
     array::{FormatShape, Shape},
     run::{ArrayArg, FunctionArg},
     value::Value,
@@ -56,20 +60,26 @@ pub fn each(env: &mut Uiua) -> UiuaResult {
 }
 
 fn each1_1(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
-    let mut new_values = Vec::with_capacity(xs.flat_len());
     let mut new_shape = Shape::from(xs.shape());
-    let mut old_values = xs.into_flat_values();
-    for val in old_values.by_ref() {
-        env.push(val);
-        let broke = env.call_catch_break(f.clone())?;
-        new_values.push(env.pop("each's function result")?);
-        if broke {
-            for row in old_values {
-                new_values.push(row);
+    let new_values = if should_parallelize(&f, &[&xs], xs.flat_len(), env) {
+        let groups = xs.into_flat_values().map(|x| vec![x]).collect();
+        par_call(&f, groups, env)?
+    } else {
+        let mut new_values = Vec::with_capacity(xs.flat_len());
+        let mut old_values = xs.into_flat_values();
+        for val in old_values.by_ref() {
+            env.push(val);
+            let broke = env.call_catch_break(f.clone())?;
+            new_values.push(env.pop("each's function result")?);
+            if broke {
+                for row in old_values {
+                    new_values.push(row);
+                }
+                break;
             }
-            break;
         }
-    }
+        new_values
+    };
     let mut eached = Value::from_row_values(new_values, env)?;
     new_shape.extend_from_slice(&eached.shape()[1..]);
     *eached.shape_mut() = new_shape;
@@ -89,6 +99,17 @@ fn each1_0(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
 }
 
 fn each2_1(f: Value, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
+    if xs.shape() == ys.shape() && should_parallelize(&f, &[&xs, &ys], xs.flat_len(), env) {
+        let mut shape = Shape::from(xs.shape());
+        let groups = (xs.into_flat_values().zip(ys.into_flat_values()))
+            .map(|(x, y)| vec![x, y])
+            .collect();
+        let mut eached = Value::from_row_values(par_call(&f, groups, env)?, env)?;
+        shape.extend_from_slice(&eached.shape()[1..]);
+        *eached.shape_mut() = shape;
+        env.push(eached);
+        return Ok(());
+    }
     let xs_shape = xs.shape().to_vec();
     let ys_shape = ys.shape().to_vec();
     let xs_values: Vec<_> = xs.into_flat_values().collect();
@@ -146,15 +167,29 @@ fn eachn_1(f: Value, args: Vec<Value>, env: &mut Uiua) -> UiuaResult {
         }
     }
     let elem_count = args[0].flat_len();
+    let parallel = should_parallelize(&f, &args.iter().collect::<Vec<_>>(), elem_count, env);
     let mut arg_elems: Vec<_> = args.into_iter().map(|v| v.into_flat_values()).collect();
-    let mut new_values = Vec::new();
-    for _ in 0..elem_count {
-        for arg in arg_elems.iter_mut().rev() {
-            env.push(arg.next().unwrap());
+    let new_values = if parallel {
+        let groups = (0..elem_count)
+            .map(|_| {
+                arg_elems
+                    .iter_mut()
+                    .map(|arg| arg.next().unwrap())
+                    .collect()
+            })
+            .collect();
+        par_call(&f, groups, env)?
+    } else {
+        let mut new_values = Vec::new();
+        for _ in 0..elem_count {
+            for arg in arg_elems.iter_mut().rev() {
+                env.push(arg.next().unwrap());
+            }
+            env.call_error_on_break(f.clone(), "break is not allowed in multi-argument each")?;
+            new_values.push(env.pop("each's function result")?);
         }
-        env.call_error_on_break(f.clone(), "break is not allowed in multi-argument each")?;
-        new_values.push(env.pop("each's function result")?);
-    }
+        new_values
+    };
     let eached = Value::from_row_values(new_values, env)?;
     env.push(eached);
     Ok(())
@@ -230,6 +265,12 @@ pub fn rows(env: &mut Uiua) -> UiuaResult {
 }
 
 fn rows1_1(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
+    if should_parallelize(&f, &[&xs], xs.row_count(), env) {
+        let groups = xs.into_rows().map(|x| vec![x]).collect();
+        let new_rows = par_call(&f, groups, env)?;
+        env.push(Value::from_row_values(new_rows, env)?);
+        return Ok(());
+    }
     let mut new_rows = Value::builder(xs.row_count());
     let mut old_rows = xs.into_rows();
     for row in old_rows.by_ref() {
@@ -266,6 +307,14 @@ fn rows2_1(f: Value, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
             ys.row_count()
         )));
     }
+    if should_parallelize(&f, &[&xs, &ys], xs.row_count(), env) {
+        let groups = (xs.into_rows().zip(ys.into_rows()))
+            .map(|(x, y)| vec![x, y])
+            .collect();
+        let new_rows = par_call(&f, groups, env)?;
+        env.push(Value::from_row_values(new_rows, env)?);
+        return Ok(());
+    }
     let mut new_rows = Vec::with_capacity(xs.row_count());
     let x_rows = xs.into_rows();
     let y_rows = ys.into_rows();
@@ -309,15 +358,29 @@ fn rowsn_1(f: Value, args: Vec<Value>, env: &mut Uiua) -> UiuaResult {
         }
     }
     let row_count = args[0].row_count();
+    let parallel = should_parallelize(&f, &args.iter().collect::<Vec<_>>(), row_count, env);
     let mut arg_elems: Vec<_> = args.into_iter().map(|v| v.into_rows()).collect();
-    let mut new_values = Vec::new();
-    for _ in 0..row_count {
-        for arg in arg_elems.iter_mut().rev() {
-            env.push(arg.next().unwrap());
+    let new_values = if parallel {
+        let groups = (0..row_count)
+            .map(|_| {
+                arg_elems
+                    .iter_mut()
+                    .map(|arg| arg.next().unwrap())
+                    .collect()
+            })
+            .collect();
+        par_call(&f, groups, env)?
+    } else {
+        let mut new_values = Vec::new();
+        for _ in 0..row_count {
+            for arg in arg_elems.iter_mut().rev() {
+                env.push(arg.next().unwrap());
+            }
+            env.call_error_on_break(f.clone(), "break is not allowed in multi-argument each")?;
+            new_values.push(env.pop("each's function result")?);
         }
-        env.call_error_on_break(f.clone(), "break is not allowed in multi-argument each")?;
-        new_values.push(env.pop("each's function result")?);
-    }
+        new_values
+    };
     let eached = Value::from_row_values(new_values, env)?;
     env.push(eached);
     Ok(())
@@ -0,0 +1,972 @@
+//! Serialization of compiled programs
+//!
+//! `uiua build` compiles a file and writes its top-level lines, along with the
+//! span table their instructions refer to, to a `.uac` file. Pure bindings are
+//! evaluated when the program is built. Bindings that have side effects, import
+//! files, or take their values from the stack are compiled into lines that bind
+//! them when the program is loaded, so that their effects happen on every run.
+//!
+//! Files that are imported with a constant path are compiled into the `.uac` file
+//! as well, and their lines are run when they are imported.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    mem::{replace, take},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    array::{Array, Shape},
+    check::instrs_signature,
+    cowslice::CowSlice,
+    function::{DynamicFunction, DynamicKind, Function, FunctionId, Instr, Signature},
+    lex::{CodeSpan, Loc, Span},
+    primitive::Primitive,
+    value::Value,
+    Ident, SysOp, Uiua, UiuaError, UiuaResult,
+};
+
+/// The bytes every compiled file starts with
+const MAGIC: &[u8] = b"UIUAC";
+/// The version of the compiled file format
+///
+/// Compiled files are also rejected if they were built by a different version of Uiua.
+const FORMAT_VERSION: usize = 3;
+
+/// The state of a program that is being built rather than run
+#[derive(Default, Clone)]
+pub(crate) struct Build {
+    /// The compiled top-level lines
+    pub lines: Vec<Vec<Instr>>,
+    /// The globals that are bound when the program is loaded
+    pub deferred: Vec<usize>,
+    /// Whether every binding is deferred, so that nothing is run at all
+    ///
+    /// This is used to check code rather than to build it.
+    pub check: bool,
+    /// The compiled top-level lines of imported files, by import path
+    pub modules: Vec<(String, Vec<Vec<Instr>>)>,
+    /// The paths and hashes of the source files, so that stale builds can be detected
+    pub sources: Vec<(PathBuf, u64)>,
+}
+
+impl Uiua {
+    /// Compile a Uiua file into bytes that can be run with [`Uiua::load_bytecode`]
+    ///
+    /// Pure bindings are evaluated, but nothing else is run.
+    /// Other bindings are bound when the program is loaded.
+    pub fn build_file<P: AsRef<Path>>(&mut self, path: P) -> UiuaResult<Vec<u8>> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path).map_err(|e| UiuaError::Load(path.into(), e.into()))?;
+        self.build = Some(Build {
+            sources: vec![(source_path(path), hash(input.as_bytes()))],
+            ..Build::default()
+        });
+        let res = self.load_str_path(&input, path);
+        let build = self.build.take().unwrap_or_default();
+        res?;
+        let mut encoder = Encoder {
+            globals: (build.deferred.iter().enumerate())
+                .map(|(slot, &index)| (index, slot))
+                .collect(),
+            ..Encoder::default()
+        };
+        for span in self.spans.lock().iter() {
+            encoder.span(span);
+        }
+        encoder.usize(build.deferred.len());
+        encoder.usize(build.lines.len());
+        for instrs in &build.lines {
+            encoder.instrs(instrs).map_err(|e| self.error(e))?;
+        }
+        encoder.usize(build.modules.len());
+        for (import_path, lines) in &build.modules {
+            encoder.str(import_path);
+            encoder.usize(lines.len());
+            for instrs in lines {
+                encoder.instrs(instrs).map_err(|e| self.error(e))?;
+            }
+        }
+        let mut bytes = MAGIC.to_vec();
+        let mut header = Encoder::default();
+        header.usize(FORMAT_VERSION);
+        header.str(env!("CARGO_PKG_VERSION"));
+        header.usize(build.sources.len());
+        for (path, hash) in &build.sources {
+            header.str(&path.to_string_lossy());
+            header.u64(*hash);
+        }
+        header.usize(encoder.inputs.len());
+        for (path, input) in &encoder.inputs {
+            header.bool(path.is_some());
+            if let Some(path) = path {
+                header.str(&path.to_string_lossy());
+            }
+            header.str(input);
+        }
+        header.usize(self.spans.lock().len());
+        bytes.extend(header.bytes);
+        bytes.extend(encoder.bytes);
+        Ok(bytes)
+    }
+    /// Run a program compiled with [`Uiua::build_file`]
+    ///
+    /// Compiled files that were built by a different version of Uiua, or whose
+    /// source file has changed since they were built, are rejected.
+    pub fn load_bytecode<P: AsRef<Path>>(&mut self, bytes: &[u8], path: P) -> UiuaResult {
+        let path = path.as_ref();
+        let load_error =
+            |message: String| UiuaError::Load(path.into(), io::Error::other(message).into());
+        let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+            return Err(load_error("not a compiled Uiua file".into()));
+        };
+        let span_offset = self.spans.lock().len();
+        let global_offset = self.globals.lock().len();
+        let mut decoder = Decoder {
+            bytes,
+            prims: Primitive::all().collect(),
+            inputs: Vec::new(),
+            span_offset,
+            span_count: 0,
+            global_offset,
+            global_count: 0,
+        };
+        let (spans, lines, modules) = decoder.program().map_err(load_error)?;
+        self.spans.lock().extend(spans);
+        self.built_imports.extend(modules);
+        // Make room for the globals that the program binds as it runs
+        (self.globals.lock()).resize(global_offset + decoder.global_count, Value::default());
+        let input = (decoder.inputs.first()).map_or_else(|| "".into(), |(_, input)| input.clone());
+        self.execution_start = instant::now();
+        self.catch_crash(&input, |env| env.run_built_lines(&lines))
+    }
+    /// Run the top-level lines of a built program or imported file
+    ///
+    /// References to globals that are bound when the program is loaded are
+    /// replaced with their values first, so that they can be called like any
+    /// other binding. Lines that bind globals are bound the way the compiler
+    /// would have bound them.
+    pub(crate) fn run_built_lines(&mut self, lines: &[Vec<Instr>]) -> UiuaResult {
+        for line in lines {
+            let mut instrs = resolve_globals(line, &self.globals.lock());
+            let bind = match instrs.last() {
+                Some(Instr::Dynamic(DynamicFunction {
+                    kind: Some(DynamicKind::BindGlobal(index, name, sig)),
+                    ..
+                })) => Some((*index, name.clone(), *sig)),
+                _ => None,
+            };
+            if let Some((index, name, sig)) = bind {
+                instrs.pop();
+                self.bind_built(index, name, sig, instrs)?;
+            } else {
+                self.exec_global_instrs(instrs)?;
+            }
+        }
+        Ok(())
+    }
+    fn bind_built(
+        &mut self,
+        index: usize,
+        name: Ident,
+        declared: Option<Signature>,
+        instrs: Vec<Instr>,
+    ) -> UiuaResult {
+        let sig = match (instrs_signature(&instrs), declared) {
+            (Ok(sig), Some(declared)) if sig != declared => {
+                return Err(self.error(format!(
+                    "Function signature mismatch: declared {declared} but inferred {sig}"
+                )))
+            }
+            (Ok(sig), _) => sig,
+            (Err(_), Some(declared)) => declared,
+            (Err(e), None) => {
+                return Err(self.error(format!("Cannot infer function signature: {e}")))
+            }
+        };
+        let mut value = if sig.args == 0 && (sig.outputs > 0 || instrs.is_empty()) {
+            self.exec_global_instrs(instrs)?;
+            match self.stack.pop() {
+                Some(Value::Func(fs)) => match fs.into_scalar() {
+                    Ok(mut f) => {
+                        Arc::make_mut(&mut f).id = FunctionId::Named(name);
+                        f.into()
+                    }
+                    Err(fs) => fs.into(),
+                },
+                Some(value) => value,
+                None => Function::new(FunctionId::Named(name), Vec::new(), sig).into(),
+            }
+        } else {
+            Function::new(FunctionId::Named(name), instrs, sig).into()
+        };
+        value.compress();
+        self.globals.lock()[index] = value;
+        Ok(())
+    }
+    /// Compile the files that some instructions import with a constant path
+    /// into the program that is being built
+    pub(crate) fn build_imports(&mut self, instrs: &[Instr]) -> UiuaResult {
+        for path in import_paths(instrs) {
+            let build = self.build.as_ref().unwrap();
+            if build.modules.iter().any(|(built, _)| *built == path) {
+                continue;
+            }
+            if self.current_imports.lock().contains(Path::new(&path)) {
+                return Err(self.error(format!("Cycle detected importing {path}")));
+            }
+            let input = match self.backend.file_read_all(&path) {
+                Ok(bytes) => String::from_utf8(bytes)
+                    .map_err(|e| self.error(format!("Failed to read file: {e}")))?,
+                // The example file is not a real file, so it is loaded when it is imported
+                Err(_) if path == "example.ua" => continue,
+                Err(e) => return Err(self.error(e)),
+            };
+            let build = self.build.as_mut().unwrap();
+            (build.sources).push((source_path(Path::new(&path)), hash(input.as_bytes())));
+            let lines = take(&mut build.lines);
+            let res = self.in_scope(false, |env| {
+                env.load_str_path(&input, Path::new(&path)).map(drop)
+            });
+            let build = self.build.as_mut().unwrap();
+            let module = replace(&mut build.lines, lines);
+            res?;
+            build.modules.push((path, module));
+        }
+        Ok(())
+    }
+}
+
+/// Replace references to globals that are bound when a built program is loaded
+/// with the globals' values
+fn resolve_globals(instrs: &[Instr], globals: &[Value]) -> Vec<Instr> {
+    let mut resolved = Vec::with_capacity(instrs.len());
+    for instr in instrs {
+        match instr {
+            Instr::Dynamic(DynamicFunction {
+                kind: Some(DynamicKind::LoadGlobal(index, call)),
+                ..
+            }) => {
+                let value = globals[*index].clone();
+                let should_call = matches!(&value, Value::Func(f) if f.shape.is_empty());
+                resolved.push(Instr::push(value));
+                if let (true, Some(span)) = (should_call, call) {
+                    resolved.push(Instr::Call(*span));
+                }
+            }
+            Instr::Push(val) if loads_globals(std::slice::from_ref(instr)) => {
+                let mut val = (**val).clone();
+                if let Value::Func(fs) = &mut val {
+                    for f in fs.data.as_mut_slice() {
+                        let instrs = resolve_globals(&f.instrs, globals);
+                        // Now that the globals are known, the signature may be too
+                        let sig = instrs_signature(&instrs).unwrap_or(f.signature());
+                        *f = Arc::new(Function::new(f.id.clone(), instrs, sig));
+                    }
+                }
+                resolved.push(Instr::push(val));
+            }
+            instr => resolved.push(instr.clone()),
+        }
+    }
+    resolved
+}
+
+/// Check if some instructions refer to globals that are bound when a built program is loaded
+pub(crate) fn loads_globals(instrs: &[Instr]) -> bool {
+    instrs.iter().any(|instr| match instr {
+        Instr::Push(val) => match &**val {
+            Value::Func(fs) => fs.data.iter().any(|f| loads_globals(&f.instrs)),
+            _ => false,
+        },
+        Instr::Dynamic(df) => matches!(df.kind, Some(DynamicKind::LoadGlobal(..))),
+        _ => false,
+    })
+}
+
+/// Get the paths that some instructions import as constants
+fn import_paths(instrs: &[Instr]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for (i, instr) in instrs.iter().enumerate() {
+        match instr {
+            Instr::Prim(Primitive::Sys(SysOp::Import), _) => {
+                let path = i.checked_sub(1).and_then(|i| instrs[i].as_push());
+                if let Some(Value::Char(arr)) = path {
+                    if arr.rank() == 1 {
+                        paths.push(arr.data.iter().collect());
+                    }
+                }
+            }
+            Instr::Push(val) => {
+                if let Value::Func(fs) = &**val {
+                    for f in fs.data.iter() {
+                        paths.extend(import_paths(&f.instrs));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    paths
+}
+
+/// Check if executing some instructions could have side effects
+/// or depend on globals that are bound when a built program is loaded
+pub(crate) fn has_side_effects(instrs: &[Instr]) -> bool {
+    instrs.iter().any(|instr| match instr {
+        Instr::Push(val) => match &**val {
+            Value::Func(fs) => fs.data.iter().any(|f| has_side_effects(&f.instrs)),
+            _ => false,
+        },
+        Instr::Prim(Primitive::Break | Primitive::Recur, _) => false,
+        Instr::Prim(prim, _) => !prim.is_pure(),
+        Instr::Dynamic(df) => !matches!(df.kind, Some(DynamicKind::Format(_))),
+        _ => false,
+    })
+}
+
+/// The path to record for a source file
+fn source_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.into())
+}
+
+/// A stable FNV-1a hash of a source file's contents
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+    prims: HashMap<Primitive, usize>,
+    /// The paths and contents of the code that spans refer to
+    inputs: Vec<(Option<Arc<Path>>, Arc<str>)>,
+    input_indices: HashMap<*const u8, usize>,
+    /// The slots of globals that are bound when the program is loaded, by index
+    globals: HashMap<usize, usize>,
+}
+
+impl Encoder {
+    fn u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+    fn usize(&mut self, mut n: usize) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.bytes.push(byte);
+                break;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+    fn u64(&mut self, n: u64) {
+        self.bytes.extend(n.to_le_bytes());
+    }
+    fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+    fn str(&mut self, s: &str) {
+        self.usize(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+    fn prim(&mut self, prim: Primitive) {
+        if self.prims.is_empty() {
+            self.prims = Primitive::all().enumerate().map(|(i, p)| (p, i)).collect();
+        }
+        self.usize(self.prims[&prim]);
+    }
+    fn span(&mut self, span: &Span) {
+        match span {
+            Span::Code(span) => {
+                self.u8(0);
+                self.code_span(span);
+            }
+            Span::Builtin => self.u8(1),
+        }
+    }
+    fn code_span(&mut self, span: &CodeSpan) {
+        let key = span.input.as_ptr();
+        let input = if let Some(&input) = self.input_indices.get(&key) {
+            input
+        } else {
+            self.inputs.push((span.path.clone(), span.input.clone()));
+            self.input_indices.insert(key, self.inputs.len() - 1);
+            self.inputs.len() - 1
+        };
+        self.usize(input);
+        for loc in [&span.start, &span.end] {
+            self.usize(loc.char_pos);
+            self.usize(loc.byte_pos);
+            self.usize(loc.line);
+            self.usize(loc.col);
+        }
+    }
+    fn value(&mut self, val: &Value) -> Result<(), String> {
+        let shape = val.shape();
+        match val {
+            Value::Num(_) => self.u8(0),
+            Value::Byte(_) => self.u8(1),
+            Value::Char(_) => self.u8(2),
+            Value::Func(_) => self.u8(3),
+        }
+        self.usize(shape.len());
+        for &dim in shape {
+            self.usize(dim);
+        }
+        match val {
+            Value::Num(arr) => {
+                for n in arr.data.iter() {
+                    self.u64(n.to_bits());
+                }
+            }
+            Value::Byte(arr) => self.bytes.extend(arr.data.iter()),
+            Value::Char(arr) => {
+                for &c in arr.data.iter() {
+                    self.usize(c as usize);
+                }
+            }
+            Value::Func(arr) => {
+                for f in arr.data.iter() {
+                    self.function(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn function(&mut self, f: &Function) -> Result<(), String> {
+        self.function_id(&f.id);
+        let sig = f.signature();
+        self.usize(sig.args);
+        self.usize(sig.outputs);
+        self.instrs(&f.instrs)
+    }
+    fn function_id(&mut self, id: &FunctionId) {
+        match id {
+            FunctionId::Named(name) => {
+                self.u8(0);
+                self.str(name);
+            }
+            FunctionId::Anonymous(span) => {
+                self.u8(1);
+                self.code_span(span);
+            }
+            FunctionId::Primitive(prim) => {
+                self.u8(2);
+                self.prim(*prim);
+            }
+            FunctionId::Constant => self.u8(3),
+            FunctionId::Main => self.u8(4),
+            FunctionId::Composed(ids) => {
+                self.u8(5);
+                self.usize(ids.len());
+                for id in ids {
+                    self.function_id(id);
+                }
+            }
+        }
+    }
+    fn instrs(&mut self, instrs: &[Instr]) -> Result<(), String> {
+        self.usize(instrs.len());
+        for instr in instrs {
+            self.instr(instr)?;
+        }
+        Ok(())
+    }
+    fn instr(&mut self, instr: &Instr) -> Result<(), String> {
+        match instr {
+            Instr::Push(val) => {
+                self.u8(0);
+                self.value(val)?;
+            }
+            Instr::BeginArray => self.u8(1),
+            Instr::EndArray { boxed, span } => {
+                self.u8(2);
+                self.bool(*boxed);
+                self.usize(*span);
+            }
+            Instr::Prim(prim, span) => {
+                self.u8(3);
+                self.prim(*prim);
+                self.usize(*span);
+            }
+            Instr::Call(span) => {
+                self.u8(4);
+                self.usize(*span);
+            }
+            Instr::Dynamic(df) => match &df.kind {
+                Some(DynamicKind::Format(lines)) => {
+                    self.u8(5);
+                    self.usize(lines.len());
+                    for line in lines.iter() {
+                        self.usize(line.len());
+                        for frag in line {
+                            self.str(frag);
+                        }
+                    }
+                }
+                Some(DynamicKind::LoadGlobal(index, call)) => {
+                    self.u8(12);
+                    self.usize(self.global_slot(*index)?);
+                    self.bool(call.is_some());
+                    if let Some(span) = call {
+                        self.usize(*span);
+                    }
+                }
+                Some(DynamicKind::BindGlobal(index, name, sig)) => {
+                    self.u8(13);
+                    self.usize(self.global_slot(*index)?);
+                    self.str(name);
+                    self.bool(sig.is_some());
+                    if let Some(sig) = sig {
+                        self.usize(sig.args);
+                        self.usize(sig.outputs);
+                    }
+                }
+                None => return Err(format!("Cannot compile dynamic function {df:?}")),
+            },
+            Instr::PushTempUnder { count, span } => {
+                self.u8(6);
+                self.usize(*count);
+                self.usize(*span);
+            }
+            Instr::PopTempUnder { count, span } => {
+                self.u8(7);
+                self.usize(*count);
+                self.usize(*span);
+            }
+            Instr::PushTempInline { count, span } => {
+                self.u8(8);
+                self.usize(*count);
+                self.usize(*span);
+            }
+            Instr::PopTempInline { count, span } => {
+                self.u8(9);
+                self.usize(*count);
+                self.usize(*span);
+            }
+            Instr::CopyTempInline {
+                offset,
+                count,
+                span,
+            } => {
+                self.u8(10);
+                self.usize(*offset);
+                self.usize(*count);
+                self.usize(*span);
+            }
+            Instr::DropTempInline { count, span } => {
+                self.u8(11);
+                self.usize(*count);
+                self.usize(*span);
+            }
+        }
+        Ok(())
+    }
+    fn global_slot(&self, index: usize) -> Result<usize, String> {
+        (self.globals.get(&index).copied())
+            .ok_or_else(|| format!("Cannot compile a reference to global {index}"))
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    prims: Vec<Primitive>,
+    inputs: Vec<(Option<Arc<Path>>, Arc<str>)>,
+    /// Where the decoded span table starts in the runtime's span table
+    span_offset: usize,
+    span_count: usize,
+    /// Where the globals bound by the program start in the runtime's globals
+    global_offset: usize,
+    global_count: usize,
+}
+
+type DecodeResult<T> = Result<T, String>;
+/// The import path and top-level lines of an imported file
+type BuiltImport = (String, Arc<[Vec<Instr>]>);
+/// The span table, top-level lines, and imported files of a compiled file
+type Program = (Vec<Span>, Vec<Vec<Instr>>, Vec<BuiltImport>);
+
+impl<'a> Decoder<'a> {
+    /// Decode the span table, top-level lines, and imported files' lines
+    /// of a compiled file after its magic bytes
+    fn program(&mut self) -> DecodeResult<Program> {
+        let format_version = self.usize()?;
+        let version = self.str()?;
+        if format_version != FORMAT_VERSION || version != env!("CARGO_PKG_VERSION") {
+            return Err(format!(
+                "it was built with Uiua {version}, but this is Uiua {}, \
+                so it must be rebuilt with `uiua build`",
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+        for _ in 0..self.usize()? {
+            let path = PathBuf::from(self.str()?);
+            let hash = self.u64()?;
+            if fs::read(&path).is_ok_and(|bytes| self::hash(&bytes) != hash) {
+                return Err(format!(
+                    "{} has changed since it was built, \
+                    so it must be rebuilt with `uiua build`",
+                    path.display()
+                ));
+            }
+        }
+        for _ in 0..self.usize()? {
+            let path = if self.bool()? {
+                Some(Path::new(&self.str()?).into())
+            } else {
+                None
+            };
+            let input = self.str()?.into();
+            self.inputs.push((path, input));
+        }
+        self.span_count = self.usize()?;
+        let mut spans = Vec::new();
+        for _ in 0..self.span_count {
+            spans.push(self.span()?);
+        }
+        self.global_count = self.usize()?;
+        let mut lines = Vec::new();
+        for _ in 0..self.usize()? {
+            lines.push(self.instrs()?);
+        }
+        let mut modules = Vec::new();
+        for _ in 0..self.usize()? {
+            let path = self.str()?;
+            let module = (0..self.usize()?)
+                .map(|_| self.instrs())
+                .collect::<DecodeResult<Vec<_>>>()?;
+            modules.push((path, module.into()));
+        }
+        if !self.bytes.is_empty() {
+            return Err("the file has trailing data".into());
+        }
+        Ok((spans, lines, modules))
+    }
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        if self.bytes.len() < n {
+            return Err("the file is truncated".into());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn u8(&mut self) -> DecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn usize(&mut self) -> DecodeResult<usize> {
+        let mut n = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.u8()?;
+            n |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err("invalid integer".into())
+    }
+    fn u64(&mut self) -> DecodeResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn bool(&mut self) -> DecodeResult<bool> {
+        Ok(self.u8()? != 0)
+    }
+    fn str(&mut self) -> DecodeResult<String> {
+        let len = self.usize()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "invalid string".into())
+    }
+    fn prim(&mut self) -> DecodeResult<Primitive> {
+        let index = self.usize()?;
+        (self.prims.get(index).copied()).ok_or_else(|| "invalid primitive".into())
+    }
+    fn span_index(&mut self) -> DecodeResult<usize> {
+        match self.usize()? {
+            index if index < self.span_count => Ok(index + self.span_offset),
+            _ => Err("invalid span".into()),
+        }
+    }
+    fn global_index(&mut self) -> DecodeResult<usize> {
+        match self.usize()? {
+            slot if slot < self.global_count => Ok(slot + self.global_offset),
+            _ => Err("invalid global".into()),
+        }
+    }
+    fn span(&mut self) -> DecodeResult<Span> {
+        Ok(match self.u8()? {
+            0 => Span::Code(self.code_span()?),
+            1 => Span::Builtin,
+            _ => return Err("invalid span".into()),
+        })
+    }
+    fn code_span(&mut self) -> DecodeResult<CodeSpan> {
+        let index = self.usize()?;
+        let (path, input) =
+            (self.inputs.get(index).cloned()).ok_or_else(|| "invalid span".to_string())?;
+        let mut loc = || -> DecodeResult<Loc> {
+            Ok(Loc {
+                char_pos: self.usize()?,
+                byte_pos: self.usize()?,
+                line: self.usize()?,
+                col: self.usize()?,
+            })
+        };
+        Ok(CodeSpan {
+            start: loc()?,
+            end: loc()?,
+            path,
+            input,
+        })
+    }
+    fn value(&mut self) -> DecodeResult<Value> {
+        let ty = self.u8()?;
+        let rank = self.usize()?;
+        let mut shape = Shape::new();
+        for _ in 0..rank {
+            shape.push(self.usize()?);
+        }
+        let len = (shape.iter())
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            .ok_or_else(|| "invalid shape".to_string())?;
+        Ok(match ty {
+            0 => {
+                let data: CowSlice<f64> = (0..len)
+                    .map(|_| self.u64().map(f64::from_bits))
+                    .collect::<DecodeResult<_>>()?;
+                Array::new(shape, data).into()
+            }
+            1 => Array::new(
+                shape,
+                self.take(len)?.iter().copied().collect::<CowSlice<_>>(),
+            )
+            .into(),
+            2 => {
+                let data: CowSlice<char> = (0..len)
+                    .map(|_| {
+                        let c = self.usize()?;
+                        (u32::try_from(c).ok().and_then(char::from_u32))
+                            .ok_or_else(|| "invalid character".to_string())
+                    })
+                    .collect::<DecodeResult<_>>()?;
+                Array::new(shape, data).into()
+            }
+            3 => {
+                let data: CowSlice<Arc<Function>> = (0..len)
+                    .map(|_| self.function().map(Arc::new))
+                    .collect::<DecodeResult<_>>()?;
+                Array::new(shape, data).into()
+            }
+            _ => return Err("invalid value".into()),
+        })
+    }
+    fn function(&mut self) -> DecodeResult<Function> {
+        let id = self.function_id()?;
+        let sig = Signature::new(self.usize()?, self.usize()?);
+        let instrs = self.instrs()?;
+        Ok(Function::new(id, instrs, sig))
+    }
+    fn function_id(&mut self) -> DecodeResult<FunctionId> {
+        Ok(match self.u8()? {
+            0 => FunctionId::Named(self.str()?.into()),
+            1 => FunctionId::Anonymous(self.code_span()?),
+            2 => FunctionId::Primitive(self.prim()?),
+            3 => FunctionId::Constant,
+            4 => FunctionId::Main,
+            5 => {
+                let len = self.usize()?;
+                let ids = (0..len)
+                    .map(|_| self.function_id())
+                    .collect::<DecodeResult<_>>()?;
+                FunctionId::Composed(ids)
+            }
+            _ => return Err("invalid function id".into()),
+        })
+    }
+    fn instrs(&mut self) -> DecodeResult<Vec<Instr>> {
+        let len = self.usize()?;
+        (0..len).map(|_| self.instr()).collect()
+    }
+    fn instr(&mut self) -> DecodeResult<Instr> {
+        Ok(match self.u8()? {
+            0 => Instr::Push(self.value()?.into()),
+            1 => Instr::BeginArray,
+            2 => Instr::EndArray {
+                boxed: self.bool()?,
+                span: self.span_index()?,
+            },
+            3 => Instr::Prim(self.prim()?, self.span_index()?),
+            4 => Instr::Call(self.span_index()?),
+            5 => {
+                let mut lines = Vec::new();
+                for _ in 0..self.usize()? {
+                    let len = self.usize()?;
+                    let line = (0..len).map(|_| self.str()).collect::<DecodeResult<_>>()?;
+                    lines.push(line);
+                }
+                if lines.is_empty() || lines.iter().any(Vec::is_empty) {
+                    return Err("invalid format string".into());
+                }
+                Instr::Dynamic(DynamicFunction::format_string(lines))
+            }
+            6 => Instr::PushTempUnder {
+                count: self.usize()?,
+                span: self.span_index()?,
+            },
+            7 => Instr::PopTempUnder {
+                count: self.usize()?,
+                span: self.span_index()?,
+            },
+            8 => Instr::PushTempInline {
+                count: self.usize()?,
+                span: self.span_index()?,
+            },
+            9 => Instr::PopTempInline {
+                count: self.usize()?,
+                span: self.span_index()?,
+            },
+            10 => Instr::CopyTempInline {
+                offset: self.usize()?,
+                count: self.usize()?,
+                span: self.span_index()?,
+            },
+            11 => Instr::DropTempInline {
+                count: self.usize()?,
+                span: self.span_index()?,
+            },
+            12 => {
+                let index = self.global_index()?;
+                let call = if self.bool()? {
+                    Some(self.span_index()?)
+                } else {
+                    None
+                };
+                Instr::Dynamic(DynamicFunction::load_global(index, call))
+            }
+            13 => {
+                let index = self.global_index()?;
+                let name = self.str()?.into();
+                let sig = if self.bool()? {
+                    Some(Signature::new(self.usize()?, self.usize()?))
+                } else {
+                    None
+                };
+                Instr::Dynamic(DynamicFunction::bind_global(index, name, sig))
+            }
+            _ => return Err("invalid instruction".into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("uiua-build-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.ua");
+        let code = "\
+            F ← |1 /+◫2\n\
+            X ← F [1 2 3 4]\n\
+            G ← $\"_ and _\"\n\
+            G X ⇌X\n\
+            ---\n\
+            Y ← ⊟.@a\n\
+            Y\n\
+            ---\n\
+            {1 2_3} ∵(×10) X";
+        fs::write(&path, code).unwrap();
+
+        let mut env = Uiua::with_native_sys();
+        env.load_file(&path).unwrap();
+        let expected = env.take_stack();
+        let bytes = Uiua::with_native_sys().build_file(&path).unwrap();
+        let mut env = Uiua::with_native_sys();
+        env.load_bytecode(&bytes, "main.uac").unwrap();
+        assert_eq!(env.take_stack(), expected);
+
+        fs::write(&path, format!("{code}\n1")).unwrap();
+        let err = (Uiua::with_native_sys().load_bytecode(&bytes, "main.uac")).unwrap_err();
+        assert!(err.to_string().contains("has changed"), "{err}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn build_with_effects() {
+        let dir = std::env::temp_dir().join(format!("uiua-build-effects-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        fs::write(
+            file("lib.ua"),
+            format!("&fwa {:?} \"loaded\"\n[1 2 3]", file("marker.txt")),
+        )
+        .unwrap();
+        fs::write(file("data.txt"), "old").unwrap();
+        let path = dir.join("main.ua");
+        let code = format!(
+            "\
+            Lib ← &i {:?}\n\
+            Data ← &fras {:?}\n\
+            10\n\
+            Ten ←\n\
+            F ← |1 +Ten +⧻Data\n\
+            F /+Lib",
+            file("lib.ua"),
+            file("data.txt")
+        );
+        fs::write(&path, code).unwrap();
+
+        // Imported files are compiled but not run when the program is built
+        let bytes = Uiua::with_native_sys().build_file(&path).unwrap();
+        assert!(!dir.join("marker.txt").exists());
+        fs::write(file("data.txt"), "newer").unwrap();
+
+        let mut env = Uiua::with_native_sys();
+        env.load_bytecode(&bytes, "main.uac").unwrap();
+        assert_eq!(env.take_stack(), [Value::from(21.0)]);
+        assert!(dir.join("marker.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn build_with_use() {
+        let dir = std::env::temp_dir().join(format!("uiua-build-use-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let lib = dir.join("lib.ua");
+        fs::write(&lib, "Double ← +.\nSquare ← ×.\nDouble_Square").unwrap();
+        let path = dir.join("main.ua");
+        let code = format!(
+            "\
+            ex ← &i {:?}\n\
+            Double ← use \"Double\" ex\n\
+            Quad ← Double Double\n\
+            F ← |1 +1 Quad\n\
+            Double 5\n\
+            ∵F [1 2]",
+            lib.to_string_lossy()
+        );
+        fs::write(&path, code).unwrap();
+
+        let mut env = Uiua::with_native_sys();
+        env.load_file(&path).unwrap();
+        let expected = env.take_stack();
+        let bytes = Uiua::with_native_sys().build_file(&path).unwrap();
+        let lib_code = fs::read_to_string(&lib).unwrap();
+
+        // The imported file is compiled into the program
+        fs::remove_file(&lib).unwrap();
+        let mut env = Uiua::with_native_sys();
+        env.load_bytecode(&bytes, "main.uac").unwrap();
+        assert_eq!(env.take_stack(), expected);
+
+        fs::write(&lib, format!("{lib_code}\n1")).unwrap();
+        let err = (Uiua::with_native_sys().load_bytecode(&bytes, "main.uac")).unwrap_err();
+        assert!(err.to_string().contains("lib.ua has changed"), "{err}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
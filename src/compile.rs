@@ -1,18 +1,17 @@
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-    sync::Arc,
-};
+use std::sync::Arc;
 
 use crate::{
     algorithm::invert::under_instrs,
     array::Array,
     ast::*,
+    bytecode::{has_side_effects, loads_globals},
     check::instrs_signature,
     function::*,
+    infer::{infer_types, InferredType},
     lex::{CodeSpan, Sp, Span},
     primitive::Primitive,
     run::RunMode,
+    testing::TestResult,
     value::Value,
     Diagnostic, DiagnosticKind, Ident, SysOp, UiuaError, UiuaResult,
 };
@@ -56,8 +55,22 @@ impl Uiua {
             }
         }
         match item {
+            Item::Scoped { items, test: true }
+                if self.collect_tests && self.mode != RunMode::Normal =>
+            {
+                let scope_stack = self.in_scope(true, |env| {
+                    env.scope.test_height = Some(env.stack.len());
+                    env.test_scope(items)
+                })?;
+                self.stack.extend(scope_stack);
+            }
             Item::Scoped { items, test } => {
-                let scope_stack = self.in_scope(true, |env| env.items(items, test))?;
+                let scope_stack = self.in_scope(true, |env| {
+                    if test {
+                        env.scope.test_height = Some(env.stack.len());
+                    }
+                    env.items(items, test)
+                })?;
                 self.stack.extend(scope_stack);
             }
             Item::Words(words) => {
@@ -68,7 +81,11 @@ impl Uiua {
                 };
                 if can_run || words_have_import(&words) || words_are_export(&words) {
                     let instrs = self.compile_words(words, true)?;
-                    self.exec_global_instrs(instrs)?;
+                    if let Some(build) = &mut self.build {
+                        build.lines.push(instrs);
+                    } else {
+                        self.exec_global_instrs(instrs)?;
+                    }
                 }
             }
             Item::Binding(binding) => {
@@ -84,6 +101,110 @@ impl Uiua {
         }
         Ok(())
     }
+    /// Run a test scope, recording a result for each test case in it
+    fn test_scope(&mut self, items: Vec<Item>) -> UiuaResult {
+        fn is_comment(words: &[Sp<Word>]) -> bool {
+            words
+                .iter()
+                .all(|w| matches!(w.value, Word::Comment(_) | Word::Spaces))
+        }
+        fn has_assert(words: &[Sp<Word>]) -> bool {
+            words
+                .iter()
+                .any(|w| matches!(w.value, Word::Primitive(Primitive::Assert)))
+        }
+        fn words_span(words: &[Sp<Word>]) -> Option<CodeSpan> {
+            let first = words.first()?.span.clone();
+            Some(first.merge(words.last()?.span.clone()))
+        }
+        // The scope is named by its first comment, or else its first line
+        let first_span = items.iter().find_map(|item| match item {
+            Item::Words(words) if !is_comment(words) => words_span(words),
+            Item::Binding(binding) => Some(binding.name.span.clone()),
+            _ => None,
+        });
+        let scope_name = items
+            .iter()
+            .find_map(|item| match item {
+                Item::Words(words) => words.iter().find_map(|w| match &w.value {
+                    Word::Comment(comment) => Some(comment.trim().to_string()),
+                    _ => None,
+                }),
+                _ => None,
+            })
+            .or_else(|| {
+                (first_span.as_ref()).map(|span| format!("test at line {}", span.start.line))
+            })
+            .unwrap_or_else(|| "empty test".into());
+        let scope_span: Span = first_span.map_or(Span::Builtin, Into::into);
+        let filter = self.test_filter.clone();
+        let matches = |name: &str| match &filter {
+            Some(filter) => name.contains(filter.as_str()),
+            None => true,
+        };
+        let case_name = |words: &[Sp<Word>]| {
+            let line = words_span(words).map_or(String::new(), |span| span.as_str().into());
+            format!("{scope_name}: {}", line.trim())
+        };
+        let scope_matches = matches(&scope_name)
+            || items.iter().any(|item| match item {
+                Item::Words(words) => has_assert(words) && matches(&case_name(words)),
+                _ => false,
+            });
+        if !scope_matches {
+            return Ok(());
+        }
+        let scope_start = instant::now();
+        let mut any_asserts = false;
+        for item in items {
+            match item {
+                Item::Words(words) if is_comment(&words) => {}
+                Item::Words(words) if has_assert(&words) => {
+                    any_asserts = true;
+                    let name = case_name(&words);
+                    if !matches(&name) {
+                        continue;
+                    }
+                    let span = words_span(&words).map_or(Span::Builtin, Into::into);
+                    let height = self.stack.len();
+                    let start = instant::now();
+                    let res = self
+                        .compile_words(words, true)
+                        .and_then(|instrs| self.exec_global_instrs(instrs));
+                    if res.is_err() {
+                        self.stack.truncate(height);
+                    }
+                    self.test_results.push(TestResult {
+                        name,
+                        span,
+                        error: res.err(),
+                        duration: instant::now() - start,
+                    });
+                }
+                item => {
+                    if let Err(e) = self.item(item, true) {
+                        // A failure outside of an assert fails the whole scope
+                        self.test_results.push(TestResult {
+                            name: scope_name,
+                            span: scope_span,
+                            error: Some(e),
+                            duration: instant::now() - scope_start,
+                        });
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        if !any_asserts {
+            self.test_results.push(TestResult {
+                name: scope_name,
+                span: scope_span,
+                error: None,
+                duration: instant::now() - scope_start,
+            });
+        }
+        Ok(())
+    }
     fn add_span(&mut self, span: impl Into<Span>) -> usize {
         let mut spans = self.spans.lock();
         let idx = spans.len();
@@ -92,6 +213,12 @@ impl Uiua {
     }
     fn binding(&mut self, binding: Binding) -> UiuaResult {
         let instrs = self.compile_words(binding.words, true)?;
+        // The signatures of globals that are bound when a built program is loaded
+        // are not known yet, so bindings that use them are bound then too
+        if (self.build.as_ref()).is_some_and(|build| !build.check) && loads_globals(&instrs) {
+            let sig = binding.signature.map(|sig| sig.value);
+            return self.defer_binding(binding.name, sig, instrs);
+        }
         let make_fn = |instrs: Vec<Instr>, sig: Signature| {
             let func = Function::new(FunctionId::Named(binding.name.value.clone()), instrs, sig);
             Value::from(func)
@@ -112,6 +239,13 @@ impl Uiua {
                 }
 
                 if sig.args == 0 && (sig.outputs > 0 || instrs.is_empty()) {
+                    // Top-level lines are not run when building, so bindings that
+                    // depend on them or have effects are bound when the program is loaded
+                    if (self.build.as_ref()).is_some_and(|build| {
+                        build.check || instrs.is_empty() || has_side_effects(&instrs)
+                    }) {
+                        return self.defer_binding(binding.name, Some(sig), instrs);
+                    }
                     self.exec_global_instrs(instrs)?;
                     if let Some(value) = self.stack.pop() {
                         match value {
@@ -139,6 +273,13 @@ impl Uiua {
             }
             Err(e) => {
                 if let Some(sig) = binding.signature {
+                    // The declared signature can only be checked when the function is called
+                    if (self.lint_config.as_ref()).is_some_and(|config| config.extra_values) {
+                        self.unchecked_signatures.insert(
+                            FunctionId::Named(binding.name.value.clone()),
+                            binding.name.span.clone(),
+                        );
+                    }
                     make_fn(instrs, sig.value)
                 } else {
                     return Err(UiuaError::Run(
@@ -148,6 +289,22 @@ impl Uiua {
                 }
             }
         };
+        // Record the binding's type
+        let ty = match val.as_func_array().and_then(Array::as_scalar) {
+            Some(f) if f.as_boxed().is_none() => {
+                let (_, outputs) = infer_types(&f.instrs, &self.spans.lock());
+                (outputs.len() == f.signature().outputs
+                    && outputs.iter().any(InferredType::is_known))
+                .then(|| {
+                    let outputs: Vec<_> = outputs.iter().map(ToString::to_string).collect();
+                    format!("{} → {}", f.signature(), outputs.join(", "))
+                })
+            }
+            _ => Some(InferredType::from_value(&val).to_string()),
+        };
+        if let Some(ty) = ty {
+            self.binding_types.insert(binding.name.span.clone(), ty);
+        }
         val.compress();
         let mut globals = self.globals.lock();
         let idx = globals.len();
@@ -155,15 +312,48 @@ impl Uiua {
         self.scope.names.insert(binding.name.value, idx);
         Ok(())
     }
+    /// Compile a binding into a line that binds it when a built program is loaded
+    fn defer_binding(
+        &mut self,
+        name: Sp<Ident>,
+        sig: Option<Signature>,
+        mut instrs: Vec<Instr>,
+    ) -> UiuaResult {
+        // The type can still be inferred from the code
+        let (_, outputs) = infer_types(&instrs, &self.spans.lock());
+        if let Some(ty) = outputs.last().filter(|ty| ty.is_known()) {
+            self.binding_types.insert(name.span.clone(), ty.to_string());
+        }
+        let index = {
+            let mut globals = self.globals.lock();
+            globals.push(Value::default());
+            globals.len() - 1
+        };
+        let bind = DynamicFunction::bind_global(index, name.value.clone(), sig);
+        instrs.push(Instr::Dynamic(bind));
+        let build = self.build.as_mut().unwrap();
+        build.deferred.push(index);
+        build.lines.push(instrs);
+        self.scope.names.insert(name.value, index);
+        Ok(())
+    }
     fn compile_words(&mut self, words: Vec<Sp<Word>>, call: bool) -> UiuaResult<Vec<Instr>> {
         self.new_functions.push(Vec::new());
         self.words(words, call)?;
+        let instrs = self.new_functions.pop().unwrap();
+        if self.new_functions.is_empty() {
+            let (diagnostics, _) = infer_types(&instrs, &self.spans.lock());
+            self.diagnostics.extend(diagnostics);
+            if (self.build.as_ref()).is_some_and(|build| !build.check) {
+                self.build_imports(&instrs)?;
+            }
+        }
+        let instrs = self.optimize_instrs(instrs);
         if self.print_diagnostics {
             for diagnostic in self.take_diagnostics() {
                 eprintln!("{}", diagnostic.show(true));
             }
         }
-        let instrs = self.new_functions.pop().unwrap();
         Ok(instrs)
     }
     fn compile_operand_words(
@@ -199,10 +389,16 @@ impl Uiua {
     fn push_instr(&mut self, instr: Instr) {
         use Primitive::*;
         let instrs = self.new_functions.last_mut().unwrap();
+        let mut coverage = self.coverage.as_ref().map(|coverage| coverage.lock());
         // Optimizations
         match (instrs.as_mut_slice(), instr) {
             // Cosine
-            ([.., Instr::Prim(Eta, _), Instr::Prim(Add, _)], Instr::Prim(Sin, span)) => {
+            ([.., Instr::Prim(Eta, eta), Instr::Prim(Add, add)], Instr::Prim(Sin, span)) => {
+                if let Some(coverage) = &mut coverage {
+                    coverage.remove_span(*eta);
+                    coverage.remove_span(*add);
+                    coverage.add_span(span, Some(Cos));
+                }
                 instrs.pop();
                 instrs.pop();
                 instrs.push(Instr::Prim(Cos, span));
@@ -211,7 +407,17 @@ impl Uiua {
             ([.., Instr::Prim(top @ Reverse, _)], Instr::Prim(First, _)) => *top = Last,
             // // Coalesce inline stack ops
             // ([.., Instr::])
-            (_, instr) => instrs.push(instr),
+            (_, instr) => {
+                if let (Some(coverage), Some(span)) = (&mut coverage, instr.span()) {
+                    let prim = if let Instr::Prim(prim, _) = instr {
+                        Some(prim)
+                    } else {
+                        None
+                    };
+                    coverage.add_span(span, prim);
+                }
+                instrs.push(instr)
+            }
         }
     }
     fn word(&mut self, word: Sp<Word>, call: bool) -> UiuaResult {
@@ -222,75 +428,11 @@ impl Uiua {
             Word::Char(c) => self.push_instr(Instr::push(c)),
             Word::String(s) => self.push_instr(Instr::push(s)),
             Word::FormatString(frags) => {
-                let signature = Signature::new(frags.len() - 1, 1);
-                let f = Function::new(
-                    FunctionId::Anonymous(word.span.clone()),
-                    vec![Instr::Dynamic(DynamicFunction {
-                        id: {
-                            let mut hasher = DefaultHasher::new();
-                            frags.hash(&mut hasher);
-                            hasher.finish()
-                        },
-                        f: Arc::new(move |env| {
-                            let mut formatted = String::new();
-                            for (i, frag) in frags.iter().enumerate() {
-                                if i > 0 {
-                                    let val = env.pop(format!("format argument {i}"))?;
-                                    formatted.push_str(&format!("{}", val));
-                                }
-                                formatted.push_str(frag);
-                            }
-                            env.push(formatted);
-                            Ok(())
-                        }),
-                        signature,
-                    })],
-                    signature,
-                );
-                self.push_instr(Instr::push(f));
-                if call {
-                    let span = self.add_span(word.span);
-                    self.push_instr(Instr::Call(span));
-                }
+                self.format_string(vec![frags], word.span, call);
             }
             Word::MultilineString(lines) => {
-                let signature = Signature::new(lines.iter().map(|l| l.value.len() - 1).sum(), 1);
-                let f = Function::new(
-                    FunctionId::Anonymous(word.span.clone()),
-                    vec![Instr::Dynamic(DynamicFunction {
-                        id: {
-                            let mut hasher = DefaultHasher::new();
-                            lines.hash(&mut hasher);
-                            hasher.finish()
-                        },
-                        f: Arc::new(move |env| {
-                            let mut formatted = String::new();
-                            let mut i = 0;
-                            for (j, line) in lines.iter().enumerate() {
-                                if j > 0 {
-                                    formatted.push('\n');
-                                }
-                                for (k, frag) in line.value.iter().enumerate() {
-                                    if k > 0 {
-                                        let val = env.pop(format!("format argument {i}"))?;
-                                        formatted.push_str(&format!("{}", val));
-                                    }
-                                    formatted.push_str(frag);
-                                    i += 1;
-                                }
-                            }
-                            env.push(formatted);
-                            Ok(())
-                        }),
-                        signature,
-                    })],
-                    signature,
-                );
-                self.push_instr(Instr::push(f));
-                if call {
-                    let span = self.add_span(word.span);
-                    self.push_instr(Instr::Call(span));
-                }
+                let lines = lines.into_iter().map(|line| line.value).collect();
+                self.format_string(lines, word.span, call);
             }
             Word::Ident(ident) => self.ident(ident, word.span, call)?,
             Word::Strand(items) => {
@@ -368,6 +510,20 @@ impl Uiua {
         }
         Ok(())
     }
+    fn format_string(&mut self, lines: Vec<Vec<String>>, span: CodeSpan, call: bool) {
+        let df = DynamicFunction::format_string(lines);
+        let signature = df.signature;
+        let f = Function::new(
+            FunctionId::Anonymous(span.clone()),
+            vec![Instr::Dynamic(df)],
+            signature,
+        );
+        self.push_instr(Instr::push(f));
+        if call {
+            let span = self.add_span(span);
+            self.push_instr(Instr::Call(span));
+        }
+    }
     fn ident(&mut self, ident: Ident, span: CodeSpan, call: bool) -> UiuaResult {
         if let Some(idx) = self.scope.names.get(&ident).or_else(|| {
             self.higher_scopes
@@ -377,7 +533,15 @@ impl Uiua {
                 .get(&ident)
         }) {
             // Name exists in scope
-            let value = self.globals.lock()[*idx].clone();
+            let idx = *idx;
+            if (self.build.as_ref()).is_some_and(|build| build.deferred.contains(&idx)) {
+                // The value is not known until the program is loaded
+                let call_span = call.then(|| self.add_span(span));
+                let load = DynamicFunction::load_global(idx, call_span);
+                self.push_instr(Instr::Dynamic(load));
+                return Ok(());
+            }
+            let value = self.globals.lock()[idx].clone();
             let should_call = matches!(&value, Value::Func(f) if f.shape.is_empty());
             self.push_instr(Instr::push(value));
             if should_call && call {
@@ -0,0 +1,46 @@
+//! Loading configuration files that are written in Uiua
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{value::Value, Ident, SysBackend, Uiua, UiuaResult};
+
+// For now disallow any syscalls in config files.
+struct ConfigBackend;
+
+impl SysBackend for ConfigBackend {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Run a config file and get the values it binds
+///
+/// The runtime is returned too so that the values can be converted.
+pub(crate) fn load_config_file(path: PathBuf) -> UiuaResult<(Uiua, HashMap<Ident, Value>)> {
+    let mut env = Uiua::with_backend(ConfigBackend).print_diagnostics(true);
+    env.load_file(path)?;
+    let bindings = env.all_bindings_in_scope();
+    Ok((env, bindings))
+}
+
+/// Search for a config file with the given name, starting at the given path
+/// (or the current directory) and moving up through its parents
+pub(crate) fn search_config_file(name: &str, path: Option<&Path>) -> Option<PathBuf> {
+    let mut path = path
+        .and_then(|p| std::fs::canonicalize(p).ok())
+        .unwrap_or(env::current_dir().ok()?);
+    loop {
+        let file_path = path.join(name);
+        if file_path.exists() {
+            return Some(file_path);
+        }
+        if !path.pop() {
+            return None;
+        }
+    }
+}
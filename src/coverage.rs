@@ -0,0 +1,299 @@
+//! Code coverage collection and reporting
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lex::{CodeSpan, Span},
+    primitive::Primitive,
+};
+
+/// Coverage data collected while running a program
+///
+/// Keys are indices into the runtime's span table.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    spans: BTreeMap<usize, SpanHits>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SpanHits {
+    hits: u64,
+    branch: Option<BranchHits>,
+}
+
+#[derive(Debug, Clone)]
+struct BranchHits {
+    kind: BranchKind,
+    taken: [u64; 2],
+}
+
+/// A kind of branching primitive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchKind {
+    /// [`Primitive::If`]
+    If,
+    /// [`Primitive::Try`]
+    Try,
+}
+
+impl BranchKind {
+    fn from_primitive(prim: Primitive) -> Option<Self> {
+        match prim {
+            Primitive::If => Some(BranchKind::If),
+            Primitive::Try => Some(BranchKind::Try),
+            _ => None,
+        }
+    }
+    /// The name of the branching primitive
+    pub fn name(&self) -> &'static str {
+        match self {
+            BranchKind::If => "if",
+            BranchKind::Try => "try",
+        }
+    }
+    /// The names of the two arms of the branch
+    pub fn arm_names(&self) -> [&'static str; 2] {
+        match self {
+            BranchKind::If => ["true", "false"],
+            BranchKind::Try => ["ok", "caught"],
+        }
+    }
+}
+
+impl Coverage {
+    /// Mark a span as executable
+    pub(crate) fn add_span(&mut self, span: usize, prim: Option<Primitive>) {
+        let entry = self.spans.entry(span).or_default();
+        if let Some(kind) = prim.and_then(BranchKind::from_primitive) {
+            entry.branch.get_or_insert(BranchHits {
+                kind,
+                taken: [0; 2],
+            });
+        }
+    }
+    /// Unmark a span that was optimized away
+    pub(crate) fn remove_span(&mut self, span: usize) {
+        self.spans.remove(&span);
+    }
+    /// Record that a span was executed
+    pub(crate) fn hit(&mut self, span: usize) {
+        self.spans.entry(span).or_default().hits += 1;
+    }
+    /// Record which arm of a branch was taken
+    ///
+    /// `arm` is `0` for the true/ok arm and `1` for the false/caught arm.
+    pub(crate) fn take_branch(&mut self, span: usize, kind: BranchKind, arm: usize) {
+        let entry = self.spans.entry(span).or_default();
+        entry.branch.get_or_insert(BranchHits {
+            kind,
+            taken: [0; 2],
+        });
+        entry.branch.as_mut().unwrap().taken[arm] += 1;
+    }
+    /// Build a per-file report using the runtime's span table
+    ///
+    /// Spans that are not in a file are ignored.
+    pub fn report(&self, spans: &[Span]) -> Vec<FileCoverage> {
+        let mut files: BTreeMap<PathBuf, FileCoverage> = BTreeMap::new();
+        for (&i, hits) in &self.spans {
+            let Some(Span::Code(span)) = spans.get(i) else {
+                continue;
+            };
+            let Some(path) = &span.path else {
+                continue;
+            };
+            let file = files
+                .entry(path.to_path_buf())
+                .or_insert_with(|| FileCoverage {
+                    path: path.to_path_buf(),
+                    lines: BTreeMap::new(),
+                    spans: Vec::new(),
+                    branches: Vec::new(),
+                });
+            let line = file.lines.entry(span.start.line).or_default();
+            *line = (*line).max(hits.hits);
+            file.spans.push((span.clone(), hits.hits));
+            if let Some(branch) = &hits.branch {
+                file.branches.push(BranchCoverage {
+                    id: i,
+                    span: span.clone(),
+                    kind: branch.kind,
+                    executed: hits.hits > 0,
+                    taken: branch.taken,
+                });
+            }
+        }
+        files.into_values().collect()
+    }
+}
+
+/// Coverage of a single file
+#[derive(Debug, Clone)]
+pub struct FileCoverage {
+    /// The path of the file
+    pub path: PathBuf,
+    /// The hit count of each line that has executable code
+    pub lines: BTreeMap<usize, u64>,
+    /// The hit count of each executable span
+    pub spans: Vec<(CodeSpan, u64)>,
+    /// The branches in the file
+    pub branches: Vec<BranchCoverage>,
+}
+
+/// Coverage of a single branching primitive
+#[derive(Debug, Clone)]
+pub struct BranchCoverage {
+    /// A unique id for the branch
+    pub id: usize,
+    /// The span of the primitive
+    pub span: CodeSpan,
+    /// The kind of branch
+    pub kind: BranchKind,
+    /// Whether the primitive was executed at all
+    pub executed: bool,
+    /// How many times each arm was taken
+    pub taken: [u64; 2],
+}
+
+impl FileCoverage {
+    /// The number of lines that were executed and the number of executable lines
+    pub fn line_counts(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|&&hits| hits > 0).count();
+        (hit, self.lines.len())
+    }
+    /// The number of spans that were executed and the number of executable spans
+    pub fn span_counts(&self) -> (usize, usize) {
+        let hit = self.spans.iter().filter(|(_, hits)| *hits > 0).count();
+        (hit, self.spans.len())
+    }
+    /// The number of branch arms that were taken and the total number of branch arms
+    pub fn branch_counts(&self) -> (usize, usize) {
+        let taken = (self.branches.iter())
+            .flat_map(|branch| branch.taken)
+            .filter(|&taken| taken > 0)
+            .count();
+        (taken, self.branches.len() * 2)
+    }
+}
+
+/// Render coverage as an LCOV tracefile
+pub fn lcov(files: &[FileCoverage]) -> String {
+    let mut lcov = String::new();
+    for file in files {
+        writeln!(lcov, "TN:").unwrap();
+        writeln!(lcov, "SF:{}", file.path.display()).unwrap();
+        for branch in &file.branches {
+            for (arm, taken) in branch.taken.into_iter().enumerate() {
+                let taken = if branch.executed {
+                    taken.to_string()
+                } else {
+                    "-".into()
+                };
+                writeln!(
+                    lcov,
+                    "BRDA:{},{},{arm},{taken}",
+                    branch.span.start.line, branch.id
+                )
+                .unwrap();
+            }
+        }
+        let (branches_hit, branches_found) = file.branch_counts();
+        writeln!(lcov, "BRF:{branches_found}").unwrap();
+        writeln!(lcov, "BRH:{branches_hit}").unwrap();
+        for (line, hits) in &file.lines {
+            writeln!(lcov, "DA:{line},{hits}").unwrap();
+        }
+        let (lines_hit, lines_found) = file.line_counts();
+        writeln!(lcov, "LF:{lines_found}").unwrap();
+        writeln!(lcov, "LH:{lines_hit}").unwrap();
+        writeln!(lcov, "end_of_record").unwrap();
+    }
+    lcov
+}
+
+/// Render a human-readable coverage summary
+///
+/// Paths are shown relative to `base` when possible.
+pub fn summary(files: &[FileCoverage], base: &Path) -> String {
+    fn percent((hit, total): (usize, usize)) -> String {
+        if total == 0 {
+            "-".into()
+        } else {
+            format!("{hit}/{total} ({:.1}%)", hit as f64 / total as f64 * 100.0)
+        }
+    }
+    let mut summary = String::new();
+    for file in files {
+        let path = file.path.strip_prefix(base).unwrap_or(&file.path);
+        writeln!(
+            summary,
+            "{}: lines {}, spans {}, branches {}",
+            path.display(),
+            percent(file.line_counts()),
+            percent(file.span_counts()),
+            percent(file.branch_counts()),
+        )
+        .unwrap();
+        for branch in &file.branches {
+            for (arm, name) in branch.kind.arm_names().into_iter().enumerate() {
+                if branch.taken[arm] == 0 {
+                    writeln!(
+                        summary,
+                        "  {}:{} {} {name} arm never taken",
+                        branch.span.start.line,
+                        branch.span.start.col,
+                        branch.kind.name()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+    summary.truncate(summary.trim_end().len());
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uiua;
+
+    #[test]
+    fn lcov_report() {
+        let code = "\
+            F ← ?(+1)(-1)\n\
+            G ← ×2\n\
+            F 1 5\n\
+            ParseOrZero ← ⍣parse⋅⋅0\n\
+            ParseOrZero \"x\"";
+        let mut env = Uiua::with_native_sys().collect_coverage(true);
+        env.load_str_path(code, "cov.ua").unwrap();
+        // `G` is never called, `?` takes only its true arm,
+        // and `⍣` only catches an error
+        assert_eq!(
+            lcov(&env.coverage()),
+            "\
+TN:
+SF:cov.ua
+BRDA:1,3,0,1
+BRDA:1,3,1,0
+BRDA:4,9,0,0
+BRDA:4,9,1,1
+BRF:4
+BRH:2
+DA:1,1
+DA:2,0
+DA:3,1
+DA:4,1
+DA:5,1
+LF:5
+LH:4
+end_of_record
+"
+        );
+    }
+}
@@ -1,11 +1,18 @@
 use std::{
     borrow::Borrow,
+    cell::RefCell,
     cmp::Ordering,
     fmt,
     hash::{Hash, Hasher},
     iter::{Skip, Take},
+    mem::{size_of, take},
     ops::{Bound, Deref, RangeBounds},
+    panic::resume_unwind,
     ptr,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc,
+    },
 };
 
 macro_rules! cowslice {
@@ -37,8 +44,11 @@ impl<T> CowSlice<T> {
         self.end = (self.start + len as u32).min(self.end);
     }
     pub fn with_capacity(capacity: usize) -> Self {
+        check_allocation::<T>(capacity);
+        let data = EcoVec::with_capacity(capacity);
+        allocate::<T>(data.capacity());
         Self {
-            data: EcoVec::with_capacity(capacity),
+            data,
             start: 0,
             end: 0,
         }
@@ -60,8 +70,10 @@ impl<T> CowSlice<T> {
 impl<T: Clone> CowSlice<T> {
     pub fn as_mut_slice(&mut self) -> &mut [T] {
         if !self.data.is_unique() {
+            check_allocation::<T>(self.len());
             let mut new_data = EcoVec::with_capacity(self.len());
             new_data.extend_from_slice(&*self);
+            allocate::<T>(new_data.capacity());
             self.data = new_data;
             self.start = 0;
             self.end = self.data.len() as u32;
@@ -107,10 +119,15 @@ impl<T: Clone> CowSlice<T> {
         F: FnOnce(&mut EcoVec<T>) -> R,
     {
         if self.data.is_unique() && self.start == 0 && self.end == self.data.len() as u32 {
+            let capacity = self.data.capacity();
             let res = f(&mut self.data);
             self.end = self.data.len() as u32;
+            if self.data.capacity() > capacity {
+                allocate::<T>(self.data.capacity() - capacity);
+            }
             res
         } else {
+            check_allocation::<T>(self.len());
             let mut vec = EcoVec::from(&**self);
             let res = f(&mut vec);
             *self = vec.into();
@@ -120,6 +137,7 @@ impl<T: Clone> CowSlice<T> {
     /// Ensure that the capacity is at least `min`
     pub fn reserve_min(&mut self, min: usize) {
         if self.data.capacity() < min {
+            check_allocation::<T>(min);
             self.modify(|vec| vec.reserve(vec.capacity().max(min) - vec.len()))
         }
     }
@@ -137,6 +155,28 @@ fn cow_slice_modify() {
     assert_eq!(sub, [2, 3, 5]);
 }
 
+#[test]
+fn memory_limit() {
+    use crate::Uiua;
+    let mut env = Uiua::with_native_sys().with_memory_limit(1 << 20);
+    env.load_str("⇡1000").unwrap();
+    assert!(env.memory_usage().unwrap() > 0);
+    env.load_str(";").unwrap();
+    assert_eq!(env.memory_usage(), Some(0));
+    let err = env.load_str("↯1e8 0").unwrap_err();
+    assert!(err.message().contains("Memory limit"), "{err}");
+    let err = env.load_str("⊞+.⇡1000").unwrap_err();
+    assert!(err.message().contains("Memory limit"), "{err}");
+}
+
+impl<T> Drop for CowSlice<T> {
+    fn drop(&mut self) {
+        if self.data.is_unique() {
+            release::<T>(self.data.capacity());
+        }
+    }
+}
+
 impl<T> Default for CowSlice<T> {
     fn default() -> Self {
         Self {
@@ -179,7 +219,8 @@ fn cow_slice_deref_mut() {
 impl<T: Clone> From<CowSlice<T>> for Vec<T> {
     fn from(mut slice: CowSlice<T>) -> Self {
         if slice.data.is_unique() && slice.start == 0 && slice.end == slice.data.len() as u32 {
-            slice.data.into_iter().collect()
+            release::<T>(slice.data.capacity());
+            take(&mut slice.data).into_iter().collect()
         } else {
             slice.to_vec()
         }
@@ -188,6 +229,7 @@ impl<T: Clone> From<CowSlice<T>> for Vec<T> {
 
 impl<T: Clone> From<EcoVec<T>> for CowSlice<T> {
     fn from(data: EcoVec<T>) -> Self {
+        allocate::<T>(data.capacity());
         Self {
             start: 0,
             end: data.len() as u32,
@@ -198,21 +240,14 @@ impl<T: Clone> From<EcoVec<T>> for CowSlice<T> {
 
 impl<'a, T: Clone> From<&'a [T]> for CowSlice<T> {
     fn from(slice: &'a [T]) -> Self {
-        Self {
-            start: 0,
-            end: slice.len() as u32,
-            data: slice.into(),
-        }
+        check_allocation::<T>(slice.len());
+        EcoVec::from(slice).into()
     }
 }
 
 impl<T: Clone, const N: usize> From<[T; N]> for CowSlice<T> {
     fn from(array: [T; N]) -> Self {
-        Self {
-            start: 0,
-            end: N as u32,
-            data: array.into(),
-        }
+        EcoVec::from(array).into()
     }
 }
 
@@ -281,8 +316,11 @@ impl<T: Hash> Hash for CowSlice<T> {
 impl<T: Clone> IntoIterator for CowSlice<T> {
     type Item = T;
     type IntoIter = Take<Skip<<EcoVec<T> as IntoIterator>::IntoIter>>;
-    fn into_iter(self) -> Self::IntoIter {
-        self.data
+    fn into_iter(mut self) -> Self::IntoIter {
+        if self.data.is_unique() {
+            release::<T>(self.data.capacity());
+        }
+        take(&mut self.data)
             .into_iter()
             .skip(self.start as usize)
             .take((self.end - self.start) as usize)
@@ -307,6 +345,8 @@ impl<'a, T: Clone> IntoIterator for &'a mut CowSlice<T> {
 
 impl<T: Clone> FromIterator<T> for CowSlice<T> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        check_allocation::<T>(iter.size_hint().0);
         let mut data = EcoVec::new();
         data.extend(iter);
         data.into()
@@ -318,3 +358,104 @@ impl<T: Clone> Extend<T> for CowSlice<T> {
         self.modify(|vec| vec.extend(iter))
     }
 }
+
+thread_local! {
+    /// The memory tracker of the runtime that is executing on this thread
+    static TRACKER: RefCell<Option<Arc<MemoryTracker>>> = const { RefCell::new(None) };
+}
+
+/// Tracks the bytes allocated for array data
+#[derive(Debug)]
+pub(crate) struct MemoryTracker {
+    allocated: AtomicUsize,
+    limit: usize,
+}
+
+/// The unwind payload used when an allocation would exceed the memory limit
+pub(crate) struct MemoryLimitExceeded;
+
+/// Restores the previous memory tracker when dropped
+pub(crate) struct MemoryTrackerGuard(Option<Arc<MemoryTracker>>);
+
+impl MemoryTracker {
+    pub fn new(limit: usize) -> Self {
+        MemoryTracker {
+            allocated: AtomicUsize::new(0),
+            limit,
+        }
+    }
+    /// The number of bytes currently allocated
+    pub fn allocated(&self) -> usize {
+        self.allocated.load(atomic::Ordering::Relaxed)
+    }
+    /// The maximum number of bytes that may be allocated
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+    /// Track allocations on this thread until the guard is dropped
+    pub fn enter(self: &Arc<Self>) -> MemoryTrackerGuard {
+        MemoryTrackerGuard(TRACKER.with(|tracker| tracker.replace(Some(self.clone()))))
+    }
+}
+
+impl Drop for MemoryTrackerGuard {
+    fn drop(&mut self) {
+        TRACKER.with(|tracker| *tracker.borrow_mut() = self.0.take());
+    }
+}
+
+/// Unwind if allocating `len` items would exceed the memory limit
+///
+/// This should be called before allocating large buffers that will become array data.
+pub(crate) fn check_allocation<T>(len: usize) {
+    let bytes = len.saturating_mul(size_of::<T>());
+    if bytes == 0 {
+        return;
+    }
+    TRACKER.with(|tracker| {
+        if let Some(tracker) = &*tracker.borrow() {
+            if tracker.allocated().saturating_add(bytes) > tracker.limit {
+                resume_unwind(Box::new(MemoryLimitExceeded));
+            }
+        }
+    })
+}
+
+/// Count an allocation of `len` items, unwinding if it exceeds the memory limit
+fn allocate<T>(len: usize) {
+    let bytes = len.saturating_mul(size_of::<T>());
+    if bytes == 0 {
+        return;
+    }
+    TRACKER.with(|tracker| {
+        if let Some(tracker) = &*tracker.borrow() {
+            let allocated = tracker
+                .allocated
+                .fetch_add(bytes, atomic::Ordering::Relaxed);
+            if allocated.saturating_add(bytes) > tracker.limit {
+                tracker
+                    .allocated
+                    .fetch_sub(bytes, atomic::Ordering::Relaxed);
+                resume_unwind(Box::new(MemoryLimitExceeded));
+            }
+        }
+    })
+}
+
+/// Count a deallocation of `len` items
+fn release<T>(len: usize) {
+    let bytes = len.saturating_mul(size_of::<T>());
+    if bytes == 0 {
+        return;
+    }
+    TRACKER.with(|tracker| {
+        if let Some(tracker) = &*tracker.borrow() {
+            // Data allocated before tracking started may be released while tracking
+            let _ = (tracker.allocated).fetch_update(
+                atomic::Ordering::Relaxed,
+                atomic::Ordering::Relaxed,
+                |allocated| Some(allocated.saturating_sub(bytes)),
+            );
+        }
+    })
+}
@@ -0,0 +1,589 @@
+//! A Debug Adapter Protocol server
+//!
+//! The server listens on a TCP port and debugs one program per connection.
+//! Anything the program prints goes to the server's own stdout. The final stack
+//! and any error are sent to the client as output events.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use parking_lot::Mutex;
+use serde_json::{json, Value as Json};
+
+use crate::{
+    debug::{Breakpoint, DebugFrame, DebugState, Debugger, LineBreakpoint, PauseReason, Step},
+    function::{FunctionId, Instr},
+    lex::Span,
+    value::Value,
+    Uiua, UiuaError, UiuaResult,
+};
+
+const THREAD_ID: i64 = 1;
+const STACK_REF: i64 = 1;
+const INLINE_REF: i64 = 2;
+const UNDER_REF: i64 = 3;
+
+/// Run the debug adapter server on the given port
+pub fn run_server(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("Debug adapter listening on {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        if let Err(e) = serve(stream) {
+            eprintln!("Debug session ended with an error: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// The connection to the client
+struct Connection {
+    writer: Mutex<TcpStream>,
+    seq: AtomicI64,
+}
+
+impl Connection {
+    fn send(&self, mut message: Json) {
+        message["seq"] = self.seq.fetch_add(1, Ordering::Relaxed).into();
+        let body = message.to_string();
+        let mut writer = self.writer.lock();
+        _ = write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len());
+        _ = writer.flush();
+    }
+    fn respond(&self, request: &Json, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+    fn respond_error(&self, request: &Json, message: impl Into<String>) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message.into(),
+        }));
+    }
+    fn event(&self, event: &str, body: Json) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+    fn output(&self, category: &str, output: impl Into<String>) {
+        self.event(
+            "output",
+            json!({ "category": category, "output": output.into() }),
+        );
+    }
+}
+
+/// The runtime state captured when execution pauses
+struct Snapshot {
+    frames: Vec<DebugFrame>,
+    stack: Vec<Value>,
+    inline_stack: Vec<Value>,
+    under_stack: Vec<Value>,
+    depth: usize,
+}
+
+enum Resume {
+    Step(Step),
+    Terminate,
+}
+
+struct ExceptionFilters {
+    run: bool,
+    throw: bool,
+}
+
+impl Default for ExceptionFilters {
+    /// Matches the defaults advertised in the `initialize` response
+    fn default() -> Self {
+        ExceptionFilters {
+            run: true,
+            throw: true,
+        }
+    }
+}
+
+struct Session {
+    conn: Connection,
+    state: Mutex<DebugState>,
+    exceptions: Mutex<ExceptionFilters>,
+    paused: Mutex<Option<Snapshot>>,
+    resume_send: Mutex<Sender<Resume>>,
+    resume_recv: Mutex<Receiver<Resume>>,
+}
+
+struct DapDebugger(Arc<Session>);
+
+impl DapDebugger {
+    fn pause(&self, env: &Uiua, reason: &str, text: Option<String>) -> UiuaResult {
+        let session = &self.0;
+        *session.paused.lock() = Some(Snapshot {
+            frames: env.call_stack(),
+            stack: env.stack().to_vec(),
+            inline_stack: env.inline_stack().to_vec(),
+            under_stack: env.under_stack().to_vec(),
+            depth: env.call_depth(),
+        });
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["text"] = text.into();
+        }
+        session.conn.event("stopped", body);
+        let resume = session.resume_recv.lock().recv();
+        *session.paused.lock() = None;
+        match resume {
+            Ok(Resume::Step(step)) => {
+                session.state.lock().step = step;
+                Ok(())
+            }
+            Ok(Resume::Terminate) | Err(_) => Err(env.error("Debugging session ended")),
+        }
+    }
+}
+
+impl Debugger for DapDebugger {
+    fn before_instr(&self, env: &Uiua, instr: &Instr) -> UiuaResult {
+        let reason = self.0.state.lock().should_pause(env, instr);
+        match reason {
+            Some(PauseReason::Step) => self.pause(env, "step", None),
+            Some(PauseReason::Breakpoint(Breakpoint::Line(_))) => {
+                self.pause(env, "breakpoint", None)
+            }
+            Some(PauseReason::Breakpoint(Breakpoint::Binding(_))) => {
+                self.pause(env, "function breakpoint", None)
+            }
+            None => Ok(()),
+        }
+    }
+    fn on_error(&self, env: &Uiua, error: &UiuaError) -> UiuaResult {
+        let mut error = error;
+        while let UiuaError::Fill(inner) = error {
+            error = inner;
+        }
+        let filters = self.0.exceptions.lock();
+        let matches = match error {
+            UiuaError::Run(_) => filters.run,
+            UiuaError::Throw(..) => filters.throw,
+            _ => false,
+        };
+        drop(filters);
+        if matches {
+            self.pause(env, "exception", Some(error.message()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn serve(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (resume_send, resume_recv) = channel();
+    let session = Arc::new(Session {
+        conn: Connection {
+            writer: Mutex::new(stream),
+            seq: AtomicI64::new(1),
+        },
+        state: Mutex::new(DebugState::default()),
+        exceptions: Mutex::new(ExceptionFilters::default()),
+        paused: Mutex::new(None),
+        resume_send: Mutex::new(resume_send),
+        resume_recv: Mutex::new(resume_recv),
+    });
+    let conn = &session.conn;
+    let mut launch: Option<(PathBuf, Vec<String>)> = None;
+    let mut configured = false;
+    let mut program: Option<thread::JoinHandle<()>> = None;
+    while let Some(request) = read_message(&mut reader)? {
+        let args = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        match command {
+            "initialize" => {
+                conn.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsFunctionBreakpoints": true,
+                        "supportsTerminateRequest": true,
+                        "exceptionBreakpointFilters": [
+                            { "filter": "run", "label": "Runtime errors", "default": true },
+                            { "filter": "throw", "label": "Thrown errors", "default": true },
+                        ],
+                    }),
+                );
+                conn.event("initialized", json!({}));
+            }
+            "launch" => {
+                let Some(path) = args["program"].as_str() else {
+                    conn.respond_error(&request, "No program specified");
+                    continue;
+                };
+                let prog_args = (args["args"].as_array().into_iter().flatten())
+                    .filter_map(|arg| arg.as_str().map(Into::into))
+                    .collect();
+                session.state.lock().step = if args["stopOnEntry"].as_bool().unwrap_or(false) {
+                    Step::Into
+                } else {
+                    Step::Continue
+                };
+                launch = Some((path.into(), prog_args));
+                conn.respond(&request, json!({}));
+            }
+            "setBreakpoints" => {
+                let path = args["source"]["path"].as_str().map(PathBuf::from);
+                let lines: Vec<usize> = (args["breakpoints"].as_array().into_iter().flatten())
+                    .filter_map(|bp| bp["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                let mut state = session.state.lock();
+                state.breakpoints.retain(|bp| match bp {
+                    Breakpoint::Line(bp) => bp.path != path,
+                    Breakpoint::Binding(_) => true,
+                });
+                let mut verified = Vec::new();
+                for line in lines {
+                    state.breakpoints.insert(Breakpoint::Line(LineBreakpoint {
+                        path: path.clone(),
+                        line,
+                    }));
+                    verified.push(json!({ "verified": true, "line": line }));
+                }
+                conn.respond(&request, json!({ "breakpoints": verified }));
+            }
+            "setFunctionBreakpoints" => {
+                let names: Vec<String> = (args["breakpoints"].as_array().into_iter().flatten())
+                    .filter_map(|bp| bp["name"].as_str().map(Into::into))
+                    .collect();
+                let mut state = session.state.lock();
+                state
+                    .breakpoints
+                    .retain(|bp| matches!(bp, Breakpoint::Line(_)));
+                let mut verified = Vec::new();
+                for name in names {
+                    state
+                        .breakpoints
+                        .insert(Breakpoint::Binding(name.as_str().into()));
+                    verified.push(json!({ "verified": true }));
+                }
+                conn.respond(&request, json!({ "breakpoints": verified }));
+            }
+            "setExceptionBreakpoints" => {
+                let filters: Vec<&str> = (args["filters"].as_array().into_iter().flatten())
+                    .filter_map(Json::as_str)
+                    .collect();
+                *session.exceptions.lock() = ExceptionFilters {
+                    run: filters.contains(&"run"),
+                    throw: filters.contains(&"throw"),
+                };
+                conn.respond(&request, json!({}));
+            }
+            "configurationDone" => {
+                configured = true;
+                conn.respond(&request, json!({}));
+            }
+            "threads" => conn.respond(
+                &request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            ),
+            "stackTrace" => {
+                let paused = session.paused.lock();
+                let frames: Vec<Json> = paused
+                    .iter()
+                    .flat_map(|snapshot| snapshot.frames.iter().enumerate().rev())
+                    .map(|(i, frame)| frame_json(i, frame))
+                    .collect();
+                conn.respond(
+                    &request,
+                    json!({ "stackFrames": frames, "totalFrames": frames.len() }),
+                );
+            }
+            "scopes" => {
+                let paused = session.paused.lock();
+                let scopes: Vec<Json> = paused
+                    .iter()
+                    .flat_map(|snapshot| {
+                        [
+                            ("Stack", STACK_REF, snapshot.stack.len()),
+                            ("Inline stack", INLINE_REF, snapshot.inline_stack.len()),
+                            ("Under stack", UNDER_REF, snapshot.under_stack.len()),
+                        ]
+                    })
+                    .map(|(name, reference, len)| {
+                        json!({
+                            "name": name,
+                            "variablesReference": reference,
+                            "indexedVariables": len,
+                            "expensive": false,
+                        })
+                    })
+                    .collect();
+                conn.respond(&request, json!({ "scopes": scopes }));
+            }
+            "variables" => {
+                let paused = session.paused.lock();
+                let values = paused.as_ref().map_or(&[][..], |snapshot| {
+                    match args["variablesReference"].as_i64() {
+                        Some(STACK_REF) => &snapshot.stack,
+                        Some(INLINE_REF) => &snapshot.inline_stack,
+                        Some(UNDER_REF) => &snapshot.under_stack,
+                        _ => &[],
+                    }
+                });
+                // The top of the stack is shown first
+                let variables: Vec<Json> = (values.iter().rev().enumerate())
+                    .map(|(i, value)| {
+                        json!({
+                            "name": i.to_string(),
+                            "value": value.show(),
+                            "type": value.type_name(),
+                            "variablesReference": 0,
+                        })
+                    })
+                    .collect();
+                conn.respond(&request, json!({ "variables": variables }));
+            }
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                let depth = session.paused.lock().as_ref().map(|s| s.depth);
+                let Some(depth) = depth else {
+                    conn.respond_error(&request, "The program is not paused");
+                    continue;
+                };
+                let step = match command {
+                    "continue" => Step::Continue,
+                    "next" => Step::Over(depth),
+                    "stepIn" => Step::Into,
+                    _ => Step::Out(depth),
+                };
+                conn.respond(&request, json!({ "allThreadsContinued": true }));
+                _ = session.resume_send.lock().send(Resume::Step(step));
+            }
+            "pause" => {
+                session.state.lock().step = Step::Into;
+                conn.respond(&request, json!({}));
+            }
+            "disconnect" | "terminate" => {
+                {
+                    let mut state = session.state.lock();
+                    state.breakpoints.clear();
+                    state.step = Step::Continue;
+                }
+                // Don't pause on the error that stops the program
+                *session.exceptions.lock() = ExceptionFilters {
+                    run: false,
+                    throw: false,
+                };
+                _ = session.resume_send.lock().send(Resume::Terminate);
+                conn.respond(&request, json!({}));
+                if command == "disconnect" {
+                    break;
+                }
+            }
+            _ => conn.respond_error(&request, format!("Unsupported request `{command}`")),
+        }
+        if program.is_none() && configured {
+            if let Some((path, args)) = launch.take() {
+                let session = session.clone();
+                program = Some(thread::spawn(move || run_program(session, path, args)));
+            }
+        }
+    }
+    if let Some(program) = program {
+        _ = program.join();
+    }
+    Ok(())
+}
+
+fn run_program(session: Arc<Session>, path: PathBuf, args: Vec<String>) {
+    let mut env = Uiua::with_native_sys()
+        .with_file_path(&path)
+        .with_args(args)
+        .with_debugger(DapDebugger(session.clone()));
+    let conn = &session.conn;
+    let exit_code = match env.load_file(&path) {
+        Ok(()) => {
+            for value in env.take_stack() {
+                conn.output("stdout", format!("{}\n", value.show()));
+            }
+            0
+        }
+        Err(e) => {
+            conn.output("stderr", format!("{}\n", e.show(false)));
+            1
+        }
+    };
+    for diagnostic in env.take_diagnostics() {
+        conn.output("console", format!("{}\n", diagnostic.show(false)));
+    }
+    conn.event("exited", json!({ "exitCode": exit_code }));
+    conn.event("terminated", json!({}));
+}
+
+fn frame_json(i: usize, frame: &DebugFrame) -> Json {
+    let name = match &frame.id {
+        FunctionId::Main => "main".into(),
+        id => id.to_string(),
+    };
+    let mut json = json!({ "id": i, "name": name, "line": 0, "column": 0 });
+    let span = match (&frame.span, &frame.call_span) {
+        (Some(Span::Code(span)), _) | (_, Span::Code(span)) => span,
+        _ => return json,
+    };
+    json["line"] = span.start.line.into();
+    json["column"] = span.start.col.into();
+    json["endLine"] = span.end.line.into();
+    json["endColumn"] = span.end.col.into();
+    if let Some(path) = &span.path {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        json["source"] = json!({
+            "name": path.file_name().map(|name| name.to_string_lossy()),
+            "path": path.to_string_lossy(),
+        });
+    }
+    json
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(len) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Client {
+        writer: TcpStream,
+        reader: BufReader<TcpStream>,
+        seq: i64,
+    }
+
+    impl Client {
+        fn request(&mut self, command: &str, arguments: Json) -> Json {
+            self.seq += 1;
+            let body = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            })
+            .to_string();
+            write!(self.writer, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+            let seq = self.seq;
+            self.expect(|message| message["request_seq"] == seq)
+        }
+        /// Read messages until one matches, returning it
+        fn expect(&mut self, f: impl Fn(&Json) -> bool) -> Json {
+            loop {
+                let message = read_message(&mut self.reader).unwrap().unwrap();
+                if f(&message) {
+                    return message;
+                }
+            }
+        }
+        fn expect_event(&mut self, event: &str) -> Json {
+            self.expect(|message| message["type"] == "event" && message["event"] == event)
+        }
+    }
+
+    #[test]
+    fn debug_session() {
+        let dir = std::env::temp_dir().join(format!("uiua-dap-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.ua");
+        std::fs::write(&path, "X ← 5\n+1 X\n⇡3").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || serve(listener.accept().unwrap().0));
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            seq: 0,
+        };
+
+        let init = client.request("initialize", json!({}));
+        assert_eq!(init["success"], true);
+        let filters = init["body"]["exceptionBreakpointFilters"]
+            .as_array()
+            .unwrap();
+        assert!(filters.iter().all(|filter| filter["default"] == true));
+        client.expect_event("initialized");
+        let bps = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": [{ "line": 3 }] }),
+        );
+        assert_eq!(bps["body"]["breakpoints"][0]["verified"], true);
+        let launch = client.request("launch", json!({ "program": path }));
+        assert_eq!(launch["success"], true);
+        client.request("configurationDone", json!({}));
+
+        let stopped = client.expect_event("stopped");
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        let trace = client.request("stackTrace", json!({ "threadId": THREAD_ID }));
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 3);
+        let vars = client.request("variables", json!({ "variablesReference": STACK_REF }));
+        let values: Vec<_> = (vars["body"]["variables"].as_array().unwrap().iter())
+            .map(|var| var["value"].as_str().unwrap())
+            .collect();
+        assert_eq!(values, ["3", "6"]);
+        let resumed = client.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(resumed["success"], true);
+
+        let output = client.expect_event("output");
+        assert_eq!(output["body"]["output"], "6\n");
+        let exited = client.expect_event("exited");
+        assert_eq!(exited["body"]["exitCode"], 0);
+        client.expect_event("terminated");
+        assert_eq!(client.request("disconnect", json!({}))["success"], true);
+        server.join().unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,493 @@
+//! Step-through debugging of Uiua programs
+
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    function::{FunctionId, Instr},
+    lex::{CodeSpan, Span},
+    value::Value,
+    Ident, Uiua, UiuaError, UiuaResult,
+};
+
+/// A hook that is called before each instruction is executed
+///
+/// Attach one to a runtime with [`Uiua::with_debugger`]
+pub trait Debugger: Send + Sync + 'static {
+    /// Called before an instruction is executed
+    ///
+    /// Returning an error aborts execution
+    fn before_instr(&self, env: &Uiua, instr: &Instr) -> UiuaResult;
+    /// Called when an instruction fails, before the call stack is unwound
+    ///
+    /// Returning an error replaces the original one
+    #[allow(unused_variables)]
+    fn on_error(&self, env: &Uiua, error: &UiuaError) -> UiuaResult {
+        Ok(())
+    }
+}
+
+/// A view of a frame in the call stack
+#[derive(Debug, Clone)]
+pub struct DebugFrame {
+    /// The id of the function being executed
+    pub id: FunctionId,
+    /// The span at which the function was called
+    pub call_span: Span,
+    /// The index of the instruction being executed
+    pub pc: usize,
+    /// The span of the instruction being executed, if it has one
+    pub span: Option<Span>,
+}
+
+/// How execution should proceed after a pause
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Step {
+    /// Run until a breakpoint is hit
+    Continue,
+    /// Pause before the next instruction
+    #[default]
+    Into,
+    /// Pause before the next instruction at or above the given call depth
+    Over(usize),
+    /// Pause before the next instruction above the given call depth
+    Out(usize),
+}
+
+/// A breakpoint on a line of source code
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LineBreakpoint {
+    /// The file the line is in. If `None`, the breakpoint applies to all files.
+    pub path: Option<PathBuf>,
+    /// The 1-based line number
+    pub line: usize,
+}
+
+impl fmt::Display for LineBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}", path.display(), self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
+/// A breakpoint
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// Pause when execution reaches a line
+    Line(LineBreakpoint),
+    /// Pause when a named binding is called
+    Binding(Ident),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line(bp) => bp.fmt(f),
+            Breakpoint::Binding(name) => write!(f, "`{name}`"),
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("expected a line number or binding name".into());
+        }
+        if let Ok(line) = s.parse() {
+            return Ok(Breakpoint::Line(LineBreakpoint { path: None, line }));
+        }
+        if let Some((path, line)) = s.rsplit_once(':') {
+            if let Ok(line) = line.parse() {
+                return Ok(Breakpoint::Line(LineBreakpoint {
+                    path: Some(path.into()),
+                    line,
+                }));
+            }
+        }
+        Ok(Breakpoint::Binding(s.into()))
+    }
+}
+
+/// Why execution was paused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PauseReason {
+    /// A step completed
+    Step,
+    /// A breakpoint was hit
+    Breakpoint(Breakpoint),
+}
+
+/// Breakpoint and stepping state shared by debugger frontends
+#[derive(Debug, Clone, Default)]
+pub struct DebugState {
+    /// The active breakpoints
+    pub breakpoints: HashSet<Breakpoint>,
+    /// How to proceed until the next pause
+    pub step: Step,
+    /// The location of the last instruction that was checked
+    last_location: Option<(Option<PathBuf>, usize)>,
+}
+
+impl DebugState {
+    /// Check whether execution should pause before an instruction
+    pub fn should_pause(&mut self, env: &Uiua, instr: &Instr) -> Option<PauseReason> {
+        let depth = env.call_depth();
+        let span = instr.span().map(|span| env.get_span(span));
+        let location = if let Some(Span::Code(span)) = &span {
+            Some((span.path.as_deref().map(Path::to_path_buf), span.start.line))
+        } else {
+            self.last_location.clone()
+        };
+        let new_location = location != self.last_location;
+        self.last_location = location;
+        // Breakpoints
+        if let Some(frame) = env.call_stack().last() {
+            if let FunctionId::Named(name) = &frame.id {
+                let bp = Breakpoint::Binding(name.clone());
+                if frame.pc == 0 && self.breakpoints.contains(&bp) {
+                    return Some(PauseReason::Breakpoint(bp));
+                }
+            }
+        }
+        if let (true, Some(Span::Code(span))) = (new_location, &span) {
+            if let Some(bp) = self.breakpoints.iter().find(|bp| match bp {
+                Breakpoint::Line(bp) => bp.matches(span),
+                Breakpoint::Binding(_) => false,
+            }) {
+                return Some(PauseReason::Breakpoint(bp.clone()));
+            }
+        }
+        // Stepping
+        let pause = match self.step {
+            Step::Continue => false,
+            Step::Into => true,
+            Step::Over(d) => depth <= d,
+            Step::Out(d) => depth < d,
+        };
+        pause.then_some(PauseReason::Step)
+    }
+}
+
+impl LineBreakpoint {
+    /// Check whether the breakpoint is on the line where a span starts
+    pub fn matches(&self, span: &CodeSpan) -> bool {
+        if self.line != span.start.line {
+            return false;
+        }
+        match (&self.path, &span.path) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(a), Some(b)) => {
+                a.ends_with(&**b)
+                    || b.ends_with(a)
+                    || (a.canonicalize().ok()).is_some_and(|a| b.canonicalize().ok() == Some(a))
+            }
+        }
+    }
+}
+
+/// A [`Debugger`] that is driven from the terminal
+#[derive(Default)]
+pub struct TerminalDebugger {
+    state: Mutex<DebugState>,
+    last_command: Mutex<String>,
+}
+
+const TERMINAL_HELP: &str = "\
+Commands:
+  s, step          Step to the next instruction
+  n, next          Step over function calls
+  o, out           Step out of the current function
+  c, continue      Continue until a breakpoint is hit
+  b <location>     Set a breakpoint on a line, file:line, or binding name
+  d <location>     Delete a breakpoint
+  bl               List breakpoints
+  p, stack         Show the main stack
+  i, inline        Show the inline temp stack
+  u, under         Show the under temp stack
+  bt, backtrace    Show the call stack
+  l, list          Show the source around the current line
+  q, quit          Stop the program
+An empty line repeats the last command";
+
+impl TerminalDebugger {
+    /// Create a new terminal debugger with some initial breakpoints
+    ///
+    /// If there are no breakpoints, execution will pause before the first instruction.
+    pub fn new(breakpoints: impl IntoIterator<Item = Breakpoint>) -> Self {
+        let breakpoints: HashSet<_> = breakpoints.into_iter().collect();
+        let step = if breakpoints.is_empty() {
+            Step::Into
+        } else {
+            Step::Continue
+        };
+        TerminalDebugger {
+            state: Mutex::new(DebugState {
+                breakpoints,
+                step,
+                last_location: None,
+            }),
+            last_command: Mutex::new(String::new()),
+        }
+    }
+}
+
+impl Debugger for TerminalDebugger {
+    fn before_instr(&self, env: &Uiua, instr: &Instr) -> UiuaResult {
+        let mut state = self.state.lock();
+        let Some(reason) = state.should_pause(env, instr) else {
+            return Ok(());
+        };
+        if let PauseReason::Breakpoint(bp) = &reason {
+            println!("Breakpoint hit: {bp}");
+        }
+        let frames = env.call_stack();
+        let span = frames.last().and_then(|frame| frame.span.clone());
+        show_location(&frames, instr, span.as_ref());
+        let stdin = io::stdin();
+        loop {
+            print!("(debug) ");
+            _ = io::stdout().flush();
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                state.step = Step::Continue;
+                state.breakpoints.clear();
+                return Ok(());
+            }
+            let mut line = line.trim().to_string();
+            {
+                let mut last = self.last_command.lock();
+                if line.is_empty() {
+                    line = last.clone();
+                } else {
+                    *last = line.clone();
+                }
+            }
+            let (command, arg) = line
+                .split_once(char::is_whitespace)
+                .map_or((line.as_str(), ""), |(c, a)| (c, a.trim()));
+            let depth = env.call_depth();
+            match command {
+                "s" | "step" => state.step = Step::Into,
+                "n" | "next" => state.step = Step::Over(depth),
+                "o" | "out" => state.step = Step::Out(depth),
+                "c" | "continue" => state.step = Step::Continue,
+                "b" | "break" => {
+                    match arg.parse::<Breakpoint>() {
+                        Ok(bp) => {
+                            println!("Breakpoint set at {bp}");
+                            state.breakpoints.insert(bp);
+                        }
+                        Err(e) => println!("Invalid breakpoint: {e}"),
+                    }
+                    continue;
+                }
+                "d" | "delete" => {
+                    match arg.parse::<Breakpoint>() {
+                        Ok(bp) if state.breakpoints.remove(&bp) => {
+                            println!("Breakpoint at {bp} deleted")
+                        }
+                        Ok(bp) => println!("No breakpoint at {bp}"),
+                        Err(e) => println!("Invalid breakpoint: {e}"),
+                    }
+                    continue;
+                }
+                "bl" => {
+                    if state.breakpoints.is_empty() {
+                        println!("No breakpoints");
+                    }
+                    for bp in &state.breakpoints {
+                        println!("  {bp}");
+                    }
+                    continue;
+                }
+                "p" | "stack" => {
+                    show_stack("stack", env.stack());
+                    continue;
+                }
+                "i" | "inline" => {
+                    show_stack("inline stack", env.inline_stack());
+                    continue;
+                }
+                "u" | "under" => {
+                    show_stack("under stack", env.under_stack());
+                    continue;
+                }
+                "bt" | "backtrace" => {
+                    for (i, frame) in frames.iter().enumerate().rev() {
+                        let id = match &frame.id {
+                            FunctionId::Main => "main".into(),
+                            id => id.to_string(),
+                        };
+                        match &frame.span {
+                            Some(Span::Code(span)) => println!("  {i}: {id} at {span}"),
+                            _ => println!("  {i}: {id}"),
+                        }
+                    }
+                    continue;
+                }
+                "l" | "list" => {
+                    if let Some(Span::Code(span)) = &span {
+                        show_source(span, 3);
+                    } else {
+                        println!("No source for this instruction");
+                    }
+                    continue;
+                }
+                "q" | "quit" => return Err(env.error("Debugging session ended")),
+                "h" | "help" | "?" => {
+                    println!("{TERMINAL_HELP}");
+                    continue;
+                }
+                _ => {
+                    println!("Unknown command `{command}`. Type `help` for a list of commands");
+                    continue;
+                }
+            }
+            return Ok(());
+        }
+    }
+}
+
+fn show_location(frames: &[DebugFrame], instr: &Instr, span: Option<&Span>) {
+    let function = match frames.last().map(|frame| &frame.id) {
+        Some(FunctionId::Main) | None => String::new(),
+        Some(id) => format!(" in {id}"),
+    };
+    match span {
+        Some(Span::Code(span)) => {
+            println!("{span}{function}: {instr:?}");
+            show_source(span, 0);
+        }
+        _ => println!("{instr:?}{function}"),
+    }
+}
+
+fn show_source(span: &CodeSpan, context: usize) {
+    let line = span.start.line;
+    let first = line.saturating_sub(context).max(1);
+    for (i, text) in span.input.lines().enumerate().skip(first - 1) {
+        let n = i + 1;
+        if n > line + context {
+            break;
+        }
+        let marker = if n == line { ">" } else { " " };
+        println!("{marker}{n:>4} | {text}");
+        if n == line {
+            let col = span.start.col.saturating_sub(1);
+            let width = if span.end.line == span.start.line {
+                span.end.col.saturating_sub(span.start.col).max(1)
+            } else {
+                1
+            };
+            println!("       | {}{}", " ".repeat(col), "^".repeat(width));
+        }
+    }
+}
+
+fn show_stack(name: &str, stack: &[Value]) {
+    if stack.is_empty() {
+        println!("The {name} is empty");
+        return;
+    }
+    for value in stack {
+        println!("{}", value.show());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A debugger that records where it pauses and then keeps stepping the same way
+    struct Recorder {
+        state: Mutex<DebugState>,
+        pauses: Mutex<Vec<(usize, usize, PauseReason)>>,
+    }
+
+    impl Debugger for std::sync::Arc<Recorder> {
+        fn before_instr(&self, env: &Uiua, instr: &Instr) -> UiuaResult {
+            let mut state = self.state.lock();
+            if let Some(reason) = state.should_pause(env, instr) {
+                let line = state.last_location.as_ref().map_or(0, |(_, line)| *line);
+                self.pauses.lock().push((line, env.call_depth(), reason));
+            }
+            Ok(())
+        }
+    }
+
+    fn pauses(breakpoints: &[&str], step: Step) -> Vec<(usize, usize, PauseReason)> {
+        let recorder = std::sync::Arc::new(Recorder {
+            state: Mutex::new(DebugState {
+                breakpoints: breakpoints.iter().map(|bp| bp.parse().unwrap()).collect(),
+                step,
+                last_location: None,
+            }),
+            pauses: Mutex::new(Vec::new()),
+        });
+        let mut env = Uiua::with_native_sys()
+            .optimize(false)
+            .with_debugger(recorder.clone());
+        env.load_str("F ← +1\nG ← ×2 F\nG 3\n+1 4").unwrap();
+        let pauses = recorder.pauses.lock().clone();
+        pauses
+    }
+
+    #[test]
+    fn parse_breakpoints() {
+        let line = |path: Option<&str>, line| {
+            Breakpoint::Line(LineBreakpoint {
+                path: path.map(Into::into),
+                line,
+            })
+        };
+        assert_eq!("12".parse(), Ok(line(None, 12)));
+        assert_eq!(" main.ua:3 ".parse(), Ok(line(Some("main.ua"), 3)));
+        assert_eq!("C:\\a.ua:3".parse(), Ok(line(Some("C:\\a.ua"), 3)));
+        assert_eq!("Foo".parse(), Ok(Breakpoint::Binding("Foo".into())));
+        assert_eq!("a.ua:x".parse(), Ok(Breakpoint::Binding("a.ua:x".into())));
+        assert!("  ".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn pause_at_breakpoints() {
+        let reasons: Vec<_> = pauses(&["F", "4"], Step::Continue)
+            .into_iter()
+            .map(|(_, _, reason)| reason)
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                PauseReason::Breakpoint("F".parse().unwrap()),
+                PauseReason::Breakpoint("4".parse().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn step_into_over_and_out() {
+        let into = pauses(&[], Step::Into);
+        let lines: Vec<_> = into.iter().map(|(line, ..)| *line).collect();
+        assert!(lines.contains(&1) && lines.contains(&4), "{lines:?}");
+        let top = into.iter().map(|(_, depth, _)| *depth).min().unwrap();
+        assert!(into.iter().any(|(_, depth, _)| *depth > top));
+        // Stepping over or out of the top level never pauses inside functions
+        for step in [Step::Over(top), Step::Out(top + 1)] {
+            let steps = pauses(&[], step);
+            assert!(!steps.is_empty());
+            assert!(steps.iter().all(|(_, depth, _)| *depth == top), "{steps:?}");
+        }
+        assert!(pauses(&[], Step::Out(top)).is_empty());
+    }
+}
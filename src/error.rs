@@ -30,6 +30,7 @@ pub enum UiuaError {
     Throw(Box<Value>, Span),
     Break(usize, Span),
     Timeout(Span),
+    MemoryLimit(usize, Span),
     Fill(Box<Self>),
 }
 
@@ -76,6 +77,9 @@ impl fmt::Display for UiuaError {
             UiuaError::Throw(value, span) => write!(f, "{span}: {value}"),
             UiuaError::Break(_, span) => write!(f, "{span}: Break amount exceeded loop depth"),
             UiuaError::Timeout(_) => write!(f, "Maximum execution time exceeded"),
+            UiuaError::MemoryLimit(limit, _) => {
+                write!(f, "Memory limit of {limit} bytes exceeded")
+            }
             UiuaError::Fill(error) => error.fmt(f),
         }
     }
@@ -215,12 +219,140 @@ impl UiuaError {
                 kind,
                 color,
             ),
+            UiuaError::MemoryLimit(_, span) => {
+                report([(self.to_string(), span.clone())], kind, color)
+            }
             UiuaError::Fill(error) => error.show(color),
             UiuaError::Load(..) | UiuaError::Format(..) => self.to_string(),
         }
     }
 }
 
+/// A machine-readable record of an error or diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct MessageRecord {
+    /// One of `error`, `warning`, `advice`, or `style`
+    pub kind: &'static str,
+    pub message: String,
+    /// The file the message refers to, if any
+    pub file: Option<PathBuf>,
+    /// Where in the file the message refers to, if anywhere
+    pub span: Option<RecordSpan>,
+    /// The call stack at the time of an error, innermost first
+    pub trace: Vec<RecordFrame>,
+}
+
+/// A 1-based line/column range in a [`MessageRecord`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct RecordSpan {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+/// A [`TraceFrame`] in a [`MessageRecord`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct RecordFrame {
+    pub function: String,
+    pub file: Option<PathBuf>,
+    pub span: Option<RecordSpan>,
+}
+
+impl MessageRecord {
+    fn new(kind: &'static str, message: impl Into<String>, span: &Span) -> Self {
+        let (file, span) = record_span(span);
+        MessageRecord {
+            kind,
+            message: message.into(),
+            file,
+            span,
+            trace: Vec::new(),
+        }
+    }
+    /// Serialize the record as a single line of JSON
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+fn record_span(span: &Span) -> (Option<PathBuf>, Option<RecordSpan>) {
+    match span {
+        Span::Code(span) => (
+            span.path.as_deref().map(Path::to_path_buf),
+            Some(RecordSpan {
+                start_line: span.start.line,
+                start_col: span.start.col,
+                end_line: span.end.line,
+                end_col: span.end.col,
+            }),
+        ),
+        Span::Builtin => (None, None),
+    }
+}
+
+impl UiuaError {
+    /// Get machine-readable records of the error
+    ///
+    /// Most errors produce a single record, but parse errors produce one per error.
+    pub fn records(&self) -> Vec<MessageRecord> {
+        const KIND: &str = "error";
+        match self {
+            UiuaError::Load(path, _) | UiuaError::Format(path, _) => vec![MessageRecord {
+                file: Some(path.clone()),
+                ..MessageRecord::new(KIND, self.to_string(), &Span::Builtin)
+            }],
+            UiuaError::Parse(errors) => errors
+                .iter()
+                .map(|error| {
+                    MessageRecord::new(KIND, error.value.to_string(), &error.span.clone().into())
+                })
+                .collect(),
+            UiuaError::Run(error) => vec![MessageRecord::new(KIND, &error.value, &error.span)],
+            UiuaError::Traced { error, trace } => {
+                let frames: Vec<RecordFrame> = trace
+                    .iter()
+                    .filter(|frame| frame.id != FunctionId::Main)
+                    .map(|frame| {
+                        let (file, span) = record_span(&frame.span);
+                        RecordFrame {
+                            function: frame.id.to_string(),
+                            file,
+                            span,
+                        }
+                    })
+                    .collect();
+                let mut records = error.records();
+                for record in &mut records {
+                    record.trace.extend(frames.iter().cloned());
+                }
+                records
+            }
+            UiuaError::Throw(value, span) => {
+                vec![MessageRecord::new(KIND, value.to_string(), span)]
+            }
+            UiuaError::Break(_, span) => vec![MessageRecord::new(
+                KIND,
+                "Break amount exceeded loop depth",
+                span,
+            )],
+            UiuaError::Timeout(span) => vec![MessageRecord::new(
+                KIND,
+                "Maximum execution time exceeded",
+                span,
+            )],
+            UiuaError::MemoryLimit(_, span) => {
+                vec![MessageRecord::new(KIND, self.to_string(), span)]
+            }
+            UiuaError::Fill(error) => error.records(),
+        }
+    }
+}
+
 /// A message to be displayed to the user that is not an error
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Diagnostic {
@@ -250,6 +382,15 @@ impl Diagnostic {
             kind,
         }
     }
+    /// Get a machine-readable record of the diagnostic
+    pub fn record(&self) -> MessageRecord {
+        let kind = match self.kind {
+            DiagnosticKind::Warning => "warning",
+            DiagnosticKind::Advice => "advice",
+            DiagnosticKind::Style => "style",
+        };
+        MessageRecord::new(kind, &self.message, &self.span)
+    }
     pub fn show(&self, color: bool) -> String {
         report(
             [(&self.message, self.span.clone())],
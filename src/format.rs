@@ -1,7 +1,6 @@
 //! Functions for formatting Uiua code.
 
 use std::{
-    any::Any,
     collections::BTreeMap,
     env,
     fmt::Display,
@@ -13,23 +12,15 @@ use paste::paste;
 
 use crate::{
     ast::*,
+    config::{load_config_file, search_config_file},
     function::Signature,
     grid_fmt::GridFmt,
     lex::{is_ident_char, CodeSpan, Loc, Sp},
     parse::parse,
     value::Value,
-    SysBackend, Uiua, UiuaError, UiuaResult,
+    Uiua, UiuaError, UiuaResult,
 };
 
-// For now disallow any syscalls in the format config file.
-struct FormatConfigBackend;
-
-impl SysBackend for FormatConfigBackend {
-    fn any(&self) -> &dyn Any {
-        self
-    }
-}
-
 trait ConfigValue: Sized {
     fn from_value(value: &Value, env: &Uiua, requirement: &'static str) -> UiuaResult<Self>;
 }
@@ -112,10 +103,7 @@ macro_rules! create_config {
         impl PartialFormatConfig {
             paste! {
                 fn from_file(file_path: PathBuf) -> UiuaResult<Self> {
-                    let mut env = Uiua::with_backend(FormatConfigBackend)
-                        .print_diagnostics(true);
-                    env.load_file(file_path)?;
-                    let mut bindings = env.all_bindings_in_scope();
+                    let (env, mut bindings) = load_config_file(file_path)?;
 
                     $(
                         let $name = {
@@ -248,7 +236,7 @@ impl FormatConfig {
     pub fn from_source(source: FormatConfigSource, target_path: Option<&Path>) -> UiuaResult<Self> {
         match source {
             FormatConfigSource::SearchFile => {
-                if let Some(file_path) = Self::search_config_file(target_path) {
+                if let Some(file_path) = search_config_file(".fmt.ua", target_path) {
                     Self::from_file(file_path)
                 } else {
                     Ok(Self::default())
@@ -258,21 +246,6 @@ impl FormatConfig {
             FormatConfigSource::Path(file_path) => Self::from_file(file_path),
         }
     }
-
-    fn search_config_file(path: Option<&Path>) -> Option<PathBuf> {
-        let mut path = path
-            .and_then(|p| std::fs::canonicalize(p).ok())
-            .unwrap_or(env::current_dir().ok()?);
-        loop {
-            let file_path = path.join(".fmt.ua");
-            if file_path.exists() {
-                return Some(file_path);
-            }
-            if !path.pop() {
-                return None;
-            }
-        }
-    }
 }
 
 pub struct FormatOutput {
@@ -1,5 +1,6 @@
 use std::{
     cmp::Ordering,
+    collections::hash_map::DefaultHasher,
     fmt,
     hash::{Hash, Hasher},
     mem::{discriminant, transmute},
@@ -143,6 +144,20 @@ impl Instr {
             _ => None,
         }
     }
+    /// Get the index of the instruction's span, if it has one
+    pub fn span(&self) -> Option<usize> {
+        match self {
+            Instr::Push(_) | Instr::BeginArray | Instr::Dynamic(_) => None,
+            Instr::Prim(_, span) | Instr::Call(span) => Some(*span),
+            Instr::EndArray { span, .. }
+            | Instr::PushTempUnder { span, .. }
+            | Instr::PopTempUnder { span, .. }
+            | Instr::PushTempInline { span, .. }
+            | Instr::PopTempInline { span, .. }
+            | Instr::CopyTempInline { span, .. }
+            | Instr::DropTempInline { span, .. } => Some(*span),
+        }
+    }
     pub fn is_temp(&self) -> bool {
         matches!(
             self,
@@ -250,6 +265,111 @@ pub struct DynamicFunction {
     pub id: u64,
     pub f: Arc<dyn Fn(&mut Uiua) -> UiuaResult + Send + Sync>,
     pub signature: Signature,
+    /// What the function does, if it is a kind that can be serialized
+    pub kind: Option<DynamicKind>,
+}
+
+/// What a serializable [`DynamicFunction`] does
+#[derive(Debug, Clone)]
+pub enum DynamicKind {
+    /// Format a string, with the fragments of each line between the arguments
+    Format(Arc<[Vec<String>]>),
+    /// Push the value of a global that is bound when a built program is loaded,
+    /// and call it with the span if it is a function that should be called
+    LoadGlobal(usize, Option<usize>),
+    /// Bind a global when a built program is loaded, with its declared signature
+    BindGlobal(usize, Ident, Option<Signature>),
+}
+
+impl DynamicFunction {
+    /// Create a function that formats its arguments into a string
+    ///
+    /// Each line is a list of the fragments between the arguments.
+    pub fn format_string(lines: Vec<Vec<String>>) -> Self {
+        let signature = Signature::new(lines.iter().map(|line| line.len() - 1).sum(), 1);
+        let id = {
+            let mut hasher = DefaultHasher::new();
+            lines.hash(&mut hasher);
+            hasher.finish()
+        };
+        let lines: Arc<[Vec<String>]> = lines.into();
+        let format_lines = lines.clone();
+        DynamicFunction {
+            id,
+            f: Arc::new(move |env| {
+                let mut formatted = String::new();
+                let mut i = 0;
+                for (j, line) in lines.iter().enumerate() {
+                    if j > 0 {
+                        formatted.push('\n');
+                    }
+                    for (k, frag) in line.iter().enumerate() {
+                        if k > 0 {
+                            let val = env.pop(format!("format argument {i}"))?;
+                            formatted.push_str(&format!("{}", val));
+                        }
+                        formatted.push_str(frag);
+                        i += 1;
+                    }
+                }
+                env.push(formatted);
+                Ok(())
+            }),
+            signature,
+            kind: Some(DynamicKind::Format(format_lines)),
+        }
+    }
+    /// Create a function that pushes the value of a global
+    ///
+    /// This is used for bindings that are only bound when a built program is loaded.
+    /// If a call span is given and the value is a function, it is called.
+    pub fn load_global(index: usize, call: Option<usize>) -> Self {
+        let id = {
+            let mut hasher = DefaultHasher::new();
+            ("load global", index, call).hash(&mut hasher);
+            hasher.finish()
+        };
+        DynamicFunction {
+            id,
+            f: Arc::new(move |env| {
+                let value = env.globals.lock()[index].clone();
+                let should_call = matches!(&value, Value::Func(f) if f.shape.is_empty());
+                match call {
+                    Some(span) if should_call => env.call_with_span(value, span),
+                    _ => {
+                        env.push(value);
+                        Ok(())
+                    }
+                }
+            }),
+            // The real signature is not known until the program is loaded
+            signature: Signature::new(0, 1),
+            kind: Some(DynamicKind::LoadGlobal(index, call)),
+        }
+    }
+    /// Create a function that pops a value and binds it to a global
+    ///
+    /// Built programs do not run this directly. They bind the line's code
+    /// the way the compiler would have instead.
+    pub fn bind_global(index: usize, name: Ident, signature: Option<Signature>) -> Self {
+        let id = {
+            let mut hasher = DefaultHasher::new();
+            ("bind global", index).hash(&mut hasher);
+            hasher.finish()
+        };
+        let kind = DynamicKind::BindGlobal(index, name.clone(), signature);
+        DynamicFunction {
+            id,
+            f: Arc::new(move |env| {
+                let mut value = env.pop(&*name)?;
+                value.compress();
+                env.globals.lock()[index] = value;
+                Ok(())
+            }),
+            signature: Signature::new(1, 0),
+            kind: Some(kind),
+        }
+    }
 }
 
 impl fmt::Debug for DynamicFunction {
@@ -391,6 +511,21 @@ impl Function {
     pub fn is_constant(&self) -> bool {
         matches!(&*self.instrs, [Instr::Push(_)])
     }
+    /// Check if calling the function has no side effects
+    ///
+    /// Functions pushed by this one must also be pure.
+    /// Dynamic functions are never considered pure.
+    pub fn is_pure(&self) -> bool {
+        self.instrs.iter().all(|instr| match instr {
+            Instr::Push(val) => match &**val {
+                Value::Func(fs) => fs.data.iter().all(|f| f.is_pure()),
+                _ => true,
+            },
+            Instr::Prim(prim, _) => prim.is_pure(),
+            Instr::Dynamic(_) => false,
+            _ => true,
+        })
+    }
     pub fn boxed(value: impl Into<Value>) -> Self {
         Function::new(
             FunctionId::Constant,
@@ -0,0 +1,673 @@
+//! A minimal HTTP/1.1 client and server helpers
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::Arc,
+};
+
+use once_cell::sync::Lazy;
+
+use crate::{Handle, HttpRequest, HttpResponse, SysBackend};
+
+/// The maximum number of redirects to follow
+const MAX_REDIRECTS: usize = 10;
+/// The maximum size of a request body that a server will read
+const MAX_BODY_LEN: usize = 256 * 1024 * 1024;
+/// The maximum size of a request's head, including the request line and headers
+const MAX_HEAD_LEN: usize = 16 * 1024;
+/// The maximum length of a line that gives the size of a chunk in a chunked body
+const MAX_CHUNK_LINE_LEN: usize = 1024;
+/// The most bytes to read from a stream at once
+const READ_CHUNK_LEN: usize = 64 * 1024;
+
+// https://github.com/rustls/rustls/blob/c9cfe3499681361372351a57a00ccd793837ae9c/examples/src/bin/simpleclient.rs
+pub(crate) static CLIENT_CONFIG: Lazy<Arc<rustls::ClientConfig>> = Lazy::new(|| {
+    let mut store = rustls::RootCertStore::empty();
+    store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(store)
+        .with_no_client_auth()
+        .into()
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Url {
+    https: bool,
+    host: String,
+    port: u16,
+    /// The path and query, starting with `/`
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self, String> {
+        let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(format!("URL must start with http:// or https://: {url}"));
+        };
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, "/".into()),
+        };
+        let authority = authority.rsplit_once('@').map_or(authority, |(_, a)| a);
+        // The port comes after the last colon, unless that colon is inside an IPv6 address
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("Invalid port in URL: {url}"))?;
+                (host, port)
+            }
+            _ => (authority, if https { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            return Err(format!("URL has no host: {url}"));
+        }
+        Ok(Url {
+            https,
+            host: host.into(),
+            port,
+            path,
+        })
+    }
+    fn default_port(&self) -> bool {
+        self.port == if self.https { 443 } else { 80 }
+    }
+    fn authority(&self) -> String {
+        if self.default_port() {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+    /// Resolve a redirect location against this URL
+    fn join(&self, location: &str) -> Result<Self, String> {
+        let scheme = if self.https { "https" } else { "http" };
+        let absolute = if location.contains("://") {
+            location.to_string()
+        } else if let Some(rest) = location.strip_prefix("//") {
+            format!("{scheme}://{rest}")
+        } else if location.starts_with('/') {
+            format!("{scheme}://{}{location}", self.authority())
+        } else {
+            let path = self.path.split('?').next().unwrap_or_default();
+            let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+            format!("{scheme}://{}{dir}{location}", self.authority())
+        };
+        Url::parse(&absolute)
+    }
+}
+
+/// Send a request, following redirects
+pub(crate) fn request(mut req: HttpRequest) -> Result<HttpResponse, String> {
+    let mut url = Url::parse(&req.url)?;
+    for _ in 0..=MAX_REDIRECTS {
+        let res = request_once(&req, &url)?;
+        if !matches!(res.status, 301 | 302 | 303 | 307 | 308) {
+            return Ok(res);
+        }
+        let Some(location) = res.header("location") else {
+            return Ok(res);
+        };
+        url = url.join(location)?;
+        // Only 307 and 308 preserve the method and body
+        let keep_method = matches!(res.status, 307 | 308)
+            || req.method.eq_ignore_ascii_case("GET")
+            || req.method.eq_ignore_ascii_case("HEAD");
+        if !keep_method {
+            req.method = "GET".into();
+            req.body.clear();
+        }
+    }
+    Err(format!("Too many redirects (more than {MAX_REDIRECTS})"))
+}
+
+fn request_once(req: &HttpRequest, url: &Url) -> Result<HttpResponse, String> {
+    let mut head = format!("{} {} HTTP/1.1\r\n", req.method, url.path);
+    let has_header = |name: &str| {
+        req.headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case(name))
+    };
+    if !has_header("host") {
+        head.push_str(&format!("Host: {}\r\n", url.authority()));
+    }
+    if !has_header("user-agent") {
+        head.push_str("User-Agent: uiua\r\n");
+    }
+    if !req.body.is_empty() && !has_header("content-length") {
+        head.push_str(&format!("Content-Length: {}\r\n", req.body.len()));
+    }
+    for (name, value) in &req.headers {
+        // The response is read until the connection closes
+        if !name.eq_ignore_ascii_case("connection") {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    let mut data = head.into_bytes();
+    data.extend_from_slice(&req.body);
+
+    let stream = TcpStream::connect((url.host.as_str(), url.port)).map_err(|e| e.to_string())?;
+    let raw = if url.https {
+        let server_name =
+            rustls::ServerName::try_from(url.host.as_str()).map_err(|e| e.to_string())?;
+        let conn = rustls::ClientConnection::new(CLIENT_CONFIG.clone(), server_name)
+            .map_err(|e| e.to_string())?;
+        let mut tls = rustls::StreamOwned::new(conn, stream);
+        tls.write_all(&data).map_err(|e| e.to_string())?;
+        read_all(tls)?
+    } else {
+        let mut stream = stream;
+        stream.write_all(&data).map_err(|e| e.to_string())?;
+        read_all(stream)?
+    };
+    parse_response(&raw, req.method.eq_ignore_ascii_case("HEAD"))
+}
+
+fn read_all(mut reader: impl Read) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    match reader.read_to_end(&mut buffer) {
+        Ok(_) => Ok(buffer),
+        // Some servers close TLS connections without notifying the client
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && !buffer.is_empty() => Ok(buffer),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_response(raw: &[u8], head_only: bool) -> Result<HttpResponse, String> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut res = httparse::Response::new(&mut headers);
+    let offset = match res.parse(raw) {
+        Ok(httparse::Status::Complete(offset)) => offset,
+        Ok(httparse::Status::Partial) => return Err("Incomplete HTTP response".into()),
+        Err(e) => return Err(format!("Failed to parse HTTP response: {e}")),
+    };
+    let status = res.code.unwrap_or_default();
+    let headers: Vec<(String, String)> = (res.headers.iter())
+        .map(|h| (h.name.into(), String::from_utf8_lossy(h.value).into_owned()))
+        .collect();
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    if head_only || status == 204 || status == 304 || (100..200).contains(&status) {
+        return Ok(response);
+    }
+    let rest = &raw[offset..];
+    let chunked = (response.header("transfer-encoding"))
+        .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"));
+    response.body = if chunked {
+        decode_chunked(rest)?
+    } else if let Some(len) = response.header("content-length") {
+        let len: usize = (len.trim().parse()).map_err(|_| "Invalid Content-Length header")?;
+        if rest.len() < len {
+            return Err(format!(
+                "HTTP response body ended after {} of {len} bytes",
+                rest.len()
+            ));
+        }
+        rest[..len].to_vec()
+    } else {
+        rest.to_vec()
+    };
+    Ok(response)
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = (data.windows(2).position(|w| w == b"\r\n"))
+            .ok_or("Invalid chunked HTTP body: missing chunk size")?;
+        let line = String::from_utf8_lossy(&data[..line_end]);
+        // Chunk extensions come after a semicolon
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| format!("Invalid chunked HTTP body: bad chunk size {size:?}"))?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            // Trailers are ignored
+            return Ok(body);
+        }
+        if data.len() < size {
+            return Err("Invalid chunked HTTP body: chunk is truncated".into());
+        }
+        body.extend_from_slice(&data[..size]);
+        data = data[size..].strip_prefix(b"\r\n").unwrap_or(&data[size..]);
+    }
+}
+
+/// Read one complete request from a stream
+///
+/// The request's `url` is the request target, like `/path?query`.
+pub(crate) fn read_request<B: SysBackend + ?Sized>(
+    backend: &B,
+    handle: Handle,
+) -> Result<HttpRequest, String> {
+    let Some(head) = read_until_limit(backend, handle, b"\r\n\r\n", MAX_HEAD_LEN)? else {
+        let response = HttpResponse {
+            status: 431,
+            headers: vec![("Connection".into(), "close".into())],
+            body: Vec::new(),
+        };
+        write_response(backend, handle, response)?;
+        return Err(format!(
+            "HTTP request head is larger than the limit of {MAX_HEAD_LEN} bytes"
+        ));
+    };
+    if head.is_empty() {
+        return Err("Connection closed before a request was received".into());
+    }
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut headers);
+    match req.parse(&head) {
+        Ok(httparse::Status::Complete(_)) => {}
+        Ok(httparse::Status::Partial) => return Err("Incomplete HTTP request".into()),
+        Err(e) => return Err(format!("Failed to parse HTTP request: {e}")),
+    }
+    let mut request = HttpRequest {
+        method: req.method.unwrap_or_default().into(),
+        url: req.path.unwrap_or_default().into(),
+        headers: (req.headers.iter())
+            .map(|h| (h.name.into(), String::from_utf8_lossy(h.value).into_owned()))
+            .collect(),
+        body: Vec::new(),
+    };
+    let header = |name: &str| {
+        (request.headers.iter())
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.clone())
+    };
+    let chunked = header("transfer-encoding").is_some_and(|te| te.contains("chunked"));
+    if chunked {
+        let mut raw = Vec::new();
+        loop {
+            let line = read_until_limit(backend, handle, b"\r\n", MAX_CHUNK_LINE_LEN)?.ok_or_else(
+                || {
+                    format!(
+                        "Invalid chunked HTTP body: chunk size line is longer \
+                        than the limit of {MAX_CHUNK_LINE_LEN} bytes"
+                    )
+                },
+            )?;
+            let size = String::from_utf8_lossy(&line);
+            let size = size.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .map_err(|_| format!("Invalid chunked HTTP body: bad chunk size {size:?}"))?;
+            if raw.len().saturating_add(size) > MAX_BODY_LEN {
+                return Err(body_too_large());
+            }
+            raw.extend_from_slice(&line);
+            if size == 0 {
+                raw.extend(read_exact(backend, handle, 2)?);
+                break;
+            }
+            raw.extend(read_exact(backend, handle, size + 2)?);
+        }
+        request.body = decode_chunked(&raw)?;
+    } else if let Some(len) = header("content-length") {
+        let len: u64 = (len.trim().parse()).map_err(|_| "Invalid Content-Length header")?;
+        if len > MAX_BODY_LEN as u64 {
+            return Err(body_too_large());
+        }
+        request.body = read_exact(backend, handle, len as usize)?;
+    }
+    Ok(request)
+}
+
+/// Read until a delimiter, giving up if more than `limit` bytes come before it
+///
+/// Returns `None` if the limit is exceeded.
+fn read_until_limit<B: SysBackend + ?Sized>(
+    backend: &B,
+    handle: Handle,
+    delim: &[u8],
+    limit: usize,
+) -> Result<Option<Vec<u8>>, String> {
+    // Bytes are read one at a time so that nothing after the delimiter is consumed
+    let mut buffer = Vec::new();
+    while !buffer.ends_with(delim) {
+        if buffer.len() >= limit {
+            return Ok(None);
+        }
+        let bytes = backend.read(handle, 1)?;
+        if bytes.is_empty() {
+            break;
+        }
+        buffer.extend(bytes);
+    }
+    Ok(Some(buffer))
+}
+
+fn read_exact<B: SysBackend + ?Sized>(
+    backend: &B,
+    handle: Handle,
+    len: usize,
+) -> Result<Vec<u8>, String> {
+    // The buffer grows as data arrives rather than trusting the length up front
+    let mut bytes = Vec::new();
+    while bytes.len() < len {
+        let read = backend.read(handle, (len - bytes.len()).min(READ_CHUNK_LEN))?;
+        if read.is_empty() {
+            return Err("Connection closed before the HTTP body was received".into());
+        }
+        bytes.extend(read);
+    }
+    Ok(bytes)
+}
+
+fn body_too_large() -> String {
+    format!("HTTP request body is larger than the limit of {MAX_BODY_LEN} bytes")
+}
+
+/// Write a response to a stream
+///
+/// A `Content-Length` header is added if there is none.
+pub(crate) fn write_response<B: SysBackend + ?Sized>(
+    backend: &B,
+    handle: Handle,
+    response: HttpResponse,
+) -> Result<(), String> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason_phrase(response.status)
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if response.header("content-length").is_none() {
+        head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+    }
+    head.push_str("\r\n");
+    let mut bytes = head.into_bytes();
+    bytes.extend(response.body);
+    backend.write(handle, &bytes)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{value::Value, Uiua};
+    use std::{io::BufRead, net::TcpListener, thread};
+
+    #[test]
+    fn url_parsing() {
+        let url = Url::parse("http://localhost:8080/a/b?c=d").unwrap();
+        assert_eq!(
+            (url.host.as_str(), url.port, url.path.as_str()),
+            ("localhost", 8080, "/a/b?c=d")
+        );
+        let url = Url::parse("https://example.com?q").unwrap();
+        assert_eq!((url.port, url.path.as_str()), (443, "/?q"));
+        assert_eq!(url.join("x/y").unwrap().path, "/x/y");
+        assert_eq!(url.join("//other.org/z").unwrap().host, "other.org");
+        assert!(Url::parse("ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+                let mut first = String::new();
+                reader.read_line(&mut first).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(len) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let response = if first.starts_with("POST /old ") {
+                    "HTTP/1.1 303 See Other\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n".into()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nX-Method: {}\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n",
+                        first.split(' ').next().unwrap()
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        let mut env = Uiua::with_native_sys();
+        env.load_str(&format!(
+            r#"&httprs "POST" "http://{addr}/old" {{"Content-Type" "text/plain"}} "data""#
+        ))
+        .unwrap();
+        let status = env.pop("status").unwrap();
+        let headers = env.pop("headers").unwrap();
+        let body = env.pop("body").unwrap();
+        assert_eq!(status, Value::from(200.0));
+        assert_eq!(body.as_string(&env, "").unwrap(), "hello, world");
+        assert_eq!(headers.shape(), [2, 2]);
+        let method = headers
+            .into_rows()
+            .next()
+            .unwrap()
+            .into_rows()
+            .nth(1)
+            .unwrap();
+        assert_eq!(method.as_string(&env, "").unwrap(), "GET");
+    }
+
+    #[test]
+    fn serve_request() {
+        // Find a free port for the Uiua listener
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = thread::spawn(move || {
+            let mut stream = loop {
+                if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+                    break stream;
+                }
+                thread::sleep(std::time::Duration::from_millis(10));
+            };
+            stream
+                .write_all(
+                    b"POST /echo?x=1 HTTP/1.1\r\nHost: localhost\r\n\
+                    Transfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+                )
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let mut env = Uiua::with_native_sys();
+        env.load_str(&format!(
+            r#"
+            Listener ← &tcpl "127.0.0.1:{port}"
+            Client ← &tcpa Listener
+            &httprq Client
+            "#
+        ))
+        .unwrap();
+        let mut pop = |name| {
+            let value = env.pop(name).unwrap();
+            value.show()
+        };
+        assert_eq!(pop("method"), "\"POST\"");
+        assert_eq!(pop("path"), "\"/echo\"");
+        assert_eq!(pop("query"), "\"x=1\"");
+        assert!(pop("headers").contains("Transfer-Encoding"));
+        assert_eq!(pop("body"), "[97 98 99 100 101]");
+        env.load_str(
+            r#"
+            &httpw 200 {"X-Query" "x=1"} "hello" Client
+            &cl Client
+            &cl Listener
+            "#,
+        )
+        .unwrap();
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("X-Query: x=1\r\n"), "{response}");
+        assert!(response.contains("Content-Length: 5\r\n"), "{response}");
+        assert!(response.ends_with("\r\n\r\nhello"), "{response}");
+    }
+
+    #[test]
+    fn huge_content_length() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = thread::spawn(move || {
+            let mut stream = loop {
+                if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+                    break stream;
+                }
+                thread::sleep(std::time::Duration::from_millis(10));
+            };
+            stream
+                .write_all(b"POST / HTTP/1.1\r\nContent-Length: 99999999999999999\r\n\r\nabc")
+                .unwrap();
+        });
+        let mut env = Uiua::with_native_sys();
+        let err = env
+            .load_str(&format!(
+                r#"
+                Listener ← &tcpl "127.0.0.1:{port}"
+                Client ← &tcpa Listener
+                &httprq Client
+                "#
+            ))
+            .unwrap_err();
+        assert!(err.to_string().contains("larger than the limit"), "{err}");
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn huge_head() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = thread::spawn(move || {
+            let mut stream = loop {
+                if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+                    break stream;
+                }
+                thread::sleep(std::time::Duration::from_millis(10));
+            };
+            let head = format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(20_000));
+            stream.write_all(head.as_bytes()).unwrap();
+            let mut status = String::new();
+            io::BufReader::new(stream).read_line(&mut status).unwrap();
+            status
+        });
+        let mut env = Uiua::with_native_sys();
+        let err = env
+            .load_str(&format!(
+                r#"
+                Listener ← &tcpl "127.0.0.1:{port}"
+                Client ← &tcpa Listener
+                &httprq Client
+                "#
+            ))
+            .unwrap_err();
+        assert!(err.to_string().contains("head is larger"), "{err}");
+        // The response is sent once the connection is closed
+        env.load_str("&cl Client").unwrap();
+        let status = client.join().unwrap();
+        assert!(status.starts_with("HTTP/1.1 431 "), "{status}");
+    }
+
+    #[test]
+    fn huge_chunk_line() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = thread::spawn(move || {
+            let mut stream = loop {
+                if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+                    break stream;
+                }
+                thread::sleep(std::time::Duration::from_millis(10));
+            };
+            let request = format!(
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{}3\r\nabc\r\n",
+                "0".repeat(5000)
+            );
+            stream.write_all(request.as_bytes()).unwrap();
+        });
+        let mut env = Uiua::with_native_sys();
+        let err = env
+            .load_str(&format!(
+                r#"
+                Listener ← &tcpl "127.0.0.1:{port}"
+                Client ← &tcpa Listener
+                &httprq Client
+                "#
+            ))
+            .unwrap_err();
+        assert!(err.to_string().contains("chunk size line"), "{err}");
+        client.join().unwrap();
+    }
+}
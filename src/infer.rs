@@ -0,0 +1,723 @@
+//! Static inference of the element types and shapes of values
+//!
+//! This pass interprets compiled instructions abstractly and reports
+//! operations that will likely fail at runtime.
+
+use std::fmt;
+
+use crate::{
+    check::instrs_signature,
+    function::{Function, FunctionId, Instr, Signature},
+    lex::Span,
+    primitive::{PrimClass, Primitive},
+    value::Value,
+    Diagnostic, DiagnosticKind,
+};
+
+/// The maximum depth of function calls that will be followed
+const MAX_DEPTH: usize = 8;
+
+/// The type of the elements of an array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ElemType {
+    Num,
+    Byte,
+    Char,
+    Box,
+    Func,
+}
+
+impl ElemType {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Num(_) => ElemType::Num,
+            Value::Byte(_) => ElemType::Byte,
+            Value::Char(_) => ElemType::Char,
+            Value::Func(fs) if fs.data.iter().all(|f| f.as_boxed().is_some()) => ElemType::Box,
+            Value::Func(_) => ElemType::Func,
+        }
+    }
+    fn is_numeric(self) -> bool {
+        matches!(self, ElemType::Num | ElemType::Byte)
+    }
+    /// The name used for this type in runtime errors
+    fn error_name(self) -> &'static str {
+        match self {
+            ElemType::Num | ElemType::Byte => "number",
+            ElemType::Char => "character",
+            ElemType::Box | ElemType::Func => "function",
+        }
+    }
+}
+
+impl fmt::Display for ElemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElemType::Num => write!(f, "num"),
+            ElemType::Byte => write!(f, "byte"),
+            ElemType::Char => write!(f, "char"),
+            ElemType::Box => write!(f, "box"),
+            ElemType::Func => write!(f, "func"),
+        }
+    }
+}
+
+/// What is statically known about a value
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InferredType<'a> {
+    /// The element type, if it is known
+    pub elem: Option<ElemType>,
+    /// The shape, if the rank is known
+    ///
+    /// Dimensions that are not known are `None`.
+    pub shape: Option<Vec<Option<usize>>>,
+    /// The value itself, if it is a constant
+    constant: Option<&'a Value>,
+}
+
+impl<'a> InferredType<'a> {
+    fn new(elem: Option<ElemType>, shape: Option<Vec<Option<usize>>>) -> Self {
+        InferredType {
+            elem,
+            shape,
+            constant: None,
+        }
+    }
+    fn scalar(elem: ElemType) -> Self {
+        Self::new(Some(elem), Some(Vec::new()))
+    }
+    pub fn from_value(value: &'a Value) -> Self {
+        InferredType {
+            elem: Some(ElemType::from_value(value)),
+            shape: Some(value.shape().iter().copied().map(Some).collect()),
+            constant: Some(value),
+        }
+    }
+    /// Check if anything is known about the value
+    pub fn is_known(&self) -> bool {
+        self.elem.is_some() || self.shape.is_some()
+    }
+    fn rank(&self) -> Option<usize> {
+        self.shape.as_ref().map(Vec::len)
+    }
+    fn row_count(&self) -> Option<usize> {
+        self.shape.as_ref()?.first().copied().flatten()
+    }
+    fn function(&self) -> Option<&'a Function> {
+        let f = self.constant?.as_func_array()?.as_scalar()?;
+        Some(&**f)
+    }
+}
+
+impl<'a> fmt::Display for InferredType<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.elem {
+            Some(elem) => write!(f, "{elem}")?,
+            None => write!(f, "?")?,
+        }
+        if let Some(shape) = &self.shape {
+            write!(f, " {}", FormatShape(shape))?;
+        }
+        Ok(())
+    }
+}
+
+struct FormatShape<'a>(&'a [Option<usize>]);
+
+impl<'a> fmt::Display for FormatShape<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, dim) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match dim {
+                Some(dim) => write!(f, "{dim}")?,
+                None => write!(f, "_")?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+/// Infer the types and shapes of values in some instructions
+///
+/// Returns any diagnostics that were found, as well as the types of the values left on the stack.
+pub(crate) fn infer_types<'a>(
+    instrs: &'a [Instr],
+    spans: &[Span],
+) -> (Vec<Diagnostic>, Vec<InferredType<'a>>) {
+    let mut env = TypeEnv::new(spans, false, 0);
+    env.instrs(instrs);
+    let stack = env.stack.clone();
+    env.check_remaining_functions();
+    (env.diagnostics, stack)
+}
+
+/// Compile some code without running any of it and get the inferred types of its bindings
+///
+/// The types are keyed by the spans of the bindings' names.
+#[cfg(any(feature = "lsp", test))]
+pub(crate) fn binding_types(
+    input: &str,
+) -> std::collections::HashMap<crate::lex::CodeSpan, String> {
+    use crate::{bytecode::Build, SandboxSys, Uiua};
+    let mut env = Uiua::with_backend(SandboxSys::default()).optimize(false);
+    env.build = Some(Build {
+        check: true,
+        ..Build::default()
+    });
+    _ = env.load_str(input);
+    env.binding_types
+}
+
+/// An environment that emulates the runtime but only keeps track of what is known about values
+struct TypeEnv<'a, 's> {
+    spans: &'s [Span],
+    stack: Vec<InferredType<'a>>,
+    /// The stack heights at the start of arrays, and whether those heights are still accurate
+    array_stack: Vec<(usize, bool)>,
+    inline_stack: Vec<InferredType<'a>>,
+    under_stack: Vec<InferredType<'a>>,
+    /// Whether a fill value may be set
+    filled: bool,
+    depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a, 's> TypeEnv<'a, 's> {
+    fn new(spans: &'s [Span], filled: bool, depth: usize) -> Self {
+        TypeEnv {
+            spans,
+            stack: Vec::new(),
+            array_stack: Vec::new(),
+            inline_stack: Vec::new(),
+            under_stack: Vec::new(),
+            filled,
+            depth,
+            diagnostics: Vec::new(),
+        }
+    }
+    fn instrs(&mut self, instrs: &'a [Instr]) {
+        for instr in instrs {
+            self.instr(instr);
+        }
+    }
+    fn instr(&mut self, instr: &'a Instr) {
+        match instr {
+            Instr::Push(val) => self.stack.push(InferredType::from_value(val)),
+            Instr::BeginArray => self.array_stack.push((self.stack.len(), true)),
+            Instr::EndArray { boxed, .. } => {
+                let (bottom, accurate) =
+                    self.array_stack.pop().unwrap_or((self.stack.len(), false));
+                let items: Vec<_> = self.stack.drain(bottom.min(self.stack.len())..).collect();
+                let array = array_type(&items, *boxed, accurate);
+                self.stack.push(array);
+            }
+            Instr::Call(_) => self.call(),
+            Instr::Dynamic(f) => self.handle_sig(f.signature),
+            Instr::PushTempInline { count, .. } => {
+                for _ in 0..*count {
+                    let val = self.pop();
+                    self.inline_stack.push(val);
+                }
+            }
+            Instr::PushTempUnder { count, .. } => {
+                for _ in 0..*count {
+                    let val = self.pop();
+                    self.under_stack.push(val);
+                }
+            }
+            Instr::PopTempInline { count, .. } => {
+                for _ in 0..*count {
+                    let val = self.inline_stack.pop().unwrap_or_default();
+                    self.stack.push(val);
+                }
+            }
+            Instr::PopTempUnder { count, .. } => {
+                for _ in 0..*count {
+                    let val = self.under_stack.pop().unwrap_or_default();
+                    self.stack.push(val);
+                }
+            }
+            Instr::CopyTempInline { offset, count, .. } => {
+                let len = self.inline_stack.len();
+                for i in 0..*count {
+                    let val = (len.checked_sub(offset + i + 1))
+                        .and_then(|j| self.inline_stack.get(j))
+                        .cloned()
+                        .unwrap_or_default();
+                    self.stack.push(val);
+                }
+            }
+            Instr::DropTempInline { count, .. } => {
+                let len = self.inline_stack.len();
+                self.inline_stack.truncate(len.saturating_sub(*count));
+            }
+            Instr::Prim(prim, span) => self.prim(*prim, *span),
+        }
+    }
+    fn prim(&mut self, prim: Primitive, span: usize) {
+        use Primitive::*;
+        if prim.as_constant().is_some() {
+            self.stack.push(InferredType::scalar(ElemType::Num));
+            return;
+        }
+        match prim {
+            Dup => {
+                let a = self.pop();
+                self.stack.push(a.clone());
+                self.stack.push(a);
+            }
+            Flip => {
+                let a = self.pop();
+                let b = self.pop();
+                self.stack.push(a);
+                self.stack.push(b);
+            }
+            Over => {
+                let a = self.pop();
+                let b = self.pop();
+                self.stack.push(b.clone());
+                self.stack.push(a);
+                self.stack.push(b);
+            }
+            Pop => {
+                self.pop();
+            }
+            Identity => {}
+            Call => self.call(),
+            Box => {
+                self.pop();
+                self.stack.push(InferredType::scalar(ElemType::Box));
+            }
+            Len => {
+                self.pop();
+                self.stack.push(InferredType::scalar(ElemType::Num));
+            }
+            Shape => {
+                let rank = self.pop().rank();
+                self.stack
+                    .push(InferredType::new(Some(ElemType::Num), Some(vec![rank])));
+            }
+            Range => {
+                let n = self.pop();
+                let shape = match n.rank() {
+                    Some(0) => Some(vec![n
+                        .constant
+                        .and_then(Value::as_num_array)
+                        .and_then(|n| n.as_scalar())
+                        .filter(|n| n.fract() == 0.0 && **n >= 0.0)
+                        .map(|n| *n as usize)]),
+                    Some(1) => n.row_count().map(|rank| vec![None; rank + 1]),
+                    _ => None,
+                };
+                self.stack
+                    .push(InferredType::new(Some(ElemType::Num), shape));
+            }
+            Reverse => {
+                let a = self.pop();
+                self.stack.push(InferredType::new(a.elem, a.shape));
+            }
+            Deshape => {
+                let a = self.pop();
+                let len = a
+                    .shape
+                    .and_then(|shape| shape.into_iter().product::<Option<usize>>());
+                self.stack.push(InferredType::new(a.elem, Some(vec![len])));
+            }
+            First | Last => {
+                let a = self.pop();
+                let shape = a.shape.filter(|shape| !shape.is_empty());
+                let shape = shape.map(|shape| shape[1..].to_vec());
+                self.stack.push(InferredType::new(a.elem, shape));
+            }
+            Transpose => {
+                let a = self.pop();
+                let shape = a.shape.map(|mut shape| {
+                    if !shape.is_empty() {
+                        shape.rotate_left(1);
+                    }
+                    shape
+                });
+                self.stack.push(InferredType::new(a.elem, shape));
+            }
+            Rise | Fall => {
+                let a = self.pop();
+                let shape = Some(vec![a.row_count()]);
+                self.stack
+                    .push(InferredType::new(Some(ElemType::Num), shape));
+            }
+            Couple => {
+                let a = self.pop();
+                let b = self.pop();
+                if let (Some(sa), Some(sb), false) = (&a.shape, &b.shape, self.filled) {
+                    if !shapes_match(sa, sb, false) {
+                        self.warn(
+                            span,
+                            format!(
+                                "Cannot couple arrays with shapes {} and {}",
+                                FormatShape(sa),
+                                FormatShape(sb)
+                            ),
+                        );
+                    }
+                }
+                let elem = match (a.elem, b.elem) {
+                    (Some(ea), Some(eb)) if ea == eb => Some(ea),
+                    (Some(ea), Some(eb)) if ea.is_numeric() && eb.is_numeric() => {
+                        Some(ElemType::Num)
+                    }
+                    _ => None,
+                };
+                let shape = (a.shape.zip(b.shape))
+                    .filter(|(sa, sb)| sa.len() == sb.len())
+                    .map(|(sa, sb)| {
+                        let mut shape = vec![Some(2)];
+                        shape.extend(sa.into_iter().zip(sb).map(|(da, db)| da.or(db)));
+                        shape
+                    });
+                self.stack.push(InferredType::new(elem, shape));
+            }
+            Select => {
+                let indices = self.pop();
+                let array = self.pop();
+                self.check_indices(prim, span, &indices, array.row_count());
+                let shape = (indices.shape.zip(array.shape))
+                    .filter(|(_, array_shape)| !array_shape.is_empty())
+                    .map(|(mut shape, array_shape)| {
+                        shape.extend_from_slice(&array_shape[1..]);
+                        shape
+                    });
+                self.stack.push(InferredType::new(array.elem, shape));
+            }
+            Pick => {
+                let index = self.pop();
+                let array = self.pop();
+                self.check_indices(prim, span, &index, None);
+                self.stack.push(InferredType::new(array.elem, None));
+            }
+            prim if prim.class() == PrimClass::MonadicPervasive => {
+                let a = self.pop();
+                let elem = a.elem.filter(|&elem| elem == ElemType::Num);
+                self.stack.push(InferredType::new(elem, a.shape));
+            }
+            prim if prim.class() == PrimClass::DyadicPervasive => self.dyadic_pervasive(prim, span),
+            prim => match (prim.modifier_args(), prim.args(), prim.outputs()) {
+                (Some(count), ..) => self.modifier(prim, span, count as usize),
+                (None, Some(args), Some(outputs)) => {
+                    self.handle_sig(Signature::new(args as usize, outputs as usize))
+                }
+                _ => self.lose_track(),
+            },
+        }
+    }
+    fn dyadic_pervasive(&mut self, prim: Primitive, span: usize) {
+        let a = self.pop();
+        let b = self.pop();
+        if let (Some(sa), Some(sb), false) = (&a.shape, &b.shape, self.filled) {
+            if !shapes_match(sa, sb, true) {
+                self.warn(
+                    span,
+                    format!(
+                        "Shapes {} and {} do not match",
+                        FormatShape(sa),
+                        FormatShape(sb)
+                    ),
+                );
+            }
+        }
+        let elem = match (a.elem, b.elem) {
+            (Some(ea), Some(eb)) => match pervasive_elem(prim, ea, eb) {
+                Ok(elem) => elem,
+                Err(()) => {
+                    self.warn(
+                        span,
+                        format!(
+                            "Cannot use {prim} with {} and {}",
+                            ea.error_name(),
+                            eb.error_name()
+                        ),
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+        let shape = (a.shape.zip(b.shape)).map(|(sa, sb)| {
+            let (long, short) = if sa.len() >= sb.len() {
+                (sa, sb)
+            } else {
+                (sb, sa)
+            };
+            let mut shape = long;
+            for (dim, other) in shape.iter_mut().zip(short) {
+                *dim = dim.or(other);
+            }
+            shape
+        });
+        self.stack.push(InferredType::new(elem, shape));
+    }
+    fn check_indices(
+        &mut self,
+        prim: Primitive,
+        span: usize,
+        indices: &InferredType,
+        row_count: Option<usize>,
+    ) {
+        match indices.elem {
+            Some(elem @ (ElemType::Char | ElemType::Box | ElemType::Func)) => {
+                self.warn(
+                    span,
+                    format!(
+                        "Indices for {prim} must be integers, but they are {}s",
+                        elem.error_name()
+                    ),
+                );
+                return;
+            }
+            _ => {}
+        }
+        let Some(indices) = indices.constant.and_then(Value::as_num_array) else {
+            return;
+        };
+        if let Some(&i) = indices.data.iter().find(|i| i.fract() != 0.0) {
+            self.warn(
+                span,
+                format!("Indices for {prim} must be integers, but {i} is not"),
+            );
+        } else if let (Some(len), false) = (row_count, self.filled) {
+            let len = len as f64;
+            if let Some(&i) = indices.data.iter().find(|&&i| i >= len || i < -len) {
+                self.warn(span, format!("Index {i} is out of bounds of length {len}"));
+            }
+        }
+    }
+    fn modifier(&mut self, prim: Primitive, span: usize, count: usize) {
+        let operands: Vec<_> = (0..count).map(|_| self.pop()).collect();
+        let filled = self.filled || prim == Primitive::Fill;
+        // Errors in functions passed to try are expected
+        let operands_to_check = if prim == Primitive::Try {
+            &[][..]
+        } else {
+            &operands[..]
+        };
+        for operand in operands_to_check {
+            if let Some(f) = operand.function() {
+                if let FunctionId::Anonymous(_) = f.id {
+                    self.function(f, filled);
+                }
+            }
+        }
+        // Determine the modifier's signature from its operands
+        if operands.iter().all(|operand| operand.constant.is_some()) {
+            let mut instrs: Vec<Instr> = (operands.iter().rev())
+                .map(|operand| Instr::push(operand.constant.unwrap().clone()))
+                .collect();
+            instrs.push(Instr::Prim(prim, span));
+            if let Ok(sig) = instrs_signature(&instrs) {
+                self.handle_sig(sig);
+                return;
+            }
+        }
+        self.lose_track();
+    }
+    fn call(&mut self) {
+        match self.pop().function() {
+            Some(f) if self.depth < MAX_DEPTH => {
+                self.depth += 1;
+                self.instrs(&f.instrs);
+                self.depth -= 1;
+            }
+            Some(f) => self.handle_sig(f.signature()),
+            None => self.lose_track(),
+        }
+    }
+    /// Check the body of a function that is not called directly
+    fn function(&mut self, f: &'a Function, filled: bool) {
+        if self.depth >= MAX_DEPTH {
+            return;
+        }
+        let mut env = TypeEnv::new(self.spans, filled, self.depth + 1);
+        env.instrs(&f.instrs);
+        env.check_remaining_functions();
+        self.diagnostics.append(&mut env.diagnostics);
+    }
+    /// Check the bodies of functions that were left on the stack
+    fn check_remaining_functions(&mut self) {
+        for val in std::mem::take(&mut self.stack) {
+            if let Some(f) = val.function() {
+                if let FunctionId::Anonymous(_) = f.id {
+                    self.function(f, self.filled);
+                }
+            }
+        }
+    }
+    /// Forget everything about the stack after an instruction with an unknown effect
+    fn lose_track(&mut self) {
+        self.check_remaining_functions();
+        for (bottom, accurate) in &mut self.array_stack {
+            *bottom = 0;
+            *accurate = false;
+        }
+    }
+    fn handle_sig(&mut self, sig: Signature) {
+        for _ in 0..sig.args {
+            self.pop();
+        }
+        for _ in 0..sig.outputs {
+            self.stack.push(InferredType::default());
+        }
+    }
+    fn pop(&mut self) -> InferredType<'a> {
+        let val = self.stack.pop().unwrap_or_default();
+        let height = self.stack.len();
+        for (bottom, accurate) in &mut self.array_stack {
+            if *bottom > height {
+                *bottom = height;
+                *accurate = false;
+            }
+        }
+        val
+    }
+    fn warn(&mut self, span: usize, message: String) {
+        if let Some(Span::Code(span)) = self.spans.get(span) {
+            self.diagnostics.push(Diagnostic::new(
+                message,
+                span.clone(),
+                DiagnosticKind::Warning,
+            ));
+        }
+    }
+}
+
+/// Check if two shapes are compatible
+///
+/// If `prefix` is true, the shorter shape only has to be a prefix of the longer one.
+fn shapes_match(a: &[Option<usize>], b: &[Option<usize>], prefix: bool) -> bool {
+    (prefix || a.len() == b.len())
+        && a.iter().zip(b).all(|(da, db)| match (da, db) {
+            (Some(da), Some(db)) => da == db,
+            _ => true,
+        })
+}
+
+/// Get the type of an array made from some items
+fn array_type<'a>(items: &[InferredType], boxed: bool, accurate: bool) -> InferredType<'a> {
+    let len = accurate.then_some(items.len());
+    if boxed {
+        return InferredType::new(Some(ElemType::Box), Some(vec![len]));
+    }
+    let mut elem = items.first().and_then(|item| item.elem);
+    let mut shape = items.first().and_then(|item| item.shape.clone());
+    for item in items {
+        elem = match (elem, item.elem) {
+            (Some(a), Some(b)) if a == b => Some(a),
+            (Some(a), Some(b)) if a.is_numeric() && b.is_numeric() => Some(ElemType::Num),
+            _ => None,
+        };
+        shape = match (shape, &item.shape) {
+            (Some(a), Some(b)) if a.len() == b.len() => {
+                Some(a.iter().zip(b).map(|(da, db)| da.or(*db)).collect())
+            }
+            _ => None,
+        };
+    }
+    if items.is_empty() {
+        return InferredType::new(None, Some(vec![len]));
+    }
+    let shape = shape.map(|row_shape| {
+        let mut shape = vec![len];
+        shape.extend(row_shape);
+        shape
+    });
+    InferredType::new(elem, shape)
+}
+
+/// Get the element type of the result of a dyadic pervasive function
+///
+/// Returns an error if the function is not defined for the given types.
+fn pervasive_elem(prim: Primitive, a: ElemType, b: ElemType) -> Result<Option<ElemType>, ()> {
+    use ElemType::*;
+    use Primitive::*;
+    if [a, b]
+        .iter()
+        .any(|elem| matches!(elem, ElemType::Box | ElemType::Func))
+    {
+        return Ok(None);
+    }
+    Ok(Some(match prim {
+        Eq | Ne | Lt | Le | Gt | Ge => Byte,
+        Add => match (a, b) {
+            (Char, Char) => return Err(()),
+            (Char, _) | (_, Char) => Char,
+            _ => Num,
+        },
+        Sub => match (a, b) {
+            (Char, Char) => Num,
+            (_, Char) => Char,
+            (Char, _) => return Err(()),
+            _ => Num,
+        },
+        Max | Min => match (a, b) {
+            (Char, Char) => Char,
+            (Byte, Byte) => Byte,
+            (Char, _) | (_, Char) => return Err(()),
+            _ => Num,
+        },
+        _ if a == Char || b == Char => return Err(()),
+        _ => Num,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uiua;
+
+    #[test]
+    fn inferred_diagnostics() {
+        let warnings = |code: &str| {
+            let mut env = Uiua::with_native_sys();
+            _ = env.load_str(code);
+            env.take_diagnostics()
+                .into_iter()
+                .filter(|diag| diag.kind == DiagnosticKind::Warning)
+                .count()
+        };
+        assert_eq!(warnings("+[1 2] [1 2 3]"), 1);
+        assert_eq!(warnings("+1 [1 2 3]"), 0);
+        assert_eq!(warnings("⬚0(+[1 2] [1 2 3])"), 0);
+        assert_eq!(warnings("F ← ×2 ⊟[1 2]\nF [3 4 5]"), 1);
+        assert_eq!(warnings("⊏1.5 [1 2 3]"), 1);
+        assert_eq!(warnings("⊏\"ab\" [1 2 3]"), 1);
+        assert_eq!(warnings("⊏[0 ¯1] [1 2 3]"), 0);
+        assert_eq!(warnings("⊏3 [1 2 3]"), 1);
+        assert_eq!(warnings("+\"a\" \"b\""), 1);
+        assert_eq!(warnings("-1 \"b\""), 0);
+        assert_eq!(warnings("⊙(+[1 2 3]) 4 [5 6]"), 1);
+        assert_eq!(warnings("⍣(+@a @b)∘"), 0);
+    }
+
+    #[test]
+    fn binding_types() {
+        let mut env = Uiua::with_native_sys();
+        env.load_str("X ← [1 2 3]\nF ← ⇡×2\nG ← ⊂").unwrap();
+        let mut types: Vec<_> = env.binding_types.into_values().collect();
+        types.sort();
+        assert_eq!(types, ["num [3]", "|1.1 → num"]);
+    }
+
+    #[test]
+    fn binding_types_without_running() {
+        let path = std::env::temp_dir().join(format!("uiua-infer-{}", std::process::id()));
+        let code = format!(
+            "X ← [1 2 3]\nF ← ⇡×2\nW ← &fwa {:?} \"x\"",
+            path.to_string_lossy()
+        );
+        let mut types: Vec<_> = super::binding_types(&code).into_values().collect();
+        types.sort();
+        assert_eq!(types, ["num [3]", "|1.1 → num"]);
+        assert!(!path.exists());
+    }
+}
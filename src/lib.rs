@@ -10,27 +10,42 @@ The current API should be considered deeply unstable.
 mod algorithm;
 pub mod array;
 pub mod ast;
+mod bytecode;
 mod check;
 mod compile;
+mod config;
+pub mod coverage;
 mod cowslice;
+#[cfg(feature = "dap")]
+pub mod dap;
+pub mod debug;
 mod error;
 pub mod format;
 pub mod function;
 mod grid_fmt;
+#[cfg(feature = "https")]
+mod http;
+mod infer;
 pub mod lex;
+pub mod lint;
 pub mod lsp;
+mod optimize;
 pub mod parse;
 pub mod primitive;
 #[doc(hidden)]
 pub mod profile;
+pub mod profiler;
 pub mod run;
 mod sys;
 mod sys_native;
+mod sys_sandbox;
+mod sys_virtual;
+pub mod testing;
 pub mod value;
 
 use std::sync::Arc;
 
-pub use {error::*, run::Uiua, sys::*, sys_native::*};
+pub use {error::*, run::Uiua, sys::*, sys_native::*, sys_sandbox::*, sys_virtual::*};
 
 pub type Ident = Arc<str>;
 
@@ -0,0 +1,334 @@
+//! Lints that check Uiua code for likely mistakes
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ast::*,
+    config::{load_config_file, search_config_file},
+    lex::{CodeSpan, Sp},
+    primitive::Primitive,
+    Diagnostic, DiagnosticKind, Ident, UiuaResult,
+};
+
+/// Configuration for which lints are enabled
+///
+/// By default, no lints are enabled.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    /// Warn on bindings that are never referenced
+    ///
+    /// Default: `false`
+    pub unused_bindings: bool,
+    /// Warn on bindings that shadow an earlier binding with the same name
+    ///
+    /// Default: `false`
+    pub shadowed_bindings: bool,
+    /// Warn on code that can never run because of a preceding break
+    ///
+    /// Default: `false`
+    pub unreachable_code: bool,
+    /// Warn on functions with a declared signature that leave extra values on the stack
+    ///
+    /// The compiler rejects declared signatures that do not match the inferred one,
+    /// so this only checks functions whose signature cannot be inferred, when they are called.
+    ///
+    /// Default: `false`
+    pub extra_values: bool,
+}
+
+impl LintConfig {
+    pub fn with_unused_bindings(self, unused_bindings: bool) -> Self {
+        Self {
+            unused_bindings,
+            ..self
+        }
+    }
+    pub fn with_shadowed_bindings(self, shadowed_bindings: bool) -> Self {
+        Self {
+            shadowed_bindings,
+            ..self
+        }
+    }
+    pub fn with_unreachable_code(self, unreachable_code: bool) -> Self {
+        Self {
+            unreachable_code,
+            ..self
+        }
+    }
+    pub fn with_extra_values(self, extra_values: bool) -> Self {
+        Self {
+            extra_values,
+            ..self
+        }
+    }
+    /// Load the lint configuration from a file
+    ///
+    /// Options that are not bound in the file keep their default values.
+    pub fn from_file(path: PathBuf) -> UiuaResult<Self> {
+        let (env, mut bindings) = load_config_file(path)?;
+        let mut config = Self::default();
+        for (name, option) in [
+            ("UnusedBindings", &mut config.unused_bindings),
+            ("ShadowedBindings", &mut config.shadowed_bindings),
+            ("UnreachableCode", &mut config.unreachable_code),
+            ("ExtraValues", &mut config.extra_values),
+        ] {
+            if let Some(value) = bindings.remove(name) {
+                *option = value.as_bool(&env, "Lint config options expect a boolean")?;
+            }
+        }
+        Ok(config)
+    }
+    /// Search for a .lint.ua file starting at the given path and use it as the lint configuration
+    ///
+    /// If none is found, the default configuration, which enables no lints, is used.
+    pub fn find(path: Option<&Path>) -> UiuaResult<Self> {
+        if let Some(file_path) = search_config_file(".lint.ua", path) {
+            Self::from_file(file_path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+}
+
+/// Run the enabled lints on some parsed items
+pub fn lint(items: &[Item], config: &LintConfig) -> Vec<Diagnostic> {
+    lint_impl(items, config, false)
+}
+
+/// Run the enabled lints on the items of an imported file
+///
+/// The file's top-level bindings are for the files that import it,
+/// so they are not reported as unused.
+pub(crate) fn lint_import(items: &[Item], config: &LintConfig) -> Vec<Diagnostic> {
+    lint_impl(items, config, true)
+}
+
+fn lint_impl(items: &[Item], config: &LintConfig, exported: bool) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        config,
+        scopes: vec![Vec::new()],
+        diagnostics: Vec::new(),
+    };
+    linter.items(items);
+    if exported {
+        linter.scopes.pop();
+    } else {
+        linter.end_scope();
+    }
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    /// The bindings in each scope, and whether they have been referenced
+    scopes: Vec<Vec<(Sp<Ident>, bool)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Scoped { items, .. } => {
+                    self.scopes.push(Vec::new());
+                    self.items(items);
+                    self.end_scope();
+                }
+                Item::Words(words) => self.line(words),
+                Item::Binding(binding) => self.binding(binding),
+                Item::ExtraNewlines(_) => {}
+            }
+        }
+    }
+    fn binding(&mut self, binding: &Binding) {
+        // The binding's own words refer to earlier bindings
+        self.line(&binding.words);
+        if self.config.shadowed_bindings {
+            let shadowed =
+                (self.scopes.iter().flatten()).any(|(name, _)| name.value == binding.name.value);
+            if shadowed {
+                self.warn(
+                    format!(
+                        "`{}` shadows an earlier binding with the same name",
+                        binding.name.value
+                    ),
+                    binding.name.span.clone(),
+                );
+            }
+        }
+        let scope = self.scopes.last_mut().unwrap();
+        scope.push((binding.name.clone(), false));
+    }
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        if !self.config.unused_bindings {
+            return;
+        }
+        for (name, used) in scope {
+            if !used {
+                self.warn(format!("`{}` is never used", name.value), name.span);
+            }
+        }
+    }
+    fn line(&mut self, words: &[Sp<Word>]) {
+        self.lines(&[words]);
+    }
+    /// Check lines that run one after another
+    fn lines(&mut self, lines: &[&[Sp<Word>]]) {
+        for word in lines.iter().copied().flatten() {
+            self.word(word);
+        }
+        if !self.config.unreachable_code {
+            return;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            // Words run from right to left, so everything left of an unconditional break is skipped
+            let Some(break_index) = unconditional_break(line) else {
+                continue;
+            };
+            let unreachable =
+                (line[..break_index].iter()).chain(lines[i + 1..].iter().copied().flatten());
+            if let Some(span) = words_span(unreachable) {
+                self.warn("Unreachable code after break", span);
+            }
+            break;
+        }
+    }
+    fn word(&mut self, word: &Sp<Word>) {
+        match &word.value {
+            Word::Ident(ident) => {
+                let binding = (self.scopes.iter_mut().rev()).find_map(|scope| {
+                    scope
+                        .iter_mut()
+                        .rev()
+                        .find(|(name, _)| name.value == *ident)
+                });
+                if let Some((_, used)) = binding {
+                    *used = true;
+                }
+            }
+            Word::Strand(items) => {
+                for item in items {
+                    self.word(item);
+                }
+            }
+            Word::Array(arr) => {
+                for line in &arr.lines {
+                    self.line(line);
+                }
+            }
+            Word::Func(func) => {
+                let lines: Vec<_> = func.lines.iter().map(Vec::as_slice).collect();
+                self.lines(&lines);
+            }
+            Word::Modified(modified) => {
+                for operand in &modified.operands {
+                    self.word(operand);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn warn(&mut self, message: impl Into<String>, span: CodeSpan) {
+        self.diagnostics
+            .push(Diagnostic::new(message, span, DiagnosticKind::Warning));
+    }
+}
+
+/// Find the index of a break that always breaks out of a loop
+fn unconditional_break(words: &[Sp<Word>]) -> Option<usize> {
+    let code_words: Vec<_> = (words.iter().enumerate())
+        .filter(|(_, word)| !matches!(word.value, Word::Spaces | Word::Comment(_)))
+        .collect();
+    code_words.windows(2).find_map(|pair| match pair {
+        [(
+            i,
+            Sp {
+                value: Word::Primitive(Primitive::Break),
+                ..
+            },
+        ), (
+            _,
+            Sp {
+                value: Word::Number(_, n),
+                ..
+            },
+        )] if *n >= 1.0 => Some(*i),
+        _ => None,
+    })
+}
+
+/// Get the span covering some words, ignoring spaces and comments
+fn words_span<'w>(words: impl Iterator<Item = &'w Sp<Word>>) -> Option<CodeSpan> {
+    let mut words = words.filter(|word| !matches!(word.value, Word::Spaces | Word::Comment(_)));
+    let first = words.next()?.span.clone();
+    Some(match words.last() {
+        Some(last) => first.merge(last.span.clone()),
+        None => first,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::parse, Uiua};
+
+    #[test]
+    fn lints() {
+        let messages = |code: &str, config: LintConfig| {
+            let (items, errors, _) = parse(code, None);
+            assert!(errors.is_empty());
+            lint(&items, &config)
+                .into_iter()
+                .map(|diag| diag.message)
+                .collect::<Vec<_>>()
+        };
+        let all = LintConfig::default()
+            .with_unused_bindings(true)
+            .with_shadowed_bindings(true)
+            .with_unreachable_code(true);
+        let code = "\
+            X ← 5\n\
+            Y ← 6\n\
+            X ← +1 X\n\
+            F ← |1.1 +1\n\
+            ⍥(+1 ⎋1 +2)3 Y";
+        assert_eq!(
+            messages(code, all.clone()),
+            [
+                "`X` shadows an earlier binding with the same name",
+                "Unreachable code after break",
+                "`X` is never used",
+                "`F` is never used",
+            ]
+        );
+        assert!(messages(code, LintConfig::default()).is_empty());
+        let config = all.clone().with_shadowed_bindings(false);
+        assert_eq!(messages(code, config).len(), 3);
+        let (items, _, _) = parse(code, None);
+        assert_eq!(lint_import(&items, &all).len(), 2);
+    }
+
+    #[test]
+    fn extra_values() {
+        let mut env =
+            Uiua::with_native_sys().with_lints(LintConfig::default().with_extra_values(true));
+        env.load_str(
+            "\
+            F ← |1.1 ↬0 .\n\
+            G ← |1.1 ↬0 +1\n\
+            F 1\n\
+            G 2\n\
+            F 3",
+        )
+        .unwrap();
+        let messages: Vec<_> = (env.take_diagnostics().into_iter())
+            .map(|diag| diag.message)
+            .collect();
+        assert_eq!(
+            messages,
+            ["`F` is declared as |1.1 but leaves 1 extra value on the stack"]
+        );
+    }
+}
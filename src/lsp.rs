@@ -111,6 +111,7 @@ mod server {
 
     use crate::{
         format::{format_str, FormatConfig},
+        infer::binding_types,
         lex::Loc,
         primitive::{PrimClass, PrimDocFragment},
         Ident, Uiua,
@@ -371,6 +372,9 @@ mod server {
                 }
             } else if let Some((ident, binding, range)) = binding_range {
                 let mut value: String = ident.value.as_ref().into();
+                if let Some(ty) = binding_types(&doc.input).get(&binding.span) {
+                    value.push_str(&format!(" `{ty}`"));
+                }
                 if let Some(comment) = &binding.comment {
                     value.push('\n');
                     value.push_str(comment);
@@ -18,9 +18,12 @@ use notify::{EventKind, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use uiua::{
+    debug::{Breakpoint, TerminalDebugger},
     format::{format_file, FormatConfig, FormatConfigSource},
+    lint::LintConfig,
     run::RunMode,
-    Uiua, UiuaError, UiuaResult,
+    testing::junit_xml,
+    Diagnostic, NativeSys, Permissions, SandboxSys, Uiua, UiuaError, UiuaResult,
 };
 
 fn main() {
@@ -67,17 +70,20 @@ fn run() -> UiuaResult {
             App::Fmt {
                 path,
                 formatter_options,
+                message_options: MessageOptions { message_format },
             } => {
-                let config = FormatConfig::from_source(
-                    formatter_options.format_config_source,
-                    path.as_deref(),
-                )?;
-
-                if let Some(path) = path {
-                    format_single_file(path, &config, formatter_options.stdout)?;
-                } else {
-                    format_multi_files(&config, formatter_options.stdout)?;
-                }
+                let res = (|| {
+                    let config = FormatConfig::from_source(
+                        formatter_options.format_config_source,
+                        path.as_deref(),
+                    )?;
+                    if let Some(path) = path {
+                        format_single_file(path, &config, formatter_options.stdout)
+                    } else {
+                        format_multi_files(&config, formatter_options.stdout)
+                    }
+                })();
+                report_messages(message_format, res, [])?;
             }
             App::Run {
                 path,
@@ -85,7 +91,14 @@ fn run() -> UiuaResult {
                 formatter_options,
                 no_update,
                 time_instrs,
+                no_parallel,
+                no_optimize,
+                thread_pool,
+                profile,
+                profile_interval,
+                sandbox_options,
                 mode,
+                message_options: MessageOptions { message_format },
                 #[cfg(feature = "audio")]
                 audio_options,
                 args,
@@ -104,47 +117,119 @@ fn run() -> UiuaResult {
                         }
                     }
                 };
-                if !no_format {
-                    let config = FormatConfig::from_source(
-                        formatter_options.format_config_source,
-                        Some(&path),
-                    )?;
-                    format_file(&path, &config)?;
-                }
                 let mode = mode.unwrap_or(RunMode::Normal);
                 #[cfg(feature = "audio")]
                 setup_audio(audio_options);
-                let mut rt = Uiua::with_native_sys()
+                if thread_pool.is_some() {
+                    NativeSys::set_thread_pool_size(thread_pool);
+                }
+                let lint_config = LintConfig::find(Some(&path))?;
+                let mut rt = sandbox_options
+                    .runtime()
                     .with_mode(mode)
                     .with_file_path(&path)
                     .with_args(args)
-                    .print_diagnostics(true)
-                    .time_instrs(time_instrs);
-                rt.load_file(path)?;
-                for value in rt.take_stack() {
-                    println!("{}", value.show());
+                    .with_lints(lint_config)
+                    .print_diagnostics(message_format == MessageFormat::Human)
+                    .time_instrs(time_instrs)
+                    .parallel(!no_parallel)
+                    .optimize(!no_optimize);
+                if profile.is_some() {
+                    rt = rt.with_profiler(Duration::from_micros(profile_interval));
+                }
+                let res = (|| {
+                    if path.extension().is_some_and(|ext| ext == "uac") {
+                        let bytes =
+                            fs::read(&path).map_err(|e| UiuaError::Load(path.clone(), e.into()))?;
+                        return rt.load_bytecode(&bytes, &path);
+                    }
+                    if !no_format {
+                        let config = FormatConfig::from_source(
+                            formatter_options.format_config_source,
+                            Some(&path),
+                        )?;
+                        format_file(&path, &config)?;
+                    }
+                    rt.load_file(&path)
+                })();
+                if res.is_ok() {
+                    for value in rt.take_stack() {
+                        println!("{}", value.show());
+                    }
+                }
+                if let Some((path, profile)) = profile.zip(rt.take_profile()) {
+                    if let Err(e) = fs::write(&path, profile.folded()) {
+                        eprintln!("Failed to write {}: {e}", path.display());
+                    }
+                    eprintln!("{}", profile.summary(10));
+                }
+                report_messages(message_format, res, rt.take_diagnostics())?;
+            }
+            App::Build {
+                path,
+                output,
+                no_optimize,
+                message_options: MessageOptions { message_format },
+            } => {
+                let path = if let Some(path) = path {
+                    path
+                } else {
+                    match working_file_path() {
+                        Ok(path) => path,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(());
+                        }
+                    }
+                };
+                let lint_config = LintConfig::find(Some(&path))?;
+                let mut rt = Uiua::with_native_sys()
+                    .with_file_path(&path)
+                    .with_lints(lint_config)
+                    .print_diagnostics(message_format == MessageFormat::Human)
+                    .optimize(!no_optimize);
+                let res = rt.build_file(&path);
+                if let Ok(bytes) = &res {
+                    let output = output.unwrap_or_else(|| path.with_extension("uac"));
+                    if let Err(e) = fs::write(&output, bytes) {
+                        eprintln!("Failed to write {}: {e}", output.display());
+                        exit(1);
+                    }
                 }
+                report_messages(message_format, res.map(drop), rt.take_diagnostics())?;
             }
             App::Eval {
                 code,
+                sandbox_options,
+                message_options: MessageOptions { message_format },
                 #[cfg(feature = "audio")]
                 audio_options,
                 args,
             } => {
                 #[cfg(feature = "audio")]
                 setup_audio(audio_options);
-                let mut rt = Uiua::with_native_sys()
+                let mut rt = sandbox_options
+                    .runtime()
                     .with_mode(RunMode::Normal)
                     .with_args(args)
-                    .print_diagnostics(true);
-                rt.load_str(&code)?;
-                for value in rt.take_stack() {
-                    println!("{}", value.show());
+                    .print_diagnostics(message_format == MessageFormat::Human);
+                let res = rt.load_str(&code);
+                if res.is_ok() {
+                    for value in rt.take_stack() {
+                        println!("{}", value.show());
+                    }
                 }
+                report_messages(message_format, res, rt.take_diagnostics())?;
             }
             App::Test {
                 path,
                 formatter_options,
+                message_options: MessageOptions { message_format },
+                filter,
+                junit,
+                bless,
+                coverage,
+                sandbox_options,
             } => {
                 let path = if let Some(path) = path {
                     path
@@ -157,14 +242,86 @@ fn run() -> UiuaResult {
                         }
                     }
                 };
-                let config =
-                    FormatConfig::from_source(formatter_options.format_config_source, Some(&path))?;
-                format_file(&path, &config)?;
-                Uiua::with_native_sys()
+                let lint_config = LintConfig::find(Some(&path))?;
+                let mut rt = sandbox_options
+                    .runtime()
                     .with_mode(RunMode::Test)
-                    .print_diagnostics(true)
-                    .load_file(path)?;
-                println!("No failures!");
+                    .with_file_path(&path)
+                    .with_lints(lint_config)
+                    .print_diagnostics(message_format == MessageFormat::Human)
+                    .collect_tests(true)
+                    .bless_snapshots(bless)
+                    .collect_coverage(coverage.is_some());
+                if let Some(filter) = filter {
+                    rt = rt.with_test_filter(filter);
+                }
+                let res = (|| {
+                    let config = FormatConfig::from_source(
+                        formatter_options.format_config_source,
+                        Some(&path),
+                    )?;
+                    format_file(&path, &config)?;
+                    rt.load_file(&path)
+                })();
+                let results = rt.take_test_results();
+                if let Some(junit) = junit {
+                    let suite = path.to_string_lossy();
+                    if let Err(e) = fs::write(&junit, junit_xml(&suite, &results)) {
+                        eprintln!("Failed to write {}: {e}", junit.display());
+                    }
+                }
+                let coverage = coverage.map(|lcov_path| {
+                    let files = rt.coverage();
+                    if let Err(e) = fs::write(&lcov_path, uiua::coverage::lcov(&files)) {
+                        eprintln!("Failed to write {}: {e}", lcov_path.display());
+                    }
+                    files
+                });
+                let failed: Vec<_> = results.iter().filter(|r| !r.passed()).collect();
+                match message_format {
+                    MessageFormat::Human => {
+                        for result in &results {
+                            if result.passed() {
+                                println!("{} {}", "pass".green(), result.name);
+                            } else {
+                                println!("{} {}", "FAIL".red().bold(), result.name);
+                            }
+                        }
+                        for result in &failed {
+                            if let Some(error) = &result.error {
+                                println!("\n{}\n{}", result.name.bold(), error.show(true));
+                            }
+                        }
+                        res?;
+                        println!();
+                        if failed.is_empty() {
+                            println!("{} tests passed. No failures!", results.len());
+                        } else {
+                            println!(
+                                "{} passed, {} failed",
+                                results.len() - failed.len(),
+                                failed.len()
+                            );
+                        }
+                        if let Some(files) = &coverage {
+                            let base = env::current_dir().unwrap_or_default();
+                            println!("\nCoverage:\n{}", uiua::coverage::summary(files, &base));
+                        }
+                    }
+                    MessageFormat::Json => {
+                        for result in &failed {
+                            if let Some(error) = &result.error {
+                                for record in error.records() {
+                                    println!("{}", record.to_json());
+                                }
+                            }
+                        }
+                        report_messages(message_format, res, rt.take_diagnostics())?;
+                    }
+                }
+                if !failed.is_empty() {
+                    exit(1);
+                }
             }
             App::Watch {
                 no_format,
@@ -188,8 +345,51 @@ fn run() -> UiuaResult {
                     eprintln!("Error watching file: {e}");
                 }
             }
+            App::Debug {
+                path,
+                breakpoints,
+                args,
+            } => {
+                let path = if let Some(path) = path {
+                    path
+                } else {
+                    match working_file_path() {
+                        Ok(path) => path,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(());
+                        }
+                    }
+                };
+                let mut rt = Uiua::with_native_sys()
+                    .with_file_path(&path)
+                    .with_args(args)
+                    .print_diagnostics(true)
+                    .with_debugger(TerminalDebugger::new(breakpoints));
+                println!("Debugging {} (type `help` for commands)", path.display());
+                rt.load_file(path)?;
+                for value in rt.take_stack() {
+                    println!("{}", value.show());
+                }
+            }
+            App::Repl {
+                path,
+                #[cfg(feature = "audio")]
+                audio_options,
+                args,
+            } => {
+                #[cfg(feature = "audio")]
+                setup_audio(audio_options);
+                repl(path, args);
+            }
             #[cfg(feature = "lsp")]
             App::Lsp => uiua::lsp::run_server(),
+            #[cfg(feature = "dap")]
+            App::Dap { port } => {
+                if let Err(e) = uiua::dap::run_server(port) {
+                    eprintln!("Error running debug adapter: {e}");
+                }
+            }
         },
         Err(e) if e.kind() == ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => {
             show_update_message();
@@ -412,6 +612,150 @@ fn watch(
     }
 }
 
+const REPL_HELP: &str = "\
+Commands:
+  :help           Show this message
+  :clear          Clear the stack
+  :bindings       List all bindings in scope
+  :load <path>    Load a file
+  :reload         Reload the last loaded file
+  :quit           Exit the REPL";
+
+fn repl(path: Option<PathBuf>, args: Vec<String>) {
+    let mut rt = Uiua::with_native_sys()
+        .with_mode(RunMode::Normal)
+        .with_args(args)
+        .print_diagnostics(true);
+    let mut last_path = None;
+    let load = |rt: &mut Uiua, path: &Path| {
+        if let Err(e) = rt.load_file(path) {
+            println!("{}", e.show(true));
+        }
+    };
+    if let Some(path) = path {
+        load(&mut rt, &path);
+        last_path = Some(path);
+    }
+    println!(
+        "Uiua {} REPL (type :help for commands, end with ctrl+D)",
+        env!("CARGO_PKG_VERSION")
+    );
+    print_repl_stack(&rt);
+    let stdin = io::stdin();
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "» " } else { "… " });
+        _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        if input.is_empty() {
+            if let Some(command) = line.trim().strip_prefix(':') {
+                let (command, arg) = command
+                    .split_once(char::is_whitespace)
+                    .map_or((command, ""), |(c, a)| (c, a.trim()));
+                match command {
+                    "help" | "h" | "?" => println!("{REPL_HELP}"),
+                    "clear" | "c" => {
+                        rt.take_stack();
+                    }
+                    "bindings" | "b" => {
+                        let mut bindings: Vec<_> = rt.all_bindings_in_scope().into_iter().collect();
+                        bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+                        if bindings.is_empty() {
+                            println!("No bindings");
+                        }
+                        for (name, value) in bindings {
+                            let shown = value.show();
+                            if shown.contains('\n') {
+                                println!("{name} ←\n{shown}");
+                            } else {
+                                println!("{name} ← {shown}");
+                            }
+                        }
+                        continue;
+                    }
+                    "load" | "l" if !arg.is_empty() => {
+                        let path = PathBuf::from(arg);
+                        load(&mut rt, &path);
+                        last_path = Some(path);
+                    }
+                    "reload" | "r" => {
+                        if let Some(path) = &last_path {
+                            load(&mut rt, path);
+                        } else {
+                            println!("No file has been loaded");
+                            continue;
+                        }
+                    }
+                    "quit" | "q" | "exit" => break,
+                    _ => {
+                        println!("Unknown command `:{command}`\n{REPL_HELP}");
+                        continue;
+                    }
+                }
+                print_repl_stack(&rt);
+                continue;
+            }
+        }
+        if !input.is_empty() {
+            input.push('\n');
+        }
+        input.push_str(line);
+        if unclosed_delimiters(&input) > 0 {
+            continue;
+        }
+        if let Err(e) = rt.load_str(&input) {
+            println!("{}", e.show(true));
+        }
+        input.clear();
+        print_repl_stack(&rt);
+    }
+    println!();
+}
+
+fn print_repl_stack(rt: &Uiua) {
+    for value in rt.stack() {
+        println!("{}", value.show());
+    }
+}
+
+/// Count the brackets that have been opened but not closed,
+/// ignoring those in strings, characters, and comments
+fn unclosed_delimiters(input: &str) -> usize {
+    let mut depth = 0usize;
+    for line in input.lines() {
+        let mut chars = line.chars();
+        let mut in_string = false;
+        if line.trim_start().starts_with('$') {
+            continue;
+        }
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if in_string => {
+                    chars.next();
+                }
+                '"' => in_string = !in_string,
+                _ if in_string => {}
+                '@' => {
+                    let escaped = chars.next() == Some('\\');
+                    if escaped {
+                        chars.next();
+                    }
+                }
+                '#' => break,
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    depth
+}
+
 #[derive(Parser)]
 #[clap(version)]
 enum App {
@@ -428,17 +772,59 @@ enum App {
         no_update: bool,
         #[clap(long, help = "Emit the duration of each instruction's execution")]
         time_instrs: bool,
+        #[clap(long, help = "Don't split rows, each, or table across threads")]
+        no_parallel: bool,
+        #[clap(long, help = "Don't optimize compiled code")]
+        no_optimize: bool,
+        #[clap(
+            long,
+            value_name = "SIZE",
+            help = "Run spawned functions on a pool of this many worker threads"
+        )]
+        thread_pool: Option<usize>,
+        #[clap(
+            long,
+            value_name = "PATH",
+            num_args = 0..=1,
+            default_missing_value = "profile.folded",
+            help = "Profile the program and write folded stacks for flamegraph tools to this path"
+        )]
+        profile: Option<PathBuf>,
+        #[clap(
+            long,
+            default_value_t = 100,
+            help = "The minimum time between profiler samples in microseconds"
+        )]
+        profile_interval: u64,
+        #[clap(flatten)]
+        sandbox_options: SandboxOptions,
         #[clap(long, help = "Run the file in a specific mode")]
         mode: Option<RunMode>,
+        #[clap(flatten)]
+        message_options: MessageOptions,
         #[cfg(feature = "audio")]
         #[clap(flatten)]
         audio_options: AudioOptions,
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
+    #[clap(about = "Compile a file to a .uac file that can be run without recompiling")]
+    Build {
+        path: Option<PathBuf>,
+        #[clap(long, short, help = "The path to write the compiled file to")]
+        output: Option<PathBuf>,
+        #[clap(long, help = "Don't optimize compiled code")]
+        no_optimize: bool,
+        #[clap(flatten)]
+        message_options: MessageOptions,
+    },
     #[clap(about = "Evaluate an expression and print its output")]
     Eval {
         code: String,
+        #[clap(flatten)]
+        sandbox_options: SandboxOptions,
+        #[clap(flatten)]
+        message_options: MessageOptions,
         #[cfg(feature = "audio")]
         #[clap(flatten)]
         audio_options: AudioOptions,
@@ -450,6 +836,24 @@ enum App {
         path: Option<PathBuf>,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
+        #[clap(long, help = "Only run tests whose names contain this string")]
+        filter: Option<String>,
+        #[clap(long, help = "Write a JUnit XML report to this path")]
+        junit: Option<PathBuf>,
+        #[clap(long, help = "Overwrite snapshots that do not match")]
+        bless: bool,
+        #[clap(
+            long,
+            value_name = "PATH",
+            num_args = 0..=1,
+            default_missing_value = "lcov.info",
+            help = "Record code coverage and write it as LCOV to this path"
+        )]
+        coverage: Option<PathBuf>,
+        #[clap(flatten)]
+        sandbox_options: SandboxOptions,
+        #[clap(flatten)]
+        message_options: MessageOptions,
     },
     #[clap(about = "Run .ua files in the current directory when they change")]
     Watch {
@@ -466,15 +870,88 @@ enum App {
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
+    #[clap(about = "Run a file in the step debugger")]
+    Debug {
+        path: Option<PathBuf>,
+        #[clap(
+            long = "break",
+            short = 'b',
+            help = "Set a breakpoint on a line, file:line, or binding name"
+        )]
+        breakpoints: Vec<Breakpoint>,
+        #[clap(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+    #[clap(about = "Start an interactive read-eval-print loop")]
+    Repl {
+        #[clap(help = "A file to load before starting")]
+        path: Option<PathBuf>,
+        #[cfg(feature = "audio")]
+        #[clap(flatten)]
+        audio_options: AudioOptions,
+        #[clap(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
     #[clap(about = "Format a uiua file or all files in the current directory")]
     Fmt {
         path: Option<PathBuf>,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
+        #[clap(flatten)]
+        message_options: MessageOptions,
     },
     #[cfg(feature = "lsp")]
     #[clap(about = "Run the Language Server")]
     Lsp,
+    #[cfg(feature = "dap")]
+    #[clap(about = "Run the Debug Adapter Protocol server")]
+    Dap {
+        #[clap(long, default_value_t = 4711, help = "The port to listen on")]
+        port: u16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
+/// Print errors and diagnostics in the requested format
+///
+/// In human mode, diagnostics are printed as they are encountered,
+/// so only the error is passed on.
+fn report_messages(
+    format: MessageFormat,
+    res: UiuaResult,
+    diagnostics: impl IntoIterator<Item = Diagnostic>,
+) -> UiuaResult {
+    match format {
+        MessageFormat::Human => res,
+        MessageFormat::Json => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic.record().to_json());
+            }
+            if let Err(e) = res {
+                for record in e.records() {
+                    println!("{}", record.to_json());
+                }
+                exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct MessageOptions {
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = MessageFormat::Human,
+        help = "How to print errors and diagnostics"
+    )]
+    message_format: MessageFormat,
 }
 
 #[derive(clap::Args)]
@@ -494,6 +971,92 @@ struct FormatterOptions {
     stdout: bool,
 }
 
+#[derive(clap::Args)]
+struct SandboxOptions {
+    #[clap(
+        long,
+        help = "Deny file, network, subprocess, and environment access unless allowed by an --allow flag"
+    )]
+    sandbox: bool,
+    #[clap(
+        long,
+        value_name = "PATHS",
+        num_args = 0..,
+        value_delimiter = ',',
+        require_equals = true,
+        help = "Allow reading files under these comma-separated paths, or anywhere if none are given"
+    )]
+    allow_read: Option<Vec<PathBuf>>,
+    #[clap(
+        long,
+        value_name = "PATHS",
+        num_args = 0..,
+        value_delimiter = ',',
+        require_equals = true,
+        help = "Allow writing files under these comma-separated paths, or anywhere if none are given"
+    )]
+    allow_write: Option<Vec<PathBuf>>,
+    #[clap(long, help = "Allow network access")]
+    allow_net: bool,
+    #[clap(long, help = "Allow running subprocesses")]
+    allow_run: bool,
+    #[clap(
+        long,
+        value_name = "NAMES",
+        num_args = 0..,
+        value_delimiter = ',',
+        require_equals = true,
+        help = "Allow reading these comma-separated environment variables, or any if none are given"
+    )]
+    allow_env: Option<Vec<String>>,
+}
+
+impl SandboxOptions {
+    /// Create a runtime, sandboxed if any sandbox flag was given
+    fn runtime(self) -> Uiua {
+        let sandboxed = self.sandbox
+            || self.allow_read.is_some()
+            || self.allow_write.is_some()
+            || self.allow_net
+            || self.allow_run
+            || self.allow_env.is_some();
+        if !sandboxed {
+            return Uiua::with_native_sys();
+        }
+        let mut permissions = Permissions::none()
+            .allow_net(self.allow_net)
+            .allow_run(self.allow_run);
+        match self.allow_read {
+            Some(paths) if paths.is_empty() => permissions = permissions.allow_read_all(),
+            Some(paths) => {
+                for path in paths {
+                    permissions = permissions.allow_read(path);
+                }
+            }
+            None => {}
+        }
+        match self.allow_write {
+            Some(paths) if paths.is_empty() => permissions = permissions.allow_write_all(),
+            Some(paths) => {
+                for path in paths {
+                    permissions = permissions.allow_write(path);
+                }
+            }
+            None => {}
+        }
+        match self.allow_env {
+            Some(names) if names.is_empty() => permissions = permissions.allow_env_all(),
+            Some(names) => {
+                for name in names {
+                    permissions = permissions.allow_env(name);
+                }
+            }
+            None => {}
+        }
+        Uiua::with_backend(SandboxSys::new(NativeSys, permissions))
+    }
+}
+
 #[cfg(feature = "audio")]
 #[derive(clap::Args)]
 struct AudioOptions {
@@ -602,3 +1165,40 @@ fn format_multi_files(config: &FormatConfig, stdout: bool) -> Result<(), UiuaErr
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uiua::value::Value;
+
+    #[test]
+    fn repl_delimiters() {
+        for (input, unclosed) in [
+            ("+1 2", 0),
+            ("[1 2", 1),
+            ("{(⊂[1", 3),
+            ("(+1\n[2]", 1),
+            ("(+1\n)", 0),
+            (")]", 0),
+            ("\"([{\"", 0),
+            ("\"a\\\"(\"", 0),
+            ("@( @[", 0),
+            ("@\\( (", 1),
+            ("+1 # (", 0),
+            ("$ (\n$ [", 0),
+        ] {
+            assert_eq!(unclosed_delimiters(input), unclosed, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn repl_stack_persists() {
+        let mut rt = Uiua::with_native_sys();
+        rt.load_str("1 2").unwrap();
+        rt.load_str("X ← 5").unwrap();
+        rt.load_str("+X").unwrap();
+        assert_eq!(rt.stack(), [Value::from(2.0), Value::from(6.0)]);
+        assert!(rt.load_str("⍤\"no\" 0").is_err());
+        assert_eq!(rt.all_bindings_in_scope()["X"], Value::from(5.0));
+    }
+}
@@ -0,0 +1,305 @@
+//! A peephole optimizer for compiled instructions
+
+use crate::{
+    function::Instr,
+    primitive::{PrimClass, Primitive},
+    value::Value,
+    Uiua,
+};
+
+impl Uiua {
+    /// Optimize a sequence of compiled instructions
+    ///
+    /// Instructions are pushed one at a time, and the end of the optimized
+    /// sequence is rewritten whenever it forms a known pattern.
+    pub(crate) fn optimize_instrs(&self, instrs: Vec<Instr>) -> Vec<Instr> {
+        if !self.optimize_enabled() {
+            return instrs;
+        }
+        let mut optimizer = Optimizer {
+            env: self,
+            const_env: None,
+            instrs: Vec::with_capacity(instrs.len()),
+        };
+        for instr in instrs {
+            optimizer.push(instr);
+        }
+        optimizer.instrs
+    }
+}
+
+struct Optimizer<'a> {
+    env: &'a Uiua,
+    /// The runtime used to evaluate constant expressions, created when first needed
+    const_env: Option<Uiua>,
+    instrs: Vec<Instr>,
+}
+
+impl<'a> Optimizer<'a> {
+    fn push(&mut self, instr: Instr) {
+        use Primitive::*;
+        match (self.instrs.as_mut_slice(), instr) {
+            // Constants
+            (_, Instr::Prim(prim, _)) if prim.as_constant().is_some() => {
+                let n = prim.as_constant().unwrap();
+                self.instrs.push(Instr::push(n));
+            }
+            // Temp round trips
+            (
+                [.., Instr::PushTempInline { count: a, .. }],
+                Instr::PopTempInline { count: b, .. },
+            )
+            | (
+                [.., Instr::PopTempInline { count: a, .. }],
+                Instr::PushTempInline { count: b, .. },
+            ) if *a == b => {
+                self.instrs.pop();
+            }
+            // Duplicate a constant
+            ([.., Instr::Push(val)], Instr::Prim(Dup, _)) => {
+                let val = val.clone();
+                self.instrs.push(Instr::Push(val));
+            }
+            // Flip two constants
+            ([.., a @ Instr::Push(_), b @ Instr::Push(_)], Instr::Prim(Flip, _)) => {
+                std::mem::swap(a, b);
+            }
+            // Rows or reduce of windows
+            (
+                [.., Instr::Prim(Windows, a), Instr::Push(f)],
+                Instr::Prim(prim @ (Rows | Reduce), b),
+            ) if windows_modifier(prim, f).is_some() => {
+                let fused = windows_modifier(prim, f).unwrap();
+                let a = *a;
+                let f = self.instrs.pop().unwrap();
+                self.instrs.pop();
+                let span = self.merge_spans(a, b);
+                self.instrs.push(f);
+                self.push(Instr::Prim(fused, span));
+            }
+            // Reduce add = sum
+            ([.., Instr::Push(f)], Instr::Prim(Reduce, span))
+                if matches!(prim_function(f), Some((Add, _))) =>
+            {
+                let (_, add_span) = prim_function(f).unwrap();
+                self.fuse(Sum, add_span, span);
+            }
+            // First rise = index of the first minimum
+            ([.., Instr::Prim(Rise, a)], Instr::Prim(First, b)) => {
+                let a = *a;
+                self.fuse(FirstMinIndex, a, b);
+            }
+            // Reverse rise
+            ([.., Instr::Prim(Rise, a)], Instr::Prim(Reverse, b)) => {
+                let a = *a;
+                self.fuse(ReverseRise, a, b);
+            }
+            // Keep not equals
+            ([.., Instr::Prim(Ne, a)], Instr::Prim(Keep, b)) => {
+                let a = *a;
+                self.fuse(KeepNe, a, b);
+            }
+            // Constant folding
+            (_, Instr::Prim(prim, span)) if can_fold(prim) => {
+                if !self.fold(prim, span) {
+                    self.instrs.push(Instr::Prim(prim, span));
+                }
+            }
+            (_, instr) => self.instrs.push(instr),
+        }
+    }
+    /// Replace the last instruction and the next one with a fused primitive
+    fn fuse(&mut self, prim: Primitive, first_span: usize, last_span: usize) {
+        self.instrs.pop();
+        let span = self.merge_spans(first_span, last_span);
+        self.push(Instr::Prim(prim, span));
+    }
+    /// Add a span that covers two other spans
+    fn merge_spans(&self, first: usize, last: usize) -> usize {
+        let mut spans = self.env.spans.lock();
+        let span = spans[first].clone().merge(spans[last].clone());
+        spans.push(span);
+        spans.len() - 1
+    }
+    /// Try to evaluate a primitive whose arguments are all constants
+    ///
+    /// If evaluation fails, nothing is changed so that the error is
+    /// reported when the code is run.
+    fn fold(&mut self, prim: Primitive, span: usize) -> bool {
+        let Some(args) = prim.args().map(usize::from) else {
+            return false;
+        };
+        let start = match self.instrs.len().checked_sub(args) {
+            Some(start) if args > 0 => start,
+            _ => return false,
+        };
+        if !(self.instrs[start..].iter()).all(|instr| matches!(instr, Instr::Push(_))) {
+            return false;
+        }
+        let mut instrs = self.instrs[start..].to_vec();
+        instrs.push(Instr::Prim(prim, span));
+        let env = (self.const_env).get_or_insert_with(|| self.env.const_env());
+        let res = env.exec_global_instrs(instrs);
+        let mut stack = env.take_stack();
+        match res {
+            Ok(()) if stack.len() == 1 => {
+                self.instrs.truncate(start);
+                self.instrs.push(Instr::push(stack.pop().unwrap()));
+                true
+            }
+            _ => {
+                self.const_env = None;
+                false
+            }
+        }
+    }
+}
+
+/// Get the primitive and span of a pushed function value that only calls a primitive
+fn prim_function(val: &Value) -> Option<(Primitive, usize)> {
+    val.as_function()?.as_primitive()
+}
+
+/// Get the fused primitive for a modifier of windows, if its function's signature allows it
+fn windows_modifier(modifier: Primitive, f: &Value) -> Option<Primitive> {
+    let sig = f.as_function()?.signature();
+    match modifier {
+        Primitive::Rows if sig == (1, 1) => Some(Primitive::RowsWindows),
+        Primitive::Reduce if sig == (2, 1) => Some(Primitive::ReduceWindows),
+        _ => None,
+    }
+}
+
+/// Whether a primitive can be evaluated at compile time when its arguments are constant
+///
+/// Only pure primitives whose output is no larger than their inputs are folded.
+fn can_fold(prim: Primitive) -> bool {
+    use Primitive::*;
+    prim.is_pure()
+        && prim.outputs() == Some(1)
+        && (matches!(
+            prim.class(),
+            PrimClass::MonadicPervasive | PrimClass::DyadicPervasive
+        ) || matches!(
+            prim,
+            Len | Shape | Reverse | Deshape | First | Last | Transpose | Sum
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run some code, returning its stack or its error message without the location
+    ///
+    /// Errors in fused instructions point at the whole fused idiom.
+    fn run(code: &str, optimize: bool) -> Result<Vec<Value>, String> {
+        let mut env = Uiua::with_native_sys().optimize(optimize);
+        env.load_str(code).map_err(|e| {
+            let message = e.message();
+            message
+                .split_once(": ")
+                .map_or(message.clone(), |(_, message)| message.into())
+        })?;
+        Ok(env.take_stack())
+    }
+
+    #[test]
+    fn optimized_results_match() {
+        for code in [
+            "+1 ×2 3",
+            "[×. 3 -1 ¯2]",
+            "⊙(+1) 1 2",
+            "⊙(⊙(+1)) 1 2 3",
+            "○+η 1",
+            "∶ 1 2 ÷ 2 π",
+            "/+ [1 2 3 4]",
+            "/+ [1_2 3_4]",
+            "/+ []",
+            "/+ \"abc\"",
+            "/+ {1 2_3}",
+            "⊢⍏ [3 1 2 1]",
+            "⊢⍏ [2_1 1_3 1_2 1_2]",
+            "⊢⍏ \"hello\"",
+            "⊢⍏ []",
+            "⬚5⊢⍏ []",
+            "⊢⍏ 1",
+            "⇌⍏ [1 3 1 2 3]",
+            "⇌⍏ []",
+            "▽≠0. [1 0 2 0 3]",
+            "▽≠@ . \"a b c\"",
+            "▽≠2 [1 2 3] [4 5 6]",
+            "▽≠[1 2 3] [1 0 3] \"abc\"",
+            "▽≠1 [1 2] [1 2 3]",
+            "⬚1▽≠1 [1 2] [1 2 3]",
+            "⍜(▽≠1 [1 2 3])(×10) [4 5 6]",
+            "+ [1 2] [1 2 3]",
+            "≡/+◫3 [1 5 2 8 3 9 4]",
+            "≡(/+)◫2 [0.1 0.2 0.3 0.7]",
+            "≡/+◫2 [1e300 1e300 ¯1e300 1]",
+            "≡/↥◫3 [3 1 4 1 5 9 2 6 5 3]",
+            "≡/↧◫3 [3 1 4 1 5 9 2 6 5 3]",
+            "≡/↥◫2 [1 NaN 0 ¯0 2]",
+            "≡/↧◫2 [¯0 0 ¯0]",
+            "≡/+◫2 [1_2 3_4 5_6]",
+            "≡/↥◫2 +@\\0 \"hello\"",
+            "≡/+◫1 [1 2 3]",
+            "≡/+◫3 [1 2 3]",
+            "≡/+◫4 [1 2 3]",
+            "≡/+◫[2] [1 2 3]",
+            "≡/+◫2 []",
+            "≡/+◫2 5",
+            "≡/+◫2 \"abc\"",
+            "≡(⇌)◫2 \"abcd\"",
+            "≡(⊢⍏)◫3 [5 2 7 1 3]",
+            "≡(⎋1 ⇌)◫2 [1 2 3]",
+            "⬚0≡(↙⊢.)◫2 [1 3 2]",
+            "/+◫3 [1 5 2 8 3 9 4]",
+            "/↥◫2 [1 5 2 8]",
+            "/↧◫2 [1_2 5_0 2_7]",
+            "/-◫2 [1 5 2 8]",
+            "/⊂◫2 [1_2 3_4 5_6]",
+            "/(+×2)◫2 [1 5 2 8]",
+            "/(⊂)◫2 \"abcd\"",
+            "/+◫5 [1 2 3]",
+        ] {
+            assert_eq!(run(code, true), run(code, false), "{code}");
+        }
+    }
+
+    #[test]
+    fn instrs_are_optimized() {
+        use Primitive::*;
+        let compiled = |code: &str| {
+            let mut env = Uiua::with_native_sys();
+            env.load_str(&format!("F ← ({code})")).unwrap();
+            let f = env.all_bindings_in_scope().remove("F").unwrap();
+            f.as_function().unwrap().instrs.clone()
+        };
+        assert!(matches!(
+            &compiled("+ +1 ×2 3")[..],
+            [Instr::Push(val), Instr::Prim(Add, _)] if **val == Value::from(7.0)
+        ));
+        let temps = compiled("⊙(+1)⊙(×2)")
+            .into_iter()
+            .filter(|instr| {
+                matches!(
+                    instr,
+                    Instr::PushTempInline { .. } | Instr::PopTempInline { .. }
+                )
+            })
+            .count();
+        assert_eq!(temps, 2);
+        for (code, fused) in [
+            ("/+", Sum),
+            ("⊢⍏", FirstMinIndex),
+            ("⇌⍏", ReverseRise),
+            ("▽≠", KeepNe),
+            ("≡(/+)◫", RowsWindows),
+            ("/(+)◫", ReduceWindows),
+        ] {
+            let instrs = compiled(code);
+            assert!(matches!(instrs[..], [.., Instr::Prim(prim, _)] if prim == fused));
+        }
+    }
+}
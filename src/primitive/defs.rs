@@ -543,6 +543,10 @@ primitive!(
     /// Here, we sort the array descending by the [absolute value] of its elements.
     /// ex: ⊏⍖⌵.6_2_7_0_¯1_5
     (1, Fall, MonadicArray, ("fall", '⍖')),
+    /// Get the index of the first minimum row of an array
+    (1, FirstMinIndex, MonadicArray),
+    /// Get the indices into an array if it were sorted descending, with ties in reverse order
+    (1, ReverseRise, MonadicArray),
     /// Get indices where array values are not equal to zero
     ///
     /// The most basic use is to convert a mask into a list of indices.
@@ -799,6 +803,8 @@ primitive!(
     (2, Keep, DyadicArray, ("keep", '▽')),
     /// End step of under keep
     (3, Unkeep, Misc),
+    /// Keep the rows of an array where two arrays are not equal
+    (3, KeepNe, DyadicArray),
     /// Find the occurences of one array in another
     ///
     /// ex: ⌕ 5 [1 8 5 2 3 5 4 5 6 7]
@@ -864,6 +870,10 @@ primitive!(
     /// ex: /↧ []
     /// ex! /∠ []
     (1[1], Reduce, AggregatingModifier, ("reduce", '/')),
+    /// Get the sum of the rows of an array
+    (1, Sum, MonadicArray),
+    /// Reduce the windows of an array without materializing them
+    (2[1], ReduceWindows, AggregatingModifier),
     /// Apply a reducing function to an array with an initial value
     ///
     /// For reducing without an initial value, see [reduce].
@@ -920,6 +930,8 @@ primitive!(
     /// ex: ⍚¯1/+ [1_2_3 4_5_6 7_8_9]
     /// ex:   ≡/+ [1_2_3 4_5_6 7_8_9]
     ([1], Rows, IteratingModifier, ("rows", '≡')),
+    /// Apply a function to each window of an array without materializing them
+    (2[1], RowsWindows, IteratingModifier),
     /// Apply a function to a fixed value and each row of an array
     ///
     /// ex: ∺⊂ 1 2_3_4
@@ -1327,7 +1339,7 @@ primitive!(
     /// Spawn a thread
     ///
     /// Expects a function.
-    /// In the native interpreter, the function is called in a new OS thread.
+    /// In the native interpreter, the function is called in a new OS thread, or on a thread pool if one is configured.
     /// In the web editor, the function is called and blocks until it returns.
     /// A handle that can be passed to [wait] is pushed to the stack. Handles are just numbers.
     /// [wait] consumes the handle and appends the thread's stack to the current stack.
@@ -1356,7 +1368,24 @@ primitive!(
     /// [wait] is pervasive and will call [each] implicitly.
     /// ex: ↯3_3⇡9
     ///   : wait≡spawn/+.
+    ///
+    /// When waiting on an array of handles, every thread is waited on, even if one of them fails.
+    ///
+    /// To stop waiting after a timeout, use [&twait].
     (1, Wait, Misc, ("wait")),
+    /// Check a property of a function on many generated arrays
+    ///
+    /// Expects a function and a seed.
+    /// The function is called with randomly generated arguments of varying shapes and types.
+    /// Arguments may be numbers, bytes, characters, or boxed arrays.
+    /// The property holds if every call returns `1` without erroring.
+    /// ex: prop(≅⇌⇌.) 0
+    /// ex: prop(=⧻∶⧻⇌.) 0
+    ///
+    /// If the property fails, the counterexample is shrunk to a simpler one that still fails.
+    /// The seed is reported so that the failure can be reproduced.
+    /// ex! prop(≅⇌.) 0
+    (1(0)[1], Prop, OtherModifier, "prop"),
     /// Call a function
     ///
     /// When passing a scalar function, the function is simply called.
@@ -27,8 +27,9 @@ use rand::prelude::*;
 use regex::Regex;
 
 use crate::{
-    algorithm::{fork, loops, reduce, table, zip},
+    algorithm::{fork, loops, prop, reduce, table, windows, zip},
     array::Array,
+    coverage::BranchKind,
     cowslice::cowslice,
     function::Function,
     grid_fmt::GridFmt,
@@ -130,6 +131,12 @@ impl fmt::Display for Primitive {
                 Asin => write!(f, "{Invert}{Sin}"),
                 Acos => write!(f, "{Invert}{Cos}"),
                 Last => write!(f, "{First}{Reverse}"),
+                Sum => write!(f, "{Reduce}{Add}"),
+                FirstMinIndex => write!(f, "{First}{Rise}"),
+                ReverseRise => write!(f, "{Reverse}{Rise}"),
+                KeepNe => write!(f, "{Keep}{Ne}"),
+                RowsWindows => write!(f, "{Rows}{Windows}"),
+                ReduceWindows => write!(f, "{Reduce}{Windows}"),
                 _ => write!(f, "{self:?}"),
             }
         }
@@ -172,6 +179,25 @@ impl Primitive {
     pub fn is_deprecated(&self) -> bool {
         self.deprecation_suggestion().is_some()
     }
+    /// Check if the primitive has no side effects and does not affect control flow
+    pub fn is_pure(&self) -> bool {
+        use Primitive::*;
+        !matches!(
+            self,
+            Sys(_)
+                | Rand
+                | Tag
+                | Now
+                | Trace
+                | InvTrace
+                | Dump
+                | Spawn
+                | Wait
+                | Prop
+                | Break
+                | Recur
+        )
+    }
     pub fn inverse(&self) -> Option<Self> {
         use Primitive::*;
         Some(match self {
@@ -316,6 +342,12 @@ impl Primitive {
                 let into = env.pop(3)?;
                 env.push(from.unkeep(counts, into, env)?);
             }
+            Primitive::KeepNe => {
+                let a = env.pop(1)?;
+                let b = env.pop(2)?;
+                let kept = env.pop(3)?;
+                env.push(a.keep_ne(b, kept, env)?);
+            }
             Primitive::Take => env.dyadic_oo_env(Value::take)?,
             Primitive::Untake => {
                 let index = env.pop(1)?;
@@ -340,6 +372,8 @@ impl Primitive {
             }
             Primitive::Rise => env.monadic_ref_env(|v, env| v.rise(env))?,
             Primitive::Fall => env.monadic_ref_env(|v, env| v.fall(env))?,
+            Primitive::FirstMinIndex => env.monadic_ref_env(Value::first_min_index)?,
+            Primitive::ReverseRise => env.monadic_ref_env(Value::reverse_rise)?,
             Primitive::Pick => env.dyadic_oo_env(Value::pick)?,
             Primitive::Unpick => {
                 let index = env.pop(1)?;
@@ -404,9 +438,12 @@ impl Primitive {
             Primitive::InverseBits => env.monadic_ref_env(Value::inverse_bits)?,
             Primitive::Fold => reduce::fold(env)?,
             Primitive::Reduce => reduce::reduce(env)?,
+            Primitive::Sum => reduce::sum(env)?,
+            Primitive::ReduceWindows => windows::reduce_windows(env)?,
             Primitive::Scan => reduce::scan(env)?,
             Primitive::Each => zip::each(env)?,
             Primitive::Rows => zip::rows(env)?,
+            Primitive::RowsWindows => windows::rows_windows(env)?,
             Primitive::Distribute => zip::distribute(env)?,
             Primitive::Table => table::table(env)?,
             Primitive::Cross => table::cross(env)?,
@@ -500,6 +537,7 @@ impl Primitive {
                 let backup = env.clone_stack_top(f_args);
                 let bottom = env.stack_size().saturating_sub(f_args);
                 if let Err(e) = env.call(f) {
+                    env.record_branch(BranchKind::Try, 1);
                     env.truncate_stack(bottom);
                     env.backend.save_error_color(&e);
                     env.push(e.value());
@@ -507,6 +545,8 @@ impl Primitive {
                         env.push(val);
                     }
                     env.call(handler)?;
+                } else {
+                    env.record_branch(BranchKind::Try, 0);
                 }
             }
             Primitive::Assert => {
@@ -571,6 +611,7 @@ impl Primitive {
                 let handle = env.spawn(f.signature().args, |env| env.call(f))?;
                 env.push(handle);
             }
+            Primitive::Prop => prop::prop(env)?,
             Primitive::Wait => {
                 let handle = env.pop(1)?;
                 env.wait(handle)?;
@@ -815,7 +856,7 @@ impl PrimExample {
         self.should_error
     }
     pub fn should_run(&self) -> bool {
-        !["&sl", "&tcpc"]
+        !["&sl", "&tcpc", "&httpr"]
             .iter()
             .any(|prim| self.input.contains(prim))
     }
@@ -0,0 +1,223 @@
+//! Sampling profiler for Uiua programs
+
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use crate::{
+    function::FunctionId,
+    lex::{CodeSpan, Span},
+    primitive::Primitive,
+};
+
+/// A frame in a profiled call stack
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProfileFrame {
+    /// A function call
+    Function(FunctionId),
+    /// A primitive, either being executed or calling a function
+    Primitive(Primitive),
+}
+
+impl fmt::Display for ProfileFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileFrame::Function(FunctionId::Named(name)) => write!(f, "{name}"),
+            ProfileFrame::Function(id) => write!(f, "{id}"),
+            ProfileFrame::Primitive(prim) => match prim.name() {
+                Some(name) => write!(f, "{name}"),
+                None => write!(f, "{prim}"),
+            },
+        }
+    }
+}
+
+/// Collects samples of the call stack while a program runs
+#[derive(Debug, Clone)]
+pub(crate) struct Profiler {
+    /// The minimum time between samples in milliseconds
+    interval: f64,
+    /// The time at which the last sample was taken
+    last_sample: f64,
+    /// The accumulated time for each stack and the span of its innermost instruction
+    samples: HashMap<(Vec<ProfileFrame>, Option<usize>), f64>,
+}
+
+impl Profiler {
+    pub fn new(interval: f64) -> Self {
+        Profiler {
+            interval,
+            last_sample: instant::now(),
+            samples: HashMap::new(),
+        }
+    }
+    /// Don't attribute time spent outside of execution to the next sample
+    pub fn reset_clock(&mut self) {
+        self.last_sample = instant::now();
+    }
+    /// Check if enough time has passed to take a sample
+    pub fn should_sample(&self) -> bool {
+        instant::now() - self.last_sample >= self.interval
+    }
+    /// Attribute the time since the last sample to a stack
+    pub fn record(&mut self, stack: Vec<ProfileFrame>, span: Option<usize>) {
+        let now = instant::now();
+        *self.samples.entry((stack, span)).or_default() += now - self.last_sample;
+        self.last_sample = now;
+    }
+    /// Resolve span indices and finish profiling
+    pub fn finish(self, spans: &[Span]) -> Profile {
+        let mut samples: Vec<Sample> = (self.samples.into_iter())
+            .map(|((stack, span), duration)| Sample {
+                stack,
+                span: span.and_then(|span| match spans.get(span) {
+                    Some(Span::Code(span)) => Some(span.clone()),
+                    _ => None,
+                }),
+                duration,
+            })
+            .collect();
+        samples.sort_by(|a, b| b.duration.total_cmp(&a.duration));
+        Profile { samples }
+    }
+}
+
+/// The time spent in a single call stack
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// The call stack, outermost frame first
+    pub stack: Vec<ProfileFrame>,
+    /// The span of the innermost instruction
+    pub span: Option<CodeSpan>,
+    /// The time spent in milliseconds
+    pub duration: f64,
+}
+
+/// The results of profiling a program
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// The samples that were taken
+    pub samples: Vec<Sample>,
+}
+
+impl Profile {
+    /// The total time sampled in milliseconds
+    pub fn total(&self) -> f64 {
+        self.samples.iter().map(|s| s.duration).sum()
+    }
+    /// Render the profile in the folded stack format used by flamegraph tools
+    ///
+    /// Stacks are weighted in microseconds.
+    pub fn folded(&self) -> String {
+        let mut folded: HashMap<String, f64> = HashMap::new();
+        for sample in &self.samples {
+            let mut stack = "main".to_string();
+            for frame in &sample.stack {
+                stack.push(';');
+                stack.push_str(&frame.to_string().replace(';', ":"));
+            }
+            *folded.entry(stack).or_default() += sample.duration;
+        }
+        let mut folded: Vec<_> = folded.into_iter().collect();
+        folded.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut s = String::new();
+        for (stack, duration) in folded {
+            let micros = (duration * 1000.0).round() as u64;
+            if micros > 0 {
+                writeln!(s, "{stack} {micros}").unwrap();
+            }
+        }
+        s
+    }
+    /// The total time spent inside each function, including its callees
+    pub fn by_function(&self) -> Vec<(FunctionId, f64)> {
+        self.inclusive(|frame| match frame {
+            ProfileFrame::Function(id) => Some(id.clone()),
+            ProfileFrame::Primitive(_) => None,
+        })
+    }
+    /// The total time spent inside each primitive, including functions it calls
+    pub fn by_primitive(&self) -> Vec<(Primitive, f64)> {
+        self.inclusive(|frame| match frame {
+            ProfileFrame::Primitive(prim) => Some(*prim),
+            ProfileFrame::Function(_) => None,
+        })
+    }
+    /// The time spent executing the instructions at each span
+    pub fn by_span(&self) -> Vec<(CodeSpan, f64)> {
+        let mut times: HashMap<CodeSpan, f64> = HashMap::new();
+        for sample in &self.samples {
+            if let Some(span) = &sample.span {
+                *times.entry(span.clone()).or_default() += sample.duration;
+            }
+        }
+        sorted_times(times)
+    }
+    fn inclusive<T: Clone + Eq + std::hash::Hash>(
+        &self,
+        key: impl Fn(&ProfileFrame) -> Option<T>,
+    ) -> Vec<(T, f64)> {
+        let mut times: HashMap<T, f64> = HashMap::new();
+        for sample in &self.samples {
+            let mut seen = Vec::new();
+            for k in sample.stack.iter().filter_map(&key) {
+                // Recursive calls should only be counted once
+                if !seen.contains(&k) {
+                    *times.entry(k.clone()).or_default() += sample.duration;
+                    seen.push(k);
+                }
+            }
+        }
+        sorted_times(times)
+    }
+    /// Render tables of the most expensive functions, primitives, and spans
+    pub fn summary(&self, limit: usize) -> String {
+        let total = self.total();
+        let mut s = String::new();
+        writeln!(s, "Total sampled time: {total:.2}ms").unwrap();
+        let mut table = |title: &str, rows: Vec<(String, f64)>| {
+            if rows.is_empty() {
+                return;
+            }
+            writeln!(s, "\n{title}:").unwrap();
+            for (name, time) in rows.into_iter().take(limit) {
+                let percent = if total > 0.0 {
+                    time / total * 100.0
+                } else {
+                    0.0
+                };
+                writeln!(s, "  {time:>10.2}ms {percent:>5.1}%  {name}").unwrap();
+            }
+        };
+        let functions = self.by_function().into_iter();
+        table(
+            "Functions",
+            functions
+                .map(|(id, time)| (ProfileFrame::Function(id).to_string(), time))
+                .collect(),
+        );
+        let prims = self.by_primitive().into_iter();
+        table(
+            "Primitives",
+            prims
+                .map(|(prim, time)| (ProfileFrame::Primitive(prim).to_string(), time))
+                .collect(),
+        );
+        let spans = self.by_span().into_iter();
+        table(
+            "Spans",
+            spans
+                .map(|(span, time)| {
+                    let code = span.as_str().lines().next().unwrap_or_default().trim();
+                    (format!("{span} {code}"), time)
+                })
+                .collect(),
+        );
+        s.truncate(s.trim_end().len());
+        s
+    }
+}
+
+fn sorted_times<T>(times: HashMap<T, f64>) -> Vec<(T, f64)> {
+    let mut times: Vec<_> = times.into_iter().collect();
+    times.sort_by(|a, b| b.1.total_cmp(&a.1));
+    times
+}
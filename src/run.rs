@@ -3,7 +3,7 @@ use std::{
     fs,
     hash::Hash,
     mem::take,
-    panic::{catch_unwind, AssertUnwindSafe},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
@@ -14,10 +14,17 @@ use parking_lot::Mutex;
 
 use crate::{
     array::Array,
+    bytecode::Build,
+    coverage::{BranchKind, Coverage, FileCoverage},
+    cowslice::{MemoryLimitExceeded, MemoryTracker},
+    debug::{DebugFrame, Debugger},
     function::*,
-    lex::Span,
+    lex::{CodeSpan, Span},
+    lint::{lint, lint_import, LintConfig},
     parse::parse,
     primitive::{Primitive, CONSTANTS},
+    profiler::{Profile, ProfileFrame, Profiler},
+    testing::TestResult,
     value::Value,
     Diagnostic, DiagnosticKind, Handle, Ident, NativeSys, SysBackend, TraceFrame, UiuaError,
     UiuaResult,
@@ -47,15 +54,25 @@ pub struct Uiua {
     /// A limit on the execution duration in milliseconds
     execution_limit: Option<f64>,
     /// The time at which execution started
-    execution_start: f64,
+    pub(crate) execution_start: f64,
     /// The paths of files currently being imported (used to detect import cycles)
-    current_imports: Arc<Mutex<HashSet<PathBuf>>>,
+    pub(crate) current_imports: Arc<Mutex<HashSet<PathBuf>>>,
     /// The stacks of imported files
     imports: Arc<Mutex<HashMap<PathBuf, Vec<Value>>>>,
+    /// The top-level lines of imported files that were compiled into a built program,
+    /// by import path
+    pub(crate) built_imports: HashMap<String, Arc<[Vec<Instr>]>>,
     /// Accumulated diagnostics
     pub(crate) diagnostics: BTreeSet<Diagnostic>,
+    /// The inferred types of bindings, keyed by the spans of their names
+    pub(crate) binding_types: HashMap<CodeSpan, String>,
     /// Print diagnostics as they are encountered
     pub(crate) print_diagnostics: bool,
+    /// The lints to run on loaded code
+    pub(crate) lint_config: Option<LintConfig>,
+    /// Functions whose declared signatures could not be checked when they were compiled,
+    /// and the spans of their names
+    pub(crate) unchecked_signatures: HashMap<FunctionId, CodeSpan>,
     /// Whether to print the time taken to execute each instruction
     time_instrs: bool,
     /// The time at which the last instruction was executed
@@ -66,6 +83,28 @@ pub struct Uiua {
     cli_file_path: PathBuf,
     /// The system backend
     pub(crate) backend: Arc<dyn SysBackend>,
+    /// The debugger to pause execution with
+    debugger: Option<Arc<dyn Debugger>>,
+    /// Whether to collect test results rather than stopping at the first failure
+    pub(crate) collect_tests: bool,
+    /// Only run tests whose names contain this string
+    pub(crate) test_filter: Option<String>,
+    /// The results of tests that have been run
+    pub(crate) test_results: Vec<TestResult>,
+    /// Whether to overwrite snapshots that do not match
+    pub(crate) bless_snapshots: bool,
+    /// Collected code coverage
+    pub(crate) coverage: Option<Arc<Mutex<Coverage>>>,
+    /// The profiler to sample the call stack with
+    profiler: Option<Profiler>,
+    /// Tracks the memory allocated for arrays
+    memory: Option<Arc<MemoryTracker>>,
+    /// Whether iterating modifiers may call pure functions in parallel
+    parallel: bool,
+    /// Whether to run the peephole optimizer on compiled code
+    optimize: bool,
+    /// The state of a program being built rather than run
+    pub(crate) build: Option<Build>,
 }
 
 #[derive(Clone)]
@@ -80,6 +119,8 @@ pub struct Scope {
     pub local: bool,
     /// The current fill values
     fills: Fills,
+    /// The stack height at the start of this scope if it is a test scope
+    pub test_height: Option<usize>,
 }
 
 impl Default for Scope {
@@ -99,6 +140,7 @@ impl Default for Scope {
             names: HashMap::new(),
             local: false,
             fills: Fills::default(),
+            test_height: None,
         }
     }
 }
@@ -174,16 +216,31 @@ impl Uiua {
             new_functions: Vec::new(),
             current_imports: Arc::new(Mutex::new(HashSet::new())),
             imports: Arc::new(Mutex::new(HashMap::new())),
+            built_imports: HashMap::new(),
             mode: RunMode::Normal,
             diagnostics: BTreeSet::new(),
+            binding_types: HashMap::new(),
             backend: Arc::new(NativeSys),
             print_diagnostics: false,
+            lint_config: None,
+            unchecked_signatures: HashMap::new(),
             time_instrs: false,
             last_time: 0.0,
             cli_arguments: Vec::new(),
             cli_file_path: PathBuf::new(),
             execution_limit: None,
             execution_start: 0.0,
+            debugger: None,
+            collect_tests: false,
+            test_filter: None,
+            test_results: Vec::new(),
+            bless_snapshots: false,
+            coverage: None,
+            profiler: None,
+            memory: None,
+            parallel: true,
+            optimize: true,
+            build: None,
         }
     }
     /// Create a new Uiua runtime with a custom IO backend
@@ -203,15 +260,60 @@ impl Uiua {
         self.print_diagnostics = print_diagnostics;
         self
     }
+    /// Run lints on loaded code and report them as diagnostics
+    pub fn with_lints(mut self, config: LintConfig) -> Self {
+        self.lint_config = Some(config);
+        self
+    }
     pub fn time_instrs(mut self, time_instrs: bool) -> Self {
         self.time_instrs = time_instrs;
         self
     }
+    /// Allow [`Primitive::Rows`], [`Primitive::Each`], and [`Primitive::Table`] to split large arrays across threads
+    ///
+    /// Only functions without side effects are run in parallel.
+    /// Default is `true`
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+    /// Run a peephole optimizer over compiled code
+    ///
+    /// The optimizer folds constant expressions and fuses some common
+    /// idioms into faster instructions. It is always disabled when
+    /// collecting coverage.
+    /// Default is `true`
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
     /// Limit the execution duration
     pub fn with_execution_limit(mut self, limit: Duration) -> Self {
         self.execution_limit = Some(limit.as_millis() as f64);
         self
     }
+    /// Limit the number of bytes that can be allocated for array data
+    ///
+    /// Allocations that would exceed the limit fail with [`UiuaError::MemoryLimit`].
+    /// The limit is shared with spawned threads.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory = Some(Arc::new(MemoryTracker::new(bytes)));
+        self
+    }
+    /// Get the number of bytes currently allocated for array data
+    ///
+    /// Only available if a memory limit was set with [`Uiua::with_memory_limit`].
+    /// Arrays are only counted as released if they are dropped while the runtime is executing.
+    pub fn memory_usage(&self) -> Option<usize> {
+        self.memory.as_ref().map(|memory| memory.allocated())
+    }
+    /// Attach a [`Debugger`] that is called before each instruction
+    ///
+    /// Spawned threads are not debugged
+    pub fn with_debugger(mut self, debugger: impl Debugger) -> Self {
+        self.debugger = Some(Arc::new(debugger));
+        self
+    }
     /// Set the [`RunMode`]
     ///
     /// Default is [`RunMode::Normal`]
@@ -223,6 +325,53 @@ impl Uiua {
     pub fn mode(&self) -> RunMode {
         self.mode
     }
+    /// Collect the results of test cases rather than stopping at the first failure
+    ///
+    /// Results can be retrieved with [`Uiua::take_test_results`]
+    pub fn collect_tests(mut self, collect_tests: bool) -> Self {
+        self.collect_tests = collect_tests;
+        self
+    }
+    /// Only run test cases whose names contain the given string
+    pub fn with_test_filter(mut self, filter: impl Into<String>) -> Self {
+        self.test_filter = Some(filter.into());
+        self
+    }
+    /// Overwrite stored snapshots that do not match rather than failing
+    pub fn bless_snapshots(mut self, bless: bool) -> Self {
+        self.bless_snapshots = bless;
+        self
+    }
+    /// Record which spans are executed
+    ///
+    /// The results can be retrieved with [`Uiua::coverage`]
+    pub fn collect_coverage(mut self, collect_coverage: bool) -> Self {
+        self.coverage = collect_coverage.then(Default::default);
+        self
+    }
+    /// Get the coverage of the code that has been run so far
+    pub fn coverage(&self) -> Vec<FileCoverage> {
+        self.coverage.as_ref().map_or_else(Vec::new, |coverage| {
+            coverage.lock().report(&self.spans.lock())
+        })
+    }
+    /// Sample the call stack at most once per `interval`
+    ///
+    /// The results can be retrieved with [`Uiua::take_profile`].
+    /// Spawned threads are not profiled
+    pub fn with_profiler(mut self, interval: Duration) -> Self {
+        self.profiler = Some(Profiler::new(interval.as_secs_f64() * 1000.0));
+        self
+    }
+    /// Take the results of profiling
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        let profiler = self.profiler.take()?;
+        Some(profiler.finish(&self.spans.lock()))
+    }
+    /// Take the results of the test cases that have been run
+    pub fn take_test_results(&mut self) -> Vec<TestResult> {
+        take(&mut self.test_results)
+    }
     /// Set the command line arguments
     pub fn with_args(mut self, args: Vec<String>) -> Self {
         self.cli_arguments = args;
@@ -276,7 +425,15 @@ impl Uiua {
     }
     fn load_impl(&mut self, input: &str, path: Option<&Path>) -> UiuaResult {
         self.execution_start = instant::now();
-        let (items, errors, diagnostics) = parse(input, path);
+        let (items, errors, mut diagnostics) = parse(input, path);
+        if let Some(config) = &self.lint_config {
+            // Imported files are loaded in their own scope
+            if self.higher_scopes.is_empty() {
+                diagnostics.extend(lint(&items, config));
+            } else {
+                diagnostics.extend(lint_import(&items, config));
+            }
+        }
         if self.print_diagnostics {
             for diagnostic in diagnostics {
                 println!("{}", diagnostic.show(true));
@@ -290,8 +447,21 @@ impl Uiua {
         if let Some(path) = path {
             self.current_imports.lock().insert(path.into());
         }
-        let res = match catch_unwind(AssertUnwindSafe(|| self.items(items, false))) {
+        let res = self.catch_crash(input, |env| env.items(items, false));
+        if let Some(path) = path {
+            self.current_imports.lock().remove(path);
+        }
+        res
+    }
+    /// Run some code, turning a crash of the interpreter into an error
+    pub(crate) fn catch_crash(
+        &mut self,
+        input: &str,
+        f: impl FnOnce(&mut Self) -> UiuaResult,
+    ) -> UiuaResult {
+        match catch_unwind(AssertUnwindSafe(|| f(self))) {
             Ok(res) => res,
+            Err(payload) if payload.is::<MemoryLimitExceeded>() => Err(self.memory_limit_error()),
             Err(_) => Err(self.error(format!(
                 "\
 The interpreter has crashed!
@@ -304,11 +474,7 @@ code:
                 self.span(),
                 input
             ))),
-        };
-        if let Some(path) = path {
-            self.current_imports.lock().remove(path);
         }
-        res
     }
     fn trace_error(&self, mut error: UiuaError, frame: StackFrame) -> UiuaError {
         let mut frames = Vec::new();
@@ -335,6 +501,22 @@ code:
         }
     }
     pub(crate) fn import(&mut self, input: &str, path: &Path) -> UiuaResult {
+        self.import_with(path, |env| env.load_str_path(input, path).map(drop))
+    }
+    /// Import a file that was compiled into a built program
+    pub(crate) fn import_built(&mut self, path: &Path, lines: &[Vec<Instr>]) -> UiuaResult {
+        self.import_with(path, |env| {
+            env.current_imports.lock().insert(path.into());
+            let res = env.run_built_lines(lines);
+            env.current_imports.lock().remove(path);
+            res
+        })
+    }
+    fn import_with(
+        &mut self,
+        path: &Path,
+        load: impl FnOnce(&mut Self) -> UiuaResult,
+    ) -> UiuaResult {
         if self.current_imports.lock().contains(path) {
             return Err(self.error(format!(
                 "Cycle detected importing {}",
@@ -342,7 +524,7 @@ code:
             )));
         }
         if !self.imports.lock().contains_key(path) {
-            let import = self.in_scope(false, |env| env.load_str_path(input, path).map(drop))?;
+            let import = self.in_scope(false, load)?;
             self.imports.lock().insert(path.into(), import);
         }
         self.stack.extend(self.imports.lock()[path].iter().cloned());
@@ -350,6 +532,9 @@ code:
     }
     pub(crate) fn exec_global_instrs(&mut self, instrs: Vec<Instr>) -> UiuaResult {
         let func = Function::new(FunctionId::Main, instrs, Signature::new(0, 0));
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset_clock();
+        }
         self.exec(StackFrame {
             function: Arc::new(func),
             call_span: 0,
@@ -358,15 +543,36 @@ code:
         })
     }
     fn exec(&mut self, frame: StackFrame) -> UiuaResult {
+        let _memory = self.memory.as_ref().map(MemoryTracker::enter);
         let ret_height = self.scope.call.len();
         self.scope.call.push(frame);
         let mut formatted_instr = String::new();
         while self.scope.call.len() > ret_height {
             let frame = self.scope.call.last().unwrap();
-            let Some(instr) = frame.function.instrs.get(frame.pc) else {
+            if frame.pc >= frame.function.instrs.len() {
                 self.scope.call.pop();
                 continue;
-            };
+            }
+            if let Some(debugger) = self.debugger.clone() {
+                let function = frame.function.clone();
+                let instr = &function.instrs[frame.pc];
+                if let Err(err) = debugger.before_instr(self, instr) {
+                    let frames = self
+                        .scope
+                        .call
+                        .split_off(ret_height.min(self.scope.call.len()));
+                    return Err(frames
+                        .into_iter()
+                        .fold(err, |err, frame| self.trace_error(err, frame)));
+                }
+            }
+            let frame = self.scope.call.last().unwrap();
+            let instr = &frame.function.instrs[frame.pc];
+            if let Some(coverage) = &self.coverage {
+                if let Some(span) = instr.span() {
+                    coverage.lock().hit(span);
+                }
+            }
             // Uncomment to debug
             // if !self.scope.array.is_empty() {
             //     print!("array: ");
@@ -388,7 +594,7 @@ code:
             let res = match instr {
                 &Instr::Prim(prim, span) => {
                     self.push_span(span, Some(prim));
-                    let res = prim.run(self);
+                    let res = self.catch_memory_limit(|env| prim.run(env));
                     self.pop_span();
                     res
                 }
@@ -419,7 +625,7 @@ code:
                     let val = if values.is_empty() && constant {
                         Array::<Arc<Function>>::default().into()
                     } else {
-                        Value::from_row_values(values, self)?
+                        self.catch_memory_limit(|env| Value::from_row_values(values, env))?
                     };
                     self.pop_span();
                     self.push(val);
@@ -511,7 +717,17 @@ code:
                 );
                 self.last_time = instant::now();
             }
+            if self.profiler.as_ref().is_some_and(Profiler::should_sample) {
+                self.sample_profile();
+            }
             if let Err(mut err) = res {
+                if let Some(debugger) = self.debugger.clone() {
+                    if !matches!(err, UiuaError::Traced { .. }) {
+                        if let Err(e) = debugger.on_error(self, &err) {
+                            err = e;
+                        }
+                    }
+                }
                 // Trace errors
                 let frames = self
                     .scope
@@ -533,13 +749,63 @@ code:
         }
         Ok(())
     }
+    /// Turn running out of memory into an error
+    fn catch_memory_limit<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> UiuaResult<T>,
+    ) -> UiuaResult<T> {
+        if self.memory.is_none() {
+            return f(self);
+        }
+        match catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(res) => res,
+            Err(payload) if payload.is::<MemoryLimitExceeded>() => Err(self.memory_limit_error()),
+            Err(payload) => resume_unwind(payload),
+        }
+    }
+    fn memory_limit_error(&self) -> UiuaError {
+        let limit = self.memory.as_ref().map_or(0, |memory| memory.limit());
+        UiuaError::MemoryLimit(limit, self.span())
+    }
+    /// Attribute the time since the last sample to the current call stack
+    fn sample_profile(&mut self) {
+        let mut stack = Vec::new();
+        for frame in (self.higher_scopes.iter())
+            .chain([&self.scope])
+            .flat_map(|scope| &scope.call)
+        {
+            if frame.function.id != FunctionId::Main {
+                stack.push(ProfileFrame::Function(frame.function.id.clone()));
+            }
+            stack.extend(
+                frame
+                    .spans
+                    .iter()
+                    .filter_map(|(_, prim)| prim.map(ProfileFrame::Primitive)),
+            );
+        }
+        let instr = (self.scope.call.last()).and_then(|frame| frame.function.instrs.get(frame.pc));
+        if let Some(&Instr::Prim(prim, _)) = instr {
+            stack.push(ProfileFrame::Primitive(prim));
+        }
+        let span = instr.and_then(Instr::span);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(stack, span);
+        }
+    }
+    /// Record which arm of a branching primitive was taken
+    pub(crate) fn record_branch(&self, kind: BranchKind, arm: usize) {
+        if let Some(coverage) = &self.coverage {
+            coverage.lock().take_branch(self.span_index(), kind, arm);
+        }
+    }
     pub(crate) fn push_span(&mut self, span: usize, prim: Option<Primitive>) {
         self.scope.call.last_mut().unwrap().spans.push((span, prim));
     }
     pub(crate) fn pop_span(&mut self) {
         self.scope.call.last_mut().unwrap().spans.pop();
     }
-    fn call_with_span(&mut self, f: Value, call_span: usize) -> UiuaResult {
+    pub(crate) fn call_with_span(&mut self, f: Value, call_span: usize) -> UiuaResult {
         match f.into_function() {
             Ok(f) => self.call_function_with_span(f, call_span)?,
             Err(val) => self.push(val),
@@ -551,12 +817,46 @@ code:
         f: impl Into<Arc<Function>>,
         call_span: usize,
     ) -> UiuaResult {
+        let function = f.into();
+        let unchecked = (!self.unchecked_signatures.is_empty())
+            .then(|| self.unchecked_signatures.get(&function.id).cloned())
+            .flatten();
+        let Some(name_span) = unchecked else {
+            return self.exec(StackFrame {
+                function,
+                call_span,
+                spans: Vec::new(),
+                pc: 0,
+            });
+        };
+        let sig = function.signature();
+        let expected_height = self.stack.len().saturating_sub(sig.args) + sig.outputs;
         self.exec(StackFrame {
-            function: f.into(),
+            function: function.clone(),
             call_span,
             spans: Vec::new(),
             pc: 0,
-        })
+        })?;
+        if self.stack.len() > expected_height {
+            // Each function is only reported once
+            self.unchecked_signatures.remove(&function.id);
+            let extra = self.stack.len() - expected_height;
+            let diagnostic = Diagnostic::new(
+                format!(
+                    "{} is declared as {sig} but leaves {extra} extra value{} on the stack",
+                    function.id,
+                    if extra == 1 { "" } else { "s" }
+                ),
+                name_span,
+                DiagnosticKind::Warning,
+            );
+            if self.print_diagnostics {
+                eprintln!("{}", diagnostic.show(true));
+            } else {
+                self.diagnostics.insert(diagnostic);
+            }
+        }
+        Ok(())
     }
     /// Call a function
     #[inline]
@@ -655,6 +955,47 @@ code:
     pub fn push(&mut self, val: impl Into<Value>) {
         self.stack.push(val.into());
     }
+    /// Get a reference to the stack
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+    /// Get a reference to the temp stack used for inlining
+    pub fn inline_stack(&self) -> &[Value] {
+        &self.inline_stack
+    }
+    /// Get a reference to the temp stack used for unders
+    pub fn under_stack(&self) -> &[Value] {
+        &self.under_stack
+    }
+    /// Get the number of frames in the call stack, including those of enclosing scopes
+    pub fn call_depth(&self) -> usize {
+        (self.higher_scopes.iter())
+            .chain([&self.scope])
+            .map(|scope| scope.call.len())
+            .sum()
+    }
+    /// Get a view of the call stack, including the frames of enclosing scopes
+    ///
+    /// The innermost frame is last
+    pub fn call_stack(&self) -> Vec<DebugFrame> {
+        let spans = self.spans.lock();
+        (self.higher_scopes.iter())
+            .chain([&self.scope])
+            .flat_map(|scope| &scope.call)
+            .map(|frame| DebugFrame {
+                id: frame.function.id.clone(),
+                call_span: spans[frame.call_span].clone(),
+                pc: frame.pc,
+                span: (frame.function.instrs.get(frame.pc))
+                    .and_then(Instr::span)
+                    .map(|span| spans[span].clone()),
+            })
+            .collect()
+    }
+    /// Get the span at an index in the span table
+    pub fn get_span(&self, span: usize) -> Span {
+        self.spans.lock()[span].clone()
+    }
     /// Take the entire stack
     pub fn take_stack(&mut self) -> Vec<Value> {
         take(&mut self.stack)
@@ -814,27 +1155,13 @@ code:
         }
         res
     }
-    /// Spawn a thread
-    pub(crate) fn spawn(
-        &mut self,
-        capture_count: usize,
-        f: impl FnOnce(&mut Self) -> UiuaResult + Send + 'static,
-    ) -> UiuaResult<Value> {
-        if self.stack.len() < capture_count {
-            return Err(self.error(format!(
-                "Excepted at least {} value(s) on the stack, but there are {}",
-                capture_count,
-                self.stack.len()
-            )))?;
-        }
-        let env = Uiua {
+    /// Create a runtime with the given stack for running on another thread
+    pub(crate) fn thread_env(&self, stack: Vec<Value>) -> Self {
+        Uiua {
             new_functions: Vec::new(),
             globals: self.globals.clone(),
             spans: self.spans.clone(),
-            stack: self
-                .stack
-                .drain(self.stack.len() - capture_count..)
-                .collect(),
+            stack,
             inline_stack: Vec::new(),
             under_stack: Vec::new(),
             scope: self.scope.clone(),
@@ -842,8 +1169,12 @@ code:
             mode: self.mode,
             current_imports: self.current_imports.clone(),
             imports: self.imports.clone(),
+            built_imports: self.built_imports.clone(),
             diagnostics: BTreeSet::new(),
+            binding_types: HashMap::new(),
             print_diagnostics: self.print_diagnostics,
+            lint_config: self.lint_config.clone(),
+            unchecked_signatures: self.unchecked_signatures.clone(),
             time_instrs: self.time_instrs,
             last_time: self.last_time,
             cli_arguments: self.cli_arguments.clone(),
@@ -851,7 +1182,52 @@ code:
             backend: self.backend.clone(),
             execution_limit: self.execution_limit,
             execution_start: self.execution_start,
-        };
+            debugger: None,
+            collect_tests: false,
+            test_filter: None,
+            test_results: Vec::new(),
+            bless_snapshots: self.bless_snapshots,
+            coverage: self.coverage.clone(),
+            profiler: None,
+            memory: self.memory.clone(),
+            parallel: self.parallel,
+            optimize: self.optimize,
+            build: None,
+        }
+    }
+    /// Create a runtime for evaluating constant expressions during compilation
+    pub(crate) fn const_env(&self) -> Self {
+        let mut env = self.thread_env(Vec::new());
+        env.scope.fills = Fills::default();
+        env.time_instrs = false;
+        env
+    }
+    /// Whether compiled code should be optimized
+    pub(crate) fn optimize_enabled(&self) -> bool {
+        self.optimize && self.coverage.is_none()
+    }
+    /// Whether iterating modifiers may call pure functions in parallel
+    pub(crate) fn parallel_enabled(&self) -> bool {
+        self.parallel && self.debugger.is_none() && self.profiler.is_none()
+    }
+    /// Spawn a thread
+    pub(crate) fn spawn(
+        &mut self,
+        capture_count: usize,
+        f: impl FnOnce(&mut Self) -> UiuaResult + Send + 'static,
+    ) -> UiuaResult<Value> {
+        if self.stack.len() < capture_count {
+            return Err(self.error(format!(
+                "Excepted at least {} value(s) on the stack, but there are {}",
+                capture_count,
+                self.stack.len()
+            )))?;
+        }
+        let stack = self
+            .stack
+            .drain(self.stack.len() - capture_count..)
+            .collect();
+        let env = self.thread_env(stack);
         self.backend
             .spawn(env, Box::new(f))
             .map(Value::from)
@@ -874,12 +1250,13 @@ code:
                 .map_err(|e| e.unwrap_or_else(|e| self.error(e)))?;
             self.stack.extend(thread_stack);
         } else {
-            let mut rows = Vec::new();
-            for handle in handles.data {
-                let thread_stack = self
-                    .backend
-                    .wait(handle)
-                    .map_err(|e| e.unwrap_or_else(|e| self.error(e)))?;
+            // Wait on every thread before reporting an error so none are left behind
+            let results: Vec<_> = (handles.data.into_iter())
+                .map(|handle| self.backend.wait(handle))
+                .collect();
+            let mut rows = Vec::with_capacity(results.len());
+            for result in results {
+                let thread_stack = result.map_err(|e| e.unwrap_or_else(|e| self.error(e)))?;
                 let row = if thread_stack.len() == 1 {
                     thread_stack.into_iter().next().unwrap()
                 } else {
@@ -19,7 +19,10 @@ use crate::{
     cowslice::{cowslice, CowSlice},
     function::Function,
     grid_fmt::GridFmt,
+    lex::{CodeSpan, Span},
     primitive::PrimDoc,
+    run::RunMode,
+    testing::line_diff,
     value::Value,
     Uiua, UiuaError, UiuaResult,
 };
@@ -127,8 +130,8 @@ sys_op! {
     ///
     /// Standard IO will be captured. The exit code, stdout, and stderr will each be pushed to the stack.
     ///
-    /// On the web, stdout is the return value of the expression 
-    /// 
+    /// On the web, stdout is the return value of the expression
+    ///
     /// Expects either a string, a rank `2` character array, or a rank `1` array of [box] strings.
     (1(3), RunCapture, "&runc", "run command capture"),
     /// Change the current directory
@@ -159,7 +162,7 @@ sys_op! {
     (1(1), Invoke, "&invk", "invoke"),
     /// Close a stream by its handle
     ///
-    /// This will close files, tcp listeners, and tcp sockets.
+    /// This will close files, tcp listeners, tcp sockets, udp sockets, and channels.
     (1(0), Close, "&cl", "close handle"),
     /// Open a file and return a handle to it
     ///
@@ -189,6 +192,40 @@ sys_op! {
     ///
     /// Expects a path and a [rank]`1` array or either numbers or characters.
     (2(0), FWriteAll, "&fwa", "file - write all"),
+    /// Append the contents of an array to the end of a file
+    ///
+    /// The file is created if it does not exist.
+    /// Expects a path and a [rank]`1` array or either numbers or characters.
+    (2(0), FAppendAll, "&faa", "file - append all"),
+    /// Delete a file
+    (1(0), FDelete, "&fde", "file - delete"),
+    /// Create a directory and any missing parent directories
+    (1(0), FMakeDir, "&fmd", "file - make directory"),
+    /// Remove a directory and all of its contents
+    (1(0), FRemoveDir, "&frd", "file - remove directory"),
+    /// Rename or move a file or directory
+    ///
+    /// Expects the current path and then the new path.
+    (2(0), FRename, "&fmv", "file - move"),
+    /// Copy a file
+    ///
+    /// Expects the source path and then the destination path.
+    (2(0), FCopy, "&fcp", "file - copy"),
+    /// Get the metadata of a file or directory
+    ///
+    /// Returns a 3-element array of the size in bytes, the last modification time in seconds since the Unix epoch, and whether the path is a directory.
+    /// The modification time is `NaN` if it is not available.
+    (1, FMetadata, "&fmeta", "file - metadata"),
+    /// Find all paths that match a glob pattern
+    ///
+    /// `*` matches any characters in a name, `?` matches a single character, `[abc]` or `[a-z]` matches a character class, and `**` matches any number of directories.
+    /// Returns a rank `1` array of [box]ed strings.
+    (1, FGlob, "&fg", "file - glob"),
+    /// Recursively list the contents of a directory
+    ///
+    /// The paths, their depths, and whether each one is a directory will each be pushed to the stack.
+    /// The paths are [box]ed strings. The directory's direct children have depth `1`.
+    (1(3), FWalk, "&fwk", "file - walk directory"),
     /// Decode an image from a byte array
     ///
     /// Supported formats are `jpg`, `png`, `bmp`, `gif`, and `ico`.
@@ -301,14 +338,63 @@ sys_op! {
     (1, TcpAccept, "&tcpa", "tcp - accept"),
     /// Create a TCP socket and connect it to an address
     (1, TcpConnect, "&tcpc", "tcp - connect"),
-    /// Set a TCP socket to non-blocking mode
+    /// Set a TCP or UDP socket to non-blocking mode
     (1, TcpSetNonBlocking, "&tcpsnb", "tcp - set non-blocking"),
-    /// Set the read timeout of a TCP socket in seconds
+    /// Set the read timeout of a TCP or UDP socket in seconds
     (2(0), TcpSetReadTimeout, "&tcpsrt", "tcp - set read timeout"),
-    /// Set the write timeout of a TCP socket in seconds
+    /// Set the write timeout of a TCP or UDP socket in seconds
     (2(0), TcpSetWriteTimeout, "&tcpswt", "tcp - set write timeout"),
     /// Get the connection address of a TCP socket
     (1, TcpAddr, "&tcpaddr", "tcp - address"),
+    /// Make an HTTP or HTTPS request and get the response body as a string
+    ///
+    /// Expects a method, a URL, headers, and a body.
+    /// Headers are a list of [box]ed name-value pairs, or a single pair.
+    /// The body can be a string or a byte array.
+    /// Redirects are followed.
+    ///
+    /// The status code, headers, and body will each be pushed to the stack.
+    /// The response headers are a [rank]`2` array of [box]ed name-value pairs.
+    ///
+    /// ex: &httprs "GET" "https://example.com" {} ""
+    (4(3), HttpRequestStr, "&httprs", "http - request to string"),
+    /// Make an HTTP or HTTPS request and get the response body as bytes
+    ///
+    /// This is the same as [&httprs], except the body is a byte array.
+    (4(3), HttpRequestBytes, "&httprb", "http - request to bytes"),
+    /// Read an HTTP request from a TCP socket
+    ///
+    /// This is meant to be used with a socket from [&tcpa].
+    /// The body is read according to the `Content-Length` or `Transfer-Encoding` header.
+    ///
+    /// The method, path, query, headers, and body will each be pushed to the stack.
+    /// The query is the part of the request target after `?`, or an empty string.
+    /// The headers are a [rank]`2` array of [box]ed name-value pairs, and the body is a byte array.
+    (1(5), HttpReadRequest, "&httprq", "http - read request"),
+    /// Write an HTTP response to a TCP socket
+    ///
+    /// Expects a status code, headers, a body, and a socket handle.
+    /// Headers are a list of [box]ed name-value pairs, or a single pair.
+    /// The body can be a string or a byte array.
+    /// A `Content-Length` header is added if there is none.
+    ///
+    /// The socket is not closed.
+    (4(0), HttpWriteResponse, "&httpw", "http - write response"),
+    /// Create a UDP socket and bind it to an address
+    ///
+    /// Use port `0` to let the system choose a port.
+    (1, UdpBind, "&udpb", "udp - bind"),
+    /// Send a datagram from a UDP socket to an address
+    ///
+    /// Expects the data, the address, and the socket handle.
+    /// The data must be a [rank]`1` array of either numbers or characters.
+    (3(0), UdpSendTo, "&udps", "udp - send to"),
+    /// Receive a datagram with a UDP socket
+    ///
+    /// The datagram's bytes and the sender's address will each be pushed to the stack.
+    (1(2), UdpReceiveFrom, "&udpr", "udp - receive from"),
+    /// Get the local address of a UDP socket
+    (1, UdpAddr, "&udpaddr", "udp - address"),
     /// Make an HTTP request
     ///
     /// Takes in an 1.x HTTP request and returns an HTTP response.
@@ -330,6 +416,60 @@ sys_op! {
     /// - The HTTP version
     /// - The `Host` header (if not defined)
     (2, HttpsWrite, "&httpsw", "http - Make an HTTP request"),
+    /// Wait for a thread to finish, giving up after a timeout
+    ///
+    /// Expects a timeout in seconds and a handle returned by [spawn].
+    /// If the thread finishes in time, a list of its [box]ed results is pushed under a `1`.
+    /// Otherwise, the handle is pushed under a `0` so that it can be waited on again.
+    /// ex: &twait 1 spawn(+1) 2
+    /// ex: &twait 1 spawn(1 2)
+    /// ex: &twait 0 spawn(&sl 1)
+    (2(2), WaitTimeout, "&twait", "thread - wait with timeout"),
+    /// Create a channel for sending values between threads
+    ///
+    /// Returns a handle that can be used with [&chs], [&chr], and [&chtr].
+    /// Values are received in the order they were sent.
+    /// ex: Ch ← &chn
+    ///   : &chs 1 Ch
+    ///   : &chs "two" Ch
+    ///   : &chr Ch
+    ///   : &chr Ch
+    ///
+    /// Channels can be captured by [spawn]ed threads.
+    /// ex: Ch ← &chn
+    ///   : ;spawn(&chs ×2 5) Ch
+    ///   : &chr Ch
+    ///
+    /// Closing a channel with [&cl] makes any further sends fail.
+    /// Threads waiting to receive will get the values that were already sent, then fail.
+    (0, ChannelNew, "&chn", "channel - new"),
+    /// Send a value through a channel
+    ///
+    /// Expects a value and a channel handle.
+    (2(0), ChannelSend, "&chs", "channel - send"),
+    /// Receive a value from a channel
+    ///
+    /// Waits until a value is sent if there is none.
+    (1, ChannelReceive, "&chr", "channel - receive"),
+    /// Try to receive a value from a channel without waiting
+    ///
+    /// If a value was received, it is pushed to the stack under a `1`.
+    /// Otherwise, an empty list is pushed under a `0`.
+    /// ex: Ch ← &chn
+    ///   : &chs 5 Ch
+    ///   : &chtr Ch
+    ///   : &chtr Ch
+    (1(2), ChannelTryReceive, "&chtr", "channel - try receive"),
+    /// Compare the stack of a test scope against a stored snapshot
+    ///
+    /// Expects a name.
+    /// The formatted values on the stack of the current test scope are compared against the file `<script>.<name>.snap` next to the script being run.
+    /// The stack is left unchanged.
+    /// If the file does not match, an error showing the difference is thrown.
+    ///
+    /// Snapshots are only created when running `uiua test`.
+    /// Run `uiua test --bless` to update snapshots that do not match.
+    (1(0), Snapshot, "&snap", "snapshot"),
 }
 
 /// A handle to an IO stream
@@ -359,6 +499,61 @@ impl From<Handle> for Value {
     }
 }
 
+/// Metadata about a file or directory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileMetadata {
+    /// The size in bytes
+    pub size: u64,
+    /// The last modification time in seconds since the Unix epoch, if available
+    pub modified: Option<f64>,
+    /// Whether the path is a directory
+    pub is_dir: bool,
+}
+
+/// An entry found while walking a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkEntry {
+    /// The path, starting with the walked directory
+    pub path: String,
+    /// The depth, where the walked directory's children have depth `1`
+    pub depth: usize,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+}
+
+/// An HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// The method, like `GET` or `POST`
+    pub method: String,
+    /// The URL, starting with `http://` or `https://`
+    pub url: String,
+    /// The header names and values
+    pub headers: Vec<(String, String)>,
+    /// The body
+    pub body: Vec<u8>,
+}
+
+/// An HTTP response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The status code
+    pub status: u16,
+    /// The header names and values
+    pub headers: Vec<(String, String)>,
+    /// The body, with any transfer encoding removed
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Get the value of a header, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        (self.headers.iter())
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
 pub type AudioStreamFn = Box<dyn FnMut(Vec<f64>) -> UiuaResult<Vec<[f64; 2]>> + Send>;
 
 #[allow(unused_variables)]
@@ -382,8 +577,11 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn scan_line_stdin(&self) -> Result<Option<String>, String> {
         Err("Reading from stdin is not supported in this environment".into())
     }
-    fn var(&self, name: &str) -> Option<String> {
-        None
+    /// Get an environment variable
+    ///
+    /// Should return `Ok(None)` if the variable is not set.
+    fn var(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(None)
     }
     fn term_size(&self) -> Result<(usize, usize), String> {
         Err("Getting the terminal size is not supported in this environment".into())
@@ -435,6 +633,41 @@ pub trait SysBackend: Any + Send + Sync + 'static {
         self.close(handle)?;
         Ok(())
     }
+    fn file_append_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    fn delete_file(&self, path: &str) -> Result<(), String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    /// Create a directory and any missing parents
+    fn make_dir(&self, path: &str) -> Result<(), String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    /// Remove a directory and all of its contents
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    fn copy_file(&self, from: &str, to: &str) -> Result<(), String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        Err("This IO operation is not supported in this environment".into())
+    }
+    /// Recursively list the contents of a directory
+    ///
+    /// Entries are sorted, and each directory comes before its contents.
+    fn walk_dir(&self, path: &str) -> Result<Vec<WalkEntry>, String> {
+        let mut entries = Vec::new();
+        walk_with(self, path, 1, &mut entries)?;
+        Ok(entries)
+    }
+    /// Find the paths that match a glob pattern
+    fn glob(&self, pattern: &str) -> Result<Vec<String>, String> {
+        glob_with(self, pattern)
+    }
     fn sleep(&self, seconds: f64) -> Result<(), String> {
         Err("Sleeping is not supported in this environment".into())
     }
@@ -482,6 +715,43 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     ) -> Result<(), String> {
         Err("TCP sockets are not supported in this environment".into())
     }
+    /// Read a complete HTTP request from a stream
+    ///
+    /// The request's `url` is the request target, like `/path?query`.
+    fn http_read_request(&self, handle: Handle) -> Result<HttpRequest, String> {
+        #[cfg(feature = "https")]
+        {
+            crate::http::read_request(self, handle)
+        }
+        #[cfg(not(feature = "https"))]
+        Err("Reading HTTP requests is not supported in this environment".into())
+    }
+    /// Write an HTTP response to a stream
+    fn http_write_response(&self, handle: Handle, response: HttpResponse) -> Result<(), String> {
+        #[cfg(feature = "https")]
+        {
+            crate::http::write_response(self, handle, response)
+        }
+        #[cfg(not(feature = "https"))]
+        Err("Writing HTTP responses is not supported in this environment".into())
+    }
+    /// Make an HTTP request, following redirects
+    fn http_request(&self, request: HttpRequest) -> Result<HttpResponse, String> {
+        Err("Making HTTP requests is not supported in this environment".into())
+    }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    fn udp_send_to(&self, handle: Handle, data: &[u8], addr: &str) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Receive a datagram and the address of its sender
+    fn udp_receive_from(&self, handle: Handle) -> Result<(Vec<u8>, String), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
     fn close(&self, handle: Handle) -> Result<(), String> {
         Ok(())
     }
@@ -500,6 +770,30 @@ pub trait SysBackend: Any + Send + Sync + 'static {
             "Joining threads is not supported in this environment".into()
         ))
     }
+    /// Wait for a thread to finish for at most `timeout` seconds
+    ///
+    /// Returns `None` if the thread did not finish in time. The handle remains valid in that case.
+    fn wait_timeout(
+        &self,
+        handle: Handle,
+        timeout: f64,
+    ) -> Result<Option<Vec<Value>>, Result<UiuaError, String>> {
+        Err(Err(
+            "Joining threads with a timeout is not supported in this environment".into(),
+        ))
+    }
+    fn channel(&self) -> Result<Handle, String> {
+        Err("Channels are not supported in this environment".into())
+    }
+    fn channel_send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        Err("Channels are not supported in this environment".into())
+    }
+    fn channel_receive(&self, handle: Handle) -> Result<Value, String> {
+        Err("Channels are not supported in this environment".into())
+    }
+    fn channel_try_receive(&self, handle: Handle) -> Result<Option<Value>, String> {
+        Err("Channels are not supported in this environment".into())
+    }
     fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
         Err("Running commands is not supported in this environment".into())
     }
@@ -543,6 +837,58 @@ impl SysOp {
                     .print_str_stdout("\n")
                     .map_err(|e| env.error(e))?;
             }
+            SysOp::Snapshot => {
+                let name = env
+                    .pop(1)?
+                    .as_string(env, "Snapshot name must be a string")?;
+                let Some(height) = env.scope.test_height else {
+                    return Err(env.error("Snapshots can only be taken in a test scope"));
+                };
+                if name.is_empty()
+                    || !(name.chars()).all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+                {
+                    return Err(env.error(format!(
+                        "Snapshot name must only contain letters, numbers, `_`, and `-`, \
+                        but it is {name:?}"
+                    )));
+                }
+                let script = match env.span() {
+                    Span::Code(CodeSpan {
+                        path: Some(path), ..
+                    }) => path.to_path_buf(),
+                    _ if !env.file_path().as_os_str().is_empty() => env.file_path().into(),
+                    _ => return Err(env.error("Snapshots can only be taken when running a file")),
+                };
+                let stem = script.file_stem().unwrap_or_default().to_string_lossy();
+                let path = script.with_file_name(format!("{stem}.{name}.snap"));
+                let path = path.to_string_lossy();
+                let mut actual = String::new();
+                for value in &env.stack[height.min(env.stack.len())..] {
+                    actual.push_str(&value.show());
+                    actual.push('\n');
+                }
+                // Snapshots are only written by `uiua test`
+                let testing = env.mode() == RunMode::Test;
+                let exists = env.backend.file_exists(&path);
+                if !testing && !exists {
+                    return Ok(());
+                }
+                if testing && (env.bless_snapshots || !exists) {
+                    (env.backend)
+                        .file_write_all(&path, actual.as_bytes())
+                        .map_err(|e| env.error(e))?;
+                } else {
+                    let expected = env.backend.file_read_all(&path).map_err(|e| env.error(e))?;
+                    let expected = String::from_utf8_lossy(&expected);
+                    if expected != actual {
+                        return Err(env.error(format!(
+                            "Snapshot `{name}` does not match {path}\n{}\n\
+                            Run with --bless to update it",
+                            line_diff(&expected, &actual)
+                        )));
+                    }
+                }
+            }
             SysOp::ScanLine => {
                 if let Some(line) = env.backend.scan_line_stdin().map_err(|e| env.error(e))? {
                     env.push(line);
@@ -564,7 +910,9 @@ impl SysOp {
                 let key = env
                     .pop(1)?
                     .as_string(env, "Augument to var must be a string")?;
-                let var = env.backend.var(&key).unwrap_or_default();
+                let var = (env.backend.var(&key))
+                    .map_err(|e| env.error(e))?
+                    .unwrap_or_default();
                 env.push(var);
             }
             SysOp::FOpen => {
@@ -767,6 +1115,52 @@ impl SysOp {
                     })
                     .map_err(|e| env.error(e))?;
             }
+            SysOp::FAppendAll => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let data = env.pop(2)?;
+                let bytes: Vec<u8> = match data {
+                    Value::Num(arr) => arr.data.iter().map(|&x| x as u8).collect(),
+                    Value::Byte(arr) => arr.data.into(),
+                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+                    Value::Func(_) => return Err(env.error("Cannot write function array to file")),
+                };
+                env.backend
+                    .file_append_all(&path, &bytes)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::FDelete => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                env.backend.delete_file(&path).map_err(|e| env.error(e))?;
+            }
+            SysOp::FMakeDir => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                env.backend.make_dir(&path).map_err(|e| env.error(e))?;
+            }
+            SysOp::FRemoveDir => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                env.backend.remove_dir(&path).map_err(|e| env.error(e))?;
+            }
+            SysOp::FRename => {
+                let from = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let to = env.pop(2)?.as_string(env, "Path must be a string")?;
+                env.backend.rename(&from, &to).map_err(|e| env.error(e))?;
+            }
+            SysOp::FCopy => {
+                let from = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let to = env.pop(2)?.as_string(env, "Path must be a string")?;
+                env.backend
+                    .copy_file(&from, &to)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::FMetadata => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let meta = env.backend.file_metadata(&path).map_err(|e| env.error(e))?;
+                env.push(Array::<f64>::from_iter([
+                    meta.size as f64,
+                    meta.modified.unwrap_or(f64::NAN),
+                    meta.is_dir as u8 as f64,
+                ]));
+            }
             SysOp::FExists => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
                 let exists = env.backend.file_exists(&path);
@@ -777,6 +1171,22 @@ impl SysOp {
                 let paths = env.backend.list_dir(&path).map_err(|e| env.error(e))?;
                 env.push(Array::<Arc<Function>>::from_iter(paths));
             }
+            SysOp::FGlob => {
+                let pattern = env.pop(1)?.as_string(env, "Pattern must be a string")?;
+                let paths = env.backend.glob(&pattern).map_err(|e| env.error(e))?;
+                env.push(Array::<Arc<Function>>::from_iter(paths));
+            }
+            SysOp::FWalk => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let entries = env.backend.walk_dir(&path).map_err(|e| env.error(e))?;
+                let is_dir: Array<u8> = entries.iter().map(|e| e.is_dir as u8).collect();
+                let depths: Array<f64> = entries.iter().map(|e| e.depth as f64).collect();
+                env.push(is_dir);
+                env.push(depths);
+                env.push(Array::<Arc<Function>>::from_iter(
+                    entries.into_iter().map(|e| e.path),
+                ));
+            }
             SysOp::FIsFile => {
                 let path = env.pop(1)?.as_string(env, "Path must be a string")?;
                 let is_file = env.backend.is_file(&path).map_err(|e| env.error(e))?;
@@ -784,6 +1194,9 @@ impl SysOp {
             }
             SysOp::Import => {
                 let path = env.pop(1)?.as_string(env, "Import path must be a string")?;
+                if let Some(lines) = env.built_imports.get(&path).cloned() {
+                    return env.import_built(path.as_ref(), &lines);
+                }
                 let input = String::from_utf8(
                     env.backend
                         .file_read_all(&path)
@@ -1022,6 +1435,126 @@ impl SysOp {
                     .tcp_set_write_timeout(handle, timeout)
                     .map_err(|e| env.error(e))?;
             }
+            SysOp::HttpRequestStr | SysOp::HttpRequestBytes => {
+                let method = env.pop(1)?.as_string(env, "Method must be a string")?;
+                let url = env.pop(2)?.as_string(env, "URL must be a string")?;
+                let headers = value_to_headers(env.pop(3)?, env)?;
+                let body = env
+                    .pop(4)?
+                    .into_bytes(env, "Body must be a string or bytes")?;
+                let response = env
+                    .backend
+                    .http_request(HttpRequest {
+                        method,
+                        url,
+                        headers,
+                        body,
+                    })
+                    .map_err(|e| env.error(e))?;
+                if let SysOp::HttpRequestStr = self {
+                    let body = String::from_utf8(response.body)
+                        .map_err(|e| env.error(format!("Response body is not UTF-8: {e}")))?;
+                    env.push(body);
+                } else {
+                    env.push(Array::from(response.body.as_slice()));
+                }
+                let pairs = response.headers.len();
+                let headers: CowSlice<Arc<Function>> = (response.headers.into_iter())
+                    .flat_map(|(name, value)| [name, value])
+                    .map(|s| Arc::new(Function::boxed(s)))
+                    .collect();
+                env.push(Array::new(tiny_vec![pairs, 2], headers));
+                env.push(response.status as f64);
+            }
+            SysOp::HttpReadRequest => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let request = env
+                    .backend
+                    .http_read_request(handle)
+                    .map_err(|e| env.error(e))?;
+                let (path, query) = match request.url.split_once('?') {
+                    Some((path, query)) => (path.to_string(), query.to_string()),
+                    None => (request.url, String::new()),
+                };
+                let pairs = request.headers.len();
+                let headers: CowSlice<Arc<Function>> = (request.headers.into_iter())
+                    .flat_map(|(name, value)| [name, value])
+                    .map(|s| Arc::new(Function::boxed(s)))
+                    .collect();
+                env.push(Array::from(request.body.as_slice()));
+                env.push(Array::new(tiny_vec![pairs, 2], headers));
+                env.push(query);
+                env.push(path);
+                env.push(request.method);
+            }
+            SysOp::HttpWriteResponse => {
+                let status = env.pop(1)?.as_nat(env, "Status must be a natural number")?;
+                let status = u16::try_from(status)
+                    .map_err(|_| env.error(format!("{status} is not a valid status code")))?;
+                let headers = value_to_headers(env.pop(2)?, env)?;
+                let body = env
+                    .pop(3)?
+                    .into_bytes(env, "Body must be a string or bytes")?;
+                let handle = env
+                    .pop(4)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .http_write_response(
+                        handle,
+                        HttpResponse {
+                            status,
+                            headers,
+                            body,
+                        },
+                    )
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::UdpBind => {
+                let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
+                let handle = env.backend.udp_bind(&addr).map_err(|e| env.error(e))?;
+                env.push(handle);
+            }
+            SysOp::UdpSendTo => {
+                let data = env.pop(1)?;
+                let bytes: Vec<u8> = match data {
+                    Value::Num(arr) => arr.data.iter().map(|&x| x as u8).collect(),
+                    Value::Byte(arr) => arr.data.into(),
+                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+                    Value::Func(_) => return Err(env.error("Cannot send function array")),
+                };
+                let addr = env.pop(2)?.as_string(env, "Address must be a string")?;
+                let handle = env
+                    .pop(3)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .udp_send_to(handle, &bytes, &addr)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::UdpReceiveFrom => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let (bytes, addr) = env
+                    .backend
+                    .udp_receive_from(handle)
+                    .map_err(|e| env.error(e))?;
+                env.push(addr);
+                env.push(Array::from(bytes.as_slice()));
+            }
+            SysOp::UdpAddr => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let addr = env.backend.udp_addr(handle).map_err(|e| env.error(e))?;
+                env.push(addr);
+            }
             SysOp::HttpsWrite => {
                 let http = env
                     .pop(1)?
@@ -1036,6 +1569,68 @@ impl SysOp {
                     .map_err(|e| env.error(e))?;
                 env.push(res);
             }
+            SysOp::WaitTimeout => {
+                let timeout = env.pop(1)?.as_num(env, "Timeout must be a number")?;
+                let handle = env
+                    .pop(2)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                match (env.backend)
+                    .wait_timeout(handle, timeout)
+                    .map_err(|e| e.unwrap_or_else(|e| env.error(e)))?
+                {
+                    Some(thread_stack) => {
+                        // Box the results so that the signature is fixed
+                        let results: Array<Arc<Function>> = (thread_stack.into_iter().rev())
+                            .map(|value| Arc::new(Function::boxed(value)))
+                            .collect();
+                        env.push(results);
+                        env.push(true);
+                    }
+                    None => {
+                        env.push(handle);
+                        env.push(false);
+                    }
+                }
+            }
+            SysOp::ChannelNew => {
+                let handle = env.backend.channel().map_err(|e| env.error(e))?;
+                env.push(handle);
+            }
+            SysOp::ChannelSend => {
+                let value = env.pop(1)?;
+                let handle = env
+                    .pop(2)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .channel_send(handle, value)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::ChannelReceive => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let value = env
+                    .backend
+                    .channel_receive(handle)
+                    .map_err(|e| env.error(e))?;
+                env.push(value);
+            }
+            SysOp::ChannelTryReceive => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let received = env
+                    .backend
+                    .channel_try_receive(handle)
+                    .map_err(|e| env.error(e))?;
+                let success = received.is_some();
+                env.push(received.unwrap_or_else(|| Array::<f64>::default().into()));
+                env.push(success);
+            }
             SysOp::Close => {
                 let handle = env
                     .pop(1)?
@@ -1074,6 +1669,283 @@ impl SysOp {
     }
 }
 
+fn walk_with<B: SysBackend + ?Sized>(
+    backend: &B,
+    dir: &str,
+    depth: usize,
+    entries: &mut Vec<WalkEntry>,
+) -> Result<(), String> {
+    let mut children = backend.list_dir(dir)?;
+    children.sort();
+    for path in children {
+        let is_dir = !backend.is_file(&path)?;
+        entries.push(WalkEntry {
+            path: path.clone(),
+            depth,
+            is_dir,
+        });
+        if is_dir {
+            walk_with(backend, &path, depth + 1, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the directory that a glob pattern searches in
+///
+/// This is the longest prefix of the pattern's components that has no wildcards.
+pub(crate) fn glob_base(pattern: &str) -> String {
+    let literal: Vec<&str> = (pattern.split(['/', '\\']))
+        .take_while(|part| !part.contains(['*', '?', '[']))
+        .collect();
+    if literal.len() == pattern.split(['/', '\\']).count() {
+        // The whole pattern is literal
+        return pattern.into();
+    }
+    match literal.as_slice() {
+        [] => ".".into(),
+        [""] => "/".into(),
+        parts => parts.join("/"),
+    }
+}
+
+fn glob_with<B: SysBackend + ?Sized>(backend: &B, pattern: &str) -> Result<Vec<String>, String> {
+    let base = glob_base(pattern);
+    if base == pattern {
+        return Ok(if backend.file_exists(pattern) {
+            vec![pattern.into()]
+        } else {
+            Vec::new()
+        });
+    }
+    let literal_count = (pattern.split(['/', '\\']))
+        .take_while(|part| !part.contains(['*', '?', '[']))
+        .count();
+    let rest: Vec<&str> = (pattern.split(['/', '\\']))
+        .skip(literal_count)
+        .filter(|part| !part.is_empty())
+        .collect();
+    let mut paths = Vec::new();
+    let mut push_match = |path: &str| {
+        let relative = path[base.len().min(path.len())..]
+            .trim_start_matches(['/', '\\'])
+            .replace('\\', "/");
+        let parts: Vec<&str> = relative.split('/').collect();
+        if glob_match_parts(&rest, &parts) {
+            paths.push(if base == "." {
+                relative
+            } else {
+                format!("{}/{relative}", base.trim_end_matches('/'))
+            });
+        }
+    };
+    if rest.contains(&"**") {
+        for entry in backend.walk_dir(&base)? {
+            push_match(&entry.path);
+        }
+    } else {
+        // Without `**`, matches can be no deeper than the pattern
+        let mut entries = Vec::new();
+        glob_walk(backend, &base, &rest, &mut entries)?;
+        for path in entries {
+            push_match(&path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Walk a directory, only entering the subdirectories that match each pattern component
+fn glob_walk<B: SysBackend + ?Sized>(
+    backend: &B,
+    dir: &str,
+    pattern: &[&str],
+    entries: &mut Vec<String>,
+) -> Result<(), String> {
+    let Some((first, rest)) = pattern.split_first() else {
+        return Ok(());
+    };
+    let first: Vec<char> = first.chars().collect();
+    let mut children = backend.list_dir(dir)?;
+    children.sort();
+    for path in children {
+        let name: Vec<char> = (path.rsplit(['/', '\\']).next().unwrap_or(&path))
+            .chars()
+            .collect();
+        if !glob_match_name(&first, &name) {
+            continue;
+        }
+        if rest.is_empty() {
+            entries.push(path);
+        } else if !backend.is_file(&path)? {
+            glob_walk(backend, &path, rest, entries)?;
+        }
+    }
+    Ok(())
+}
+
+fn glob_match_parts(pattern: &[&str], parts: &[&str]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Like stars in names, `**` only ever backtracks to the last one
+    let mut star = None;
+    while n < parts.len() {
+        if pattern.get(p) == Some(&"**") {
+            p += 1;
+            star = Some((p, n));
+        } else if pattern.get(p).is_some_and(|pat| {
+            let pat: Vec<char> = pat.chars().collect();
+            let part: Vec<char> = parts[n].chars().collect();
+            glob_match_name(&pat, &part)
+        }) {
+            p += 1;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&pat| pat == "**")
+}
+
+fn glob_match_name(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // The pattern position after the last star and the name position it is matching from
+    let mut star = None;
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            p += 1;
+            star = Some((p, n));
+        } else if let Some(len) = glob_match_char(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last star match one more character
+            p = star_p;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match a single character against the start of a pattern,
+/// returning the length of the pattern token that matched
+fn glob_match_char(pattern: &[char], c: char) -> Option<usize> {
+    match pattern.first()? {
+        '*' => None,
+        '?' => Some(1),
+        '[' if pattern.contains(&']') => {
+            let end = pattern.iter().position(|&c| c == ']').unwrap();
+            let (negate, class) = match pattern[1..end].split_first() {
+                Some(('!' | '^', class)) => (true, class),
+                _ => (false, &pattern[1..end]),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            (matched != negate).then_some(end + 1)
+        }
+        &p => (p == c).then_some(1),
+    }
+}
+
+#[test]
+fn glob_matching() {
+    let matches = |pattern: &str, path: &str| {
+        let pattern: Vec<&str> = pattern.split('/').collect();
+        let path: Vec<&str> = path.split('/').collect();
+        glob_match_parts(&pattern, &path)
+    };
+    assert!(matches("*.csv", "a.csv"));
+    assert!(!matches("*.csv", "a/b.csv"));
+    assert!(matches("**/*.csv", "a.csv"));
+    assert!(matches("**/*.csv", "a/b/c.csv"));
+    assert!(matches("a/**/c?.[ct]sv", "a/b/c1.tsv"));
+    assert!(!matches("[!a-c]*", "banana"));
+    assert!(matches("*a*[xy]", "banana_x"));
+    assert!(!matches("*a*", "bnn"));
+    assert!(matches("*", ""));
+    // Many stars must not backtrack exponentially
+    let name = "a".repeat(100);
+    assert!(!matches(&format!("{}b", "a*".repeat(20)), &name));
+    assert!(matches(&"a*".repeat(20), &name));
+    let path = vec!["a"; 30].join("/");
+    assert!(!matches(&format!("{}b", "**/".repeat(10)), &path));
+    assert_eq!(glob_base("data/**/*.csv"), "data");
+    assert_eq!(glob_base("*.csv"), ".");
+    assert_eq!(glob_base("/tmp/*"), "/tmp");
+    assert_eq!(glob_base("/*"), "/");
+}
+
+#[test]
+fn glob_depth() {
+    use crate::sys_virtual::VirtualSys;
+    // Records every directory that is listed
+    struct Listing(VirtualSys, Mutex<Vec<String>>);
+    impl SysBackend for Listing {
+        fn any(&self) -> &dyn Any {
+            self
+        }
+        fn file_exists(&self, path: &str) -> bool {
+            self.0.file_exists(path)
+        }
+        fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+            self.1.lock().push(path.into());
+            self.0.list_dir(path)
+        }
+        fn is_file(&self, path: &str) -> Result<bool, String> {
+            self.0.is_file(path)
+        }
+    }
+    let sys = VirtualSys::new()
+        .with_file("a.x", "")
+        .with_file("sub/b.x", "")
+        .with_file("sub/deep/c.x", "")
+        .with_file("other/d.x", "");
+    let sys = Listing(sys, Mutex::new(Vec::new()));
+    assert_eq!(sys.glob("*.x").unwrap(), ["a.x"]);
+    assert_eq!(*sys.1.lock(), ["."]);
+    sys.1.lock().clear();
+    assert_eq!(sys.glob("s*/*.x").unwrap(), ["sub/b.x"]);
+    assert_eq!(*sys.1.lock(), [".", "./sub"]);
+    sys.1.lock().clear();
+    assert_eq!(sys.glob("**/*.x").unwrap().len(), 4);
+    assert_eq!(sys.1.lock().len(), 4);
+}
+
+fn value_to_headers(value: Value, env: &Uiua) -> UiuaResult<Vec<(String, String)>> {
+    let requirement = "Headers must be a list of name-value pairs";
+    let pairs: Vec<Value> = match value.rank() {
+        1 if value.row_count() == 0 => Vec::new(),
+        1 => vec![value],
+        2 => value.into_rows().collect(),
+        _ => return Err(env.error(requirement)),
+    };
+    let mut headers = Vec::new();
+    for pair in pairs {
+        if pair.row_count() != 2 {
+            return Err(env.error(requirement));
+        }
+        let mut pair = pair.into_rows();
+        let name = pair.next().unwrap().as_string(env, requirement)?;
+        let value = pair.next().unwrap().as_string(env, requirement)?;
+        headers.push((name, value));
+    }
+    Ok(headers)
+}
+
 fn value_to_command(value: &Value, env: &Uiua) -> UiuaResult<(String, Vec<String>)> {
     let mut strings = Vec::new();
     match value {
@@ -4,14 +4,19 @@ use std::{
     fs::{self, File},
     io::{stderr, stdin, stdout, BufRead, Read, Write},
     net::*,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
     process::Command,
     sync::atomic::{self, AtomicU64},
-    thread::{sleep, spawn, JoinHandle},
-    time::Duration,
+    thread::{sleep, spawn},
+    time::{Duration, UNIX_EPOCH},
 };
 
-use crate::{value::Value, Handle, SysBackend, Uiua, UiuaError, UiuaResult};
+use crate::{
+    value::Value, FileMetadata, Handle, SysBackend, Uiua, UiuaError, UiuaResult, WalkEntry,
+};
 use bufreaderwriter::seq::BufReaderWriterSeq;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError};
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
 
@@ -19,6 +24,11 @@ use once_cell::sync::Lazy;
 pub struct NativeSys;
 
 type Buffered<T> = BufReaderWriterSeq<T>;
+/// A channel's sender is dropped when it is closed, but its receiver is kept
+/// so that values that were already sent can still be received
+type Channel = (Option<Sender<Value>>, Receiver<Value>);
+type ThreadResult = Result<Vec<Value>, Result<UiuaError, String>>;
+type Job = Box<dyn FnOnce() + Send>;
 
 struct GlobalNativeSys {
     next_handle: AtomicU64,
@@ -26,7 +36,10 @@ struct GlobalNativeSys {
     tcp_listeners: DashMap<Handle, TcpListener>,
     tcp_sockets: DashMap<Handle, Buffered<TcpStream>>,
     hostnames: DashMap<Handle, String>,
-    threads: DashMap<Handle, JoinHandle<UiuaResult<Vec<Value>>>>,
+    udp_sockets: DashMap<Handle, UdpSocket>,
+    threads: DashMap<Handle, Receiver<ThreadResult>>,
+    thread_pool: parking_lot::Mutex<Option<ThreadPool>>,
+    channels: DashMap<Handle, Channel>,
     #[cfg(feature = "audio")]
     audio_stream_time: parking_lot::Mutex<Option<f64>>,
     #[cfg(feature = "audio")]
@@ -48,7 +61,10 @@ impl Default for GlobalNativeSys {
             tcp_listeners: DashMap::new(),
             tcp_sockets: DashMap::new(),
             hostnames: DashMap::new(),
+            udp_sockets: DashMap::new(),
             threads: DashMap::new(),
+            thread_pool: parking_lot::Mutex::new(None),
+            channels: DashMap::new(),
             #[cfg(feature = "audio")]
             audio_stream_time: parking_lot::Mutex::new(None),
             #[cfg(feature = "audio")]
@@ -65,6 +81,8 @@ impl GlobalNativeSys {
             if !self.files.contains_key(&handle)
                 && !self.tcp_listeners.contains_key(&handle)
                 && !self.tcp_sockets.contains_key(&handle)
+                && !self.udp_sockets.contains_key(&handle)
+                && !self.channels.contains_key(&handle)
             {
                 return handle;
             }
@@ -86,6 +104,41 @@ impl GlobalNativeSys {
 
 static NATIVE_SYS: Lazy<GlobalNativeSys> = Lazy::new(Default::default);
 
+/// A fixed number of worker threads that run queued jobs
+struct ThreadPool {
+    jobs: Sender<Job>,
+}
+
+impl ThreadPool {
+    fn new(size: usize) -> Self {
+        // The queue holds as many jobs as there are workers,
+        // so spawning blocks when the pool is saturated
+        let (jobs, recv) = crossbeam_channel::bounded::<Job>(size);
+        for _ in 0..size {
+            let recv = recv.clone();
+            spawn(move || {
+                for job in recv {
+                    job();
+                }
+            });
+        }
+        ThreadPool { jobs }
+    }
+}
+
+impl NativeSys {
+    /// Run [`spawn`](crate::Primitive::Spawn)ed functions on a pool of `size` worker threads
+    ///
+    /// While all workers are busy and `size` more functions are queued, spawning blocks.
+    /// Waiting on a thread from inside a pooled thread can deadlock if the pool is saturated.
+    ///
+    /// Pass `None` to spawn a new OS thread for each function, which is the default.
+    /// The pool is shared by all runtimes using [`NativeSys`].
+    pub fn set_thread_pool_size(size: Option<usize>) {
+        *NATIVE_SYS.thread_pool.lock() = size.map(|size| ThreadPool::new(size.max(1)));
+    }
+}
+
 #[cfg(feature = "audio")]
 pub fn set_audio_stream_time(time: f64) {
     *NATIVE_SYS.audio_stream_time.lock() = Some(time);
@@ -130,8 +183,8 @@ impl SysBackend for NativeSys {
         let (w, h) = term_size::dimensions().ok_or("Failed to get terminal size")?;
         Ok((w, h.saturating_sub(1)))
     }
-    fn var(&self, name: &str) -> Option<String> {
-        env::var(name).ok()
+    fn var(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(env::var(name).ok())
     }
     fn file_exists(&self, path: &str) -> bool {
         fs::metadata(path).is_ok()
@@ -149,6 +202,31 @@ impl SysBackend for NativeSys {
         }
         Ok(paths)
     }
+    fn walk_dir(&self, path: &str) -> Result<Vec<WalkEntry>, String> {
+        // Symlinked directories are not followed, so cycles are impossible
+        fn walk(dir: &Path, depth: usize, entries: &mut Vec<WalkEntry>) -> Result<(), String> {
+            let mut children = (fs::read_dir(dir).map_err(|e| e.to_string())?)
+                .map(|entry| entry.map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            children.sort_by_key(|entry| entry.path());
+            for entry in children {
+                let path = entry.path();
+                let is_dir = entry.file_type().map_err(|e| e.to_string())?.is_dir();
+                entries.push(WalkEntry {
+                    path: path.to_string_lossy().into(),
+                    depth,
+                    is_dir,
+                });
+                if is_dir {
+                    walk(&path, depth + 1, entries)?;
+                }
+            }
+            Ok(())
+        }
+        let mut entries = Vec::new();
+        walk(Path::new(path), 1, &mut entries)?;
+        Ok(entries)
+    }
     fn open_file(&self, path: &str) -> Result<Handle, String> {
         let handle = NATIVE_SYS.new_handle();
         let file = File::open(path).map_err(|e| e.to_string())?;
@@ -161,6 +239,40 @@ impl SysBackend for NativeSys {
         NATIVE_SYS.files.insert(handle, Buffered::new_writer(file));
         Ok(handle)
     }
+    fn file_append_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        file.write_all(contents).map_err(|e| e.to_string())
+    }
+    fn delete_file(&self, path: &str) -> Result<(), String> {
+        fs::remove_file(path).map_err(|e| e.to_string())
+    }
+    fn make_dir(&self, path: &str) -> Result<(), String> {
+        fs::create_dir_all(path).map_err(|e| e.to_string())
+    }
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        fs::remove_dir_all(path).map_err(|e| e.to_string())
+    }
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        fs::rename(from, to).map_err(|e| e.to_string())
+    }
+    fn copy_file(&self, from: &str, to: &str) -> Result<(), String> {
+        fs::copy(from, to).map(drop).map_err(|e| e.to_string())
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        let meta = fs::metadata(path).map_err(|e| e.to_string())?;
+        let modified = (meta.modified().ok())
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_secs_f64());
+        Ok(FileMetadata {
+            size: meta.len(),
+            modified,
+            is_dir: meta.is_dir(),
+        })
+    }
     fn read(&self, handle: Handle, len: usize) -> Result<Vec<u8>, String> {
         Ok(match NATIVE_SYS.get_stream(handle)? {
             SysStream::File(mut file) => {
@@ -317,12 +429,14 @@ impl SysBackend for NativeSys {
         Ok(handle)
     }
     fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        // Accept on a clone so the map isn't locked while blocking
         let listener = NATIVE_SYS
             .tcp_listeners
-            .get_mut(&handle)
-            .ok_or_else(|| "Invalid tcp listener handle".to_string())?;
+            .get(&handle)
+            .ok_or_else(|| "Invalid tcp listener handle".to_string())?
+            .try_clone()
+            .map_err(|e| e.to_string())?;
         let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
-        drop(listener);
         let handle = NATIVE_SYS.new_handle();
         NATIVE_SYS
             .tcp_sockets
@@ -356,6 +470,9 @@ impl SysBackend for NativeSys {
             .to_string())
     }
     fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        if let Some(socket) = NATIVE_SYS.udp_sockets.get(&handle) {
+            return (socket.set_nonblocking(non_blocking)).map_err(|e| e.to_string());
+        }
         let socket = NATIVE_SYS
             .tcp_sockets
             .get(&handle)
@@ -371,6 +488,9 @@ impl SysBackend for NativeSys {
         handle: Handle,
         timeout: Option<Duration>,
     ) -> Result<(), String> {
+        if let Some(socket) = NATIVE_SYS.udp_sockets.get(&handle) {
+            return (socket.set_read_timeout(timeout)).map_err(|e| e.to_string());
+        }
         let socket = NATIVE_SYS
             .tcp_sockets
             .get(&handle)
@@ -386,6 +506,9 @@ impl SysBackend for NativeSys {
         handle: Handle,
         timeout: Option<Duration>,
     ) -> Result<(), String> {
+        if let Some(socket) = NATIVE_SYS.udp_sockets.get(&handle) {
+            return (socket.set_write_timeout(timeout)).map_err(|e| e.to_string());
+        }
         let socket = NATIVE_SYS
             .tcp_sockets
             .get(&handle)
@@ -396,11 +519,50 @@ impl SysBackend for NativeSys {
             .map_err(|e| e.to_string())?;
         Ok(())
     }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        let handle = NATIVE_SYS.new_handle();
+        let socket = UdpSocket::bind(addr).map_err(|e| e.to_string())?;
+        NATIVE_SYS.udp_sockets.insert(handle, socket);
+        Ok(handle)
+    }
+    fn udp_send_to(&self, handle: Handle, data: &[u8], addr: &str) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        socket.send_to(data, addr).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    fn udp_receive_from(&self, handle: Handle) -> Result<(Vec<u8>, String), String> {
+        // Clone the socket so that a blocking receive does not lock the handle table
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?
+            .try_clone()
+            .map_err(|e| e.to_string())?;
+        // The largest possible UDP payload
+        let mut buf = vec![0; 65535];
+        let (len, addr) = socket.recv_from(&mut buf).map_err(|e| e.to_string())?;
+        buf.truncate(len);
+        Ok((buf, addr.to_string()))
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        Ok(socket.local_addr().map_err(|e| e.to_string())?.to_string())
+    }
     fn close(&self, handle: Handle) -> Result<(), String> {
+        // Only connected sockets have hostnames, not accepted ones
+        NATIVE_SYS.hostnames.remove(&handle);
         if NATIVE_SYS.files.remove(&handle).is_some()
             || NATIVE_SYS.tcp_listeners.remove(&handle).is_some()
-            || (NATIVE_SYS.tcp_sockets.remove(&handle).is_some()
-                && NATIVE_SYS.hostnames.remove(&handle).is_some())
+            || NATIVE_SYS.udp_sockets.remove(&handle).is_some()
+            || NATIVE_SYS.tcp_sockets.remove(&handle).is_some()
+            || (NATIVE_SYS.channels.get_mut(&handle))
+                .is_some_and(|mut channel| channel.0.take().is_some())
         {
             Ok(())
         } else {
@@ -416,12 +578,30 @@ impl SysBackend for NativeSys {
         mut env: Uiua,
         f: Box<dyn FnOnce(&mut Uiua) -> UiuaResult + Send>,
     ) -> Result<Handle, String> {
-        let thread = spawn(move || {
-            f(&mut env)?;
-            Ok(env.take_stack())
+        let (send, recv) = crossbeam_channel::bounded(1);
+        let job: Job = Box::new(move || {
+            let res = match catch_unwind(AssertUnwindSafe(|| f(&mut env))) {
+                Ok(Ok(())) => Ok(env.take_stack()),
+                Ok(Err(e)) => Err(Ok(e)),
+                Err(e) => Err(Err(format!("Thread panicked: {:?}", e))),
+            };
+            _ = send.send(res);
         });
+        // Clone the sender so the pool isn't locked while the queue is full
+        let pool_jobs = NATIVE_SYS
+            .thread_pool
+            .lock()
+            .as_ref()
+            .map(|pool| pool.jobs.clone());
+        if let Some(jobs) = pool_jobs {
+            jobs.send(job)
+                .map_err(|_| "Thread pool has shut down".to_string())?;
+        } else {
+            spawn(job);
+        }
+        // Only register the thread once its job is running or queued
         let handle = NATIVE_SYS.new_handle();
-        NATIVE_SYS.threads.insert(handle, thread);
+        NATIVE_SYS.threads.insert(handle, recv);
         Ok(handle)
     }
     fn wait(&self, handle: Handle) -> Result<Vec<Value>, Result<UiuaError, String>> {
@@ -429,10 +609,72 @@ impl SysBackend for NativeSys {
             .threads
             .remove(&handle)
             .ok_or_else(|| Err("Invalid thread handle".to_string()))?;
-        match thread.join() {
-            Ok(Ok(stack)) => Ok(stack),
-            Ok(Err(e)) => Err(Ok(e)),
-            Err(e) => Err(Err(format!("Thread panicked: {:?}", e))),
+        thread
+            .recv()
+            .unwrap_or_else(|_| Err(Err("Thread was lost".into())))
+    }
+    fn wait_timeout(
+        &self,
+        handle: Handle,
+        timeout: f64,
+    ) -> Result<Option<Vec<Value>>, Result<UiuaError, String>> {
+        // Clone the receiver so the map isn't locked while blocking
+        let thread = NATIVE_SYS
+            .threads
+            .get(&handle)
+            .ok_or_else(|| Err("Invalid thread handle".to_string()))?
+            .clone();
+        let res = match Duration::try_from_secs_f64(timeout.max(0.0)) {
+            Ok(timeout) => match thread.recv_timeout(timeout) {
+                Ok(res) => res,
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => Err(Err("Thread was lost".into())),
+            },
+            // The timeout is too large to represent, so wait forever
+            Err(_) => (thread.recv()).unwrap_or_else(|_| Err(Err("Thread was lost".into()))),
+        };
+        NATIVE_SYS.threads.remove(&handle);
+        res.map(Some)
+    }
+    fn channel(&self) -> Result<Handle, String> {
+        let handle = NATIVE_SYS.new_handle();
+        NATIVE_SYS.channels.insert(handle, {
+            let (send, recv) = crossbeam_channel::unbounded();
+            (Some(send), recv)
+        });
+        Ok(handle)
+    }
+    fn channel_send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        let sender = NATIVE_SYS
+            .channels
+            .get(&handle)
+            .ok_or_else(|| "Invalid channel handle".to_string())?
+            .0
+            .clone()
+            .ok_or_else(|| "Channel is closed".to_string())?;
+        sender
+            .send(value)
+            .map_err(|_| "Channel is closed".to_string())
+    }
+    fn channel_receive(&self, handle: Handle) -> Result<Value, String> {
+        // Clone the receiver so the map isn't locked while blocking
+        let receiver = NATIVE_SYS
+            .channels
+            .get(&handle)
+            .ok_or_else(|| "Invalid channel handle".to_string())?
+            .1
+            .clone();
+        receiver.recv().map_err(|_| "Channel is closed".to_string())
+    }
+    fn channel_try_receive(&self, handle: Handle) -> Result<Option<Value>, String> {
+        let channel = NATIVE_SYS
+            .channels
+            .get(&handle)
+            .ok_or_else(|| "Invalid channel handle".to_string())?;
+        match channel.1.try_recv() {
+            Ok(value) => Ok(Some(value)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err("Channel is closed".into()),
         }
     }
     fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
@@ -463,6 +705,10 @@ impl SysBackend for NativeSys {
         env::set_current_dir(path).map_err(|e| e.to_string())
     }
     #[cfg(feature = "https")]
+    fn http_request(&self, request: crate::HttpRequest) -> Result<crate::HttpResponse, String> {
+        crate::http::request(request)
+    }
+    #[cfg(feature = "https")]
     fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
         let host = NATIVE_SYS
             .hostnames
@@ -470,23 +716,6 @@ impl SysBackend for NativeSys {
             .ok_or_else(|| "Invalid tcp socket handle".to_string())?;
         let request = check_http(request.to_string(), &host)?;
 
-        // https://github.com/rustls/rustls/blob/c9cfe3499681361372351a57a00ccd793837ae9c/examples/src/bin/simpleclient.rs
-        static CLIENT_CONFIG: Lazy<std::sync::Arc<rustls::ClientConfig>> = Lazy::new(|| {
-            let mut store = rustls::RootCertStore::empty();
-            store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
-                    ta.subject,
-                    ta.spki,
-                    ta.name_constraints,
-                )
-            }));
-            rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_root_certificates(store)
-                .with_no_client_auth()
-                .into()
-        });
-
         let mut socket = NATIVE_SYS
             .tcp_sockets
             .get_mut(&handle)
@@ -495,8 +724,9 @@ impl SysBackend for NativeSys {
         let server_name = rustls::ServerName::try_from(host.as_str()).map_err(|e| e.to_string())?;
         let tcp_stream = socket.get_mut();
 
-        let mut conn = rustls::ClientConnection::new(CLIENT_CONFIG.clone(), server_name)
-            .map_err(|e| e.to_string())?;
+        let mut conn =
+            rustls::ClientConnection::new(crate::http::CLIENT_CONFIG.clone(), server_name)
+                .map_err(|e| e.to_string())?;
         let mut tls = rustls::Stream::new(&mut conn, tcp_stream);
 
         tls.write_all(request.as_bytes())
@@ -0,0 +1,580 @@
+use std::{
+    any::Any,
+    env,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use image::DynamicImage;
+
+use crate::{
+    sys::glob_base, value::Value, AudioStreamFn, FileMetadata, Handle, HttpRequest, HttpResponse,
+    NativeSys, SysBackend, Uiua, UiuaError, UiuaResult, WalkEntry,
+};
+
+/// A [`SysBackend`] that restricts another backend to a set of [`Permissions`]
+///
+/// Operations that do not require a permission, like printing or using an
+/// already-open handle, are passed through to the inner backend.
+pub struct SandboxSys<B = NativeSys> {
+    inner: B,
+    permissions: Permissions,
+}
+
+/// The capabilities granted to a [`SandboxSys`]
+///
+/// By default, nothing is allowed.
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    read: PathAccess,
+    write: PathAccess,
+    net: bool,
+    run: bool,
+    /// `None` allows every variable
+    env: Option<Vec<String>>,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Permissions {
+            read: PathAccess::default(),
+            write: PathAccess::default(),
+            net: false,
+            run: false,
+            env: Some(Vec::new()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PathAccess {
+    All,
+    Prefixes(Vec<PathBuf>),
+}
+
+impl Default for PathAccess {
+    fn default() -> Self {
+        PathAccess::Prefixes(Vec::new())
+    }
+}
+
+impl PathAccess {
+    fn add(&mut self, prefix: impl AsRef<Path>) {
+        if let PathAccess::Prefixes(prefixes) = self {
+            prefixes.push(resolve(prefix.as_ref()));
+        }
+    }
+    /// Get the path to use in place of the given one, if it is allowed
+    fn check(&self, path: &str) -> Option<String> {
+        match self {
+            PathAccess::All => Some(path.into()),
+            PathAccess::Prefixes(prefixes) => {
+                let resolved = resolve(Path::new(path));
+                (prefixes.iter().any(|prefix| resolved.starts_with(prefix)))
+                    .then(|| resolved.to_string_lossy().into_owned())
+            }
+        }
+    }
+}
+
+impl Permissions {
+    /// Permissions that allow nothing
+    pub fn none() -> Self {
+        Self::default()
+    }
+    /// Permissions that allow everything
+    pub fn all() -> Self {
+        Permissions {
+            read: PathAccess::All,
+            write: PathAccess::All,
+            net: true,
+            run: true,
+            env: None,
+        }
+    }
+    /// Allow reading files and directories under a path
+    pub fn allow_read(mut self, prefix: impl AsRef<Path>) -> Self {
+        self.read.add(prefix);
+        self
+    }
+    /// Allow reading any file or directory
+    pub fn allow_read_all(mut self) -> Self {
+        self.read = PathAccess::All;
+        self
+    }
+    /// Allow creating and writing files under a path
+    pub fn allow_write(mut self, prefix: impl AsRef<Path>) -> Self {
+        self.write.add(prefix);
+        self
+    }
+    /// Allow creating and writing any file
+    pub fn allow_write_all(mut self) -> Self {
+        self.write = PathAccess::All;
+        self
+    }
+    /// Set whether network access is allowed
+    pub fn allow_net(mut self, allow: bool) -> Self {
+        self.net = allow;
+        self
+    }
+    /// Set whether running subprocesses is allowed
+    pub fn allow_run(mut self, allow: bool) -> Self {
+        self.run = allow;
+        self
+    }
+    /// Allow reading an environment variable
+    pub fn allow_env(mut self, name: impl Into<String>) -> Self {
+        if let Some(names) = &mut self.env {
+            names.push(name.into());
+        }
+        self
+    }
+    /// Allow reading any environment variable
+    pub fn allow_env_all(mut self) -> Self {
+        self.env = None;
+        self
+    }
+}
+
+impl Default for SandboxSys {
+    fn default() -> Self {
+        SandboxSys::new(NativeSys, Permissions::none())
+    }
+}
+
+impl<B: SysBackend> SandboxSys<B> {
+    /// Restrict a backend to some permissions
+    pub fn new(inner: B, permissions: Permissions) -> Self {
+        SandboxSys { inner, permissions }
+    }
+    /// Get the wrapped backend
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+    /// Get the granted permissions
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+    /// Check that a path may be read, and get the resolved path to pass on
+    ///
+    /// The resolved path must be used so that the path that was checked
+    /// is the one that is accessed.
+    fn check_read(&self, path: &str) -> Result<String, String> {
+        (self.permissions.read.check(path))
+            .ok_or_else(|| denied(&format!("Reading `{path}`"), "read"))
+    }
+    /// Check that a path may be written, and get the resolved path to pass on
+    fn check_write(&self, path: &str) -> Result<String, String> {
+        (self.permissions.write.check(path))
+            .ok_or_else(|| denied(&format!("Writing `{path}`"), "write"))
+    }
+    fn check_net(&self, action: &str) -> Result<(), String> {
+        if self.permissions.net {
+            Ok(())
+        } else {
+            Err(denied(action, "net"))
+        }
+    }
+    fn check_run(&self, action: &str) -> Result<(), String> {
+        if self.permissions.run {
+            Ok(())
+        } else {
+            Err(denied(action, "run"))
+        }
+    }
+}
+
+/// Turn a path that the inner backend returned for a resolved path
+/// back into one relative to the path that was asked for
+fn unresolve(path: &str, resolved: &str, child: String) -> String {
+    match Path::new(&child).strip_prefix(resolved) {
+        Ok(rest) => Path::new(path).join(rest).to_string_lossy().into_owned(),
+        Err(_) => child,
+    }
+}
+
+fn denied(action: &str, permission: &str) -> String {
+    format!(
+        "Permission denied: {action} requires the `{permission}` permission \
+        (--allow-{permission})"
+    )
+}
+
+/// Make a path absolute and resolve symlinks and `..`
+///
+/// Each component is resolved in turn, so `..` is applied to where a symlink
+/// actually points rather than to the symlink's own location.
+/// Components that do not exist yet are appended as they are.
+fn resolve(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut resolved = PathBuf::new();
+    let mut exists = true;
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => {
+                resolved.push(component);
+                if exists {
+                    match resolved.canonicalize() {
+                        Ok(canonical) => resolved = canonical,
+                        Err(_) => exists = false,
+                    }
+                }
+            }
+        }
+    }
+    resolved
+}
+
+impl<B: SysBackend> SysBackend for SandboxSys<B> {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn save_error_color(&self, error: &UiuaError) {
+        self.inner.save_error_color(error)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.inner.print_str_trace(s)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        self.inner.scan_line_stdin()
+    }
+    fn var(&self, name: &str) -> Result<Option<String>, String> {
+        match &self.permissions.env {
+            Some(names) if !names.iter().any(|n| n == name) => Err(denied(
+                &format!("Reading environment variable `{name}`"),
+                "env",
+            )),
+            _ => self.inner.var(name),
+        }
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.inner.term_size()
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        (self.check_read(path)).is_ok_and(|resolved| self.inner.file_exists(&resolved))
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        let resolved = self.check_read(path)?;
+        let children = self.inner.list_dir(&resolved)?;
+        Ok(children
+            .into_iter()
+            .map(|child| unresolve(path, &resolved, child))
+            .collect())
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        let path = self.check_read(path)?;
+        self.inner.is_file(&path)
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        self.inner.read(handle, count)
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        self.inner.read_until(handle, delim)
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.inner.write(handle, contents)
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        let path = self.check_write(path)?;
+        self.inner.create_file(&path)
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        let path = self.check_read(path)?;
+        self.inner.open_file(&path)
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        let path = self.check_read(path)?;
+        self.inner.file_read_all(&path)
+    }
+    fn file_write_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let path = self.check_write(path)?;
+        self.inner.file_write_all(&path, contents)
+    }
+    fn file_append_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let path = self.check_write(path)?;
+        self.inner.file_append_all(&path, contents)
+    }
+    fn delete_file(&self, path: &str) -> Result<(), String> {
+        let path = self.check_write(path)?;
+        self.inner.delete_file(&path)
+    }
+    fn make_dir(&self, path: &str) -> Result<(), String> {
+        let path = self.check_write(path)?;
+        self.inner.make_dir(&path)
+    }
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        let path = self.check_write(path)?;
+        self.inner.remove_dir(&path)
+    }
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        let from = self.check_write(from)?;
+        let to = self.check_write(to)?;
+        self.inner.rename(&from, &to)
+    }
+    fn copy_file(&self, from: &str, to: &str) -> Result<(), String> {
+        let from = self.check_read(from)?;
+        let to = self.check_write(to)?;
+        self.inner.copy_file(&from, &to)
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        let path = self.check_read(path)?;
+        self.inner.file_metadata(&path)
+    }
+    fn walk_dir(&self, path: &str) -> Result<Vec<WalkEntry>, String> {
+        let resolved = self.check_read(path)?;
+        let entries = self.inner.walk_dir(&resolved)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| WalkEntry {
+                path: unresolve(path, &resolved, entry.path),
+                ..entry
+            })
+            .collect())
+    }
+    fn glob(&self, pattern: &str) -> Result<Vec<String>, String> {
+        let base = glob_base(pattern);
+        let resolved = self.check_read(&base)?;
+        if base == pattern {
+            return self.inner.glob(&resolved);
+        }
+        let literal_count = (pattern.split(['/', '\\']))
+            .take_while(|part| !part.contains(['*', '?', '[']))
+            .count();
+        let rest: Vec<&str> = pattern.split(['/', '\\']).skip(literal_count).collect();
+        let resolved_pattern = format!("{}/{}", resolved.trim_end_matches('/'), rest.join("/"));
+        let paths = self.inner.glob(&resolved_pattern)?;
+        Ok(paths
+            .into_iter()
+            .map(|child| match Path::new(&child).strip_prefix(&resolved) {
+                Ok(rest) if base == "." => rest.to_string_lossy().into_owned(),
+                _ => unresolve(&base, &resolved, child),
+            })
+            .collect())
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.inner.sleep(seconds)
+    }
+    fn show_image(&self, image: DynamicImage) -> Result<(), String> {
+        self.inner.show_image(image)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>) -> Result<(), String> {
+        self.inner.show_gif(gif_bytes)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>) -> Result<(), String> {
+        self.inner.play_audio(wave_bytes)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        self.inner.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        self.inner.stream_audio(f)
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.check_net(&format!("Listening on `{addr}`"))?;
+        self.inner.tcp_listen(addr)
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        self.inner.tcp_accept(handle)
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.check_net(&format!("Connecting to `{addr}`"))?;
+        self.inner.tcp_connect(addr)
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.inner.tcp_addr(handle)
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.inner.tcp_set_non_blocking(handle, non_blocking)
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.inner.tcp_set_read_timeout(handle, timeout)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.inner.tcp_set_write_timeout(handle, timeout)
+    }
+    fn http_read_request(&self, handle: Handle) -> Result<HttpRequest, String> {
+        self.inner.http_read_request(handle)
+    }
+    fn http_write_response(&self, handle: Handle, response: HttpResponse) -> Result<(), String> {
+        self.inner.http_write_response(handle, response)
+    }
+    fn http_request(&self, request: HttpRequest) -> Result<HttpResponse, String> {
+        self.check_net(&format!("Requesting `{}`", request.url))?;
+        self.inner.http_request(request)
+    }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        self.check_net(&format!("Binding to `{addr}`"))?;
+        self.inner.udp_bind(addr)
+    }
+    fn udp_send_to(&self, handle: Handle, data: &[u8], addr: &str) -> Result<(), String> {
+        self.inner.udp_send_to(handle, data, addr)
+    }
+    fn udp_receive_from(&self, handle: Handle) -> Result<(Vec<u8>, String), String> {
+        self.inner.udp_receive_from(handle)
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.inner.udp_addr(handle)
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.inner.close(handle)
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        self.check_run(&format!("Invoking `{path}`"))?;
+        self.inner.invoke(path)
+    }
+    fn spawn(
+        &self,
+        env: Uiua,
+        f: Box<dyn FnOnce(&mut Uiua) -> UiuaResult + Send>,
+    ) -> Result<Handle, String> {
+        self.inner.spawn(env, f)
+    }
+    fn wait(&self, handle: Handle) -> Result<Vec<Value>, Result<UiuaError, String>> {
+        self.inner.wait(handle)
+    }
+    fn wait_timeout(
+        &self,
+        handle: Handle,
+        timeout: f64,
+    ) -> Result<Option<Vec<Value>>, Result<UiuaError, String>> {
+        self.inner.wait_timeout(handle, timeout)
+    }
+    fn channel(&self) -> Result<Handle, String> {
+        self.inner.channel()
+    }
+    fn channel_send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        self.inner.channel_send(handle, value)
+    }
+    fn channel_receive(&self, handle: Handle) -> Result<Value, String> {
+        self.inner.channel_receive(handle)
+    }
+    fn channel_try_receive(&self, handle: Handle) -> Result<Option<Value>, String> {
+        self.inner.channel_try_receive(handle)
+    }
+    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
+        self.check_run(&format!("Running `{command}`"))?;
+        self.inner.run_command_inherit(command, args)
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+    ) -> Result<(i32, String, String), String> {
+        self.check_run(&format!("Running `{command}`"))?;
+        self.inner.run_command_capture(command, args)
+    }
+    fn change_directory(&self, path: &str) -> Result<(), String> {
+        let path = self.check_read(path)?;
+        self.inner.change_directory(&path)
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        self.check_net("Making HTTPS requests")?;
+        self.inner.https_get(request, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sandbox_permissions() {
+        let dir = env::temp_dir().join("uiua_sandbox_test");
+        let allowed = dir.join("allowed");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        let permissions = Permissions::none()
+            .allow_read(&allowed)
+            .allow_write(&allowed)
+            .allow_env("UIUA_SANDBOX_ALLOWED");
+        let mut env = Uiua::with_backend(SandboxSys::new(NativeSys, permissions));
+        let run = |env: &mut Uiua, code: &str| env.load_str(code).map_err(|e| e.message());
+
+        let path = allowed.join("file.txt").display().to_string();
+        run(&mut env, &format!("&fwa {path:?} \"hi\"")).unwrap();
+        run(&mut env, &format!("&fras {path:?}")).unwrap();
+        let contents = env.pop("contents").unwrap();
+        assert_eq!(contents.as_string(&env, "").unwrap(), "hi");
+
+        // Escaping the allowed directory
+        let escape = allowed.join("..").join("secret.txt").display().to_string();
+        let err = run(&mut env, &format!("&fras {escape:?}")).unwrap_err();
+        assert!(err.contains("--allow-read"), "{err}");
+        let err = run(&mut env, &format!("&fwa {escape:?} \"x\"")).unwrap_err();
+        assert!(err.contains("--allow-write"), "{err}");
+
+        let err = run(&mut env, "&runc {\"echo\" \"hi\"}").unwrap_err();
+        assert!(err.contains("--allow-run"), "{err}");
+        let err = run(&mut env, "&tcpc \"localhost:1\"").unwrap_err();
+        assert!(err.contains("--allow-net"), "{err}");
+        let err = run(&mut env, "&var \"HOME\"").unwrap_err();
+        assert!(err.contains("--allow-env"), "{err}");
+        run(&mut env, "&var \"UIUA_SANDBOX_ALLOWED\"").unwrap();
+
+        _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sandbox_symlink_escape() {
+        let dir = env::temp_dir().join("uiua_sandbox_symlink_test");
+        _ = std::fs::remove_dir_all(&dir);
+        let allowed = dir.join("allowed");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::create_dir_all(outside.join("inner")).unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        std::fs::write(allowed.join("file.txt"), "hi").unwrap();
+        std::os::unix::fs::symlink(outside.join("inner"), allowed.join("link")).unwrap();
+        std::os::unix::fs::symlink(allowed.join("file.txt"), allowed.join("file_link")).unwrap();
+        let permissions = Permissions::none().allow_read(&allowed);
+        let mut env = Uiua::with_backend(SandboxSys::new(NativeSys, permissions));
+        let run = |env: &mut Uiua, code: &str| env.load_str(code).map_err(|e| e.message());
+
+        // `..` after a symlink leaves the directory the symlink points to
+        let escape = allowed.join("link/../secret.txt").display().to_string();
+        let err = run(&mut env, &format!("&fras {escape:?}")).unwrap_err();
+        assert!(err.contains("--allow-read"), "{err}");
+        let escape = allowed.join("link/..").display().to_string();
+        let err = run(&mut env, &format!("&fld {escape:?}")).unwrap_err();
+        assert!(err.contains("--allow-read"), "{err}");
+        let err = run(&mut env, &format!("&fg {:?}", format!("{escape}/*.txt"))).unwrap_err();
+        assert!(err.contains("--allow-read"), "{err}");
+
+        // Symlinks that stay inside are fine
+        let inside = allowed.join("file_link").display().to_string();
+        run(&mut env, &format!("&fras {inside:?}")).unwrap();
+        let contents = env.pop("contents").unwrap();
+        assert_eq!(contents.as_string(&env, "").unwrap(), "hi");
+        let listed = allowed.display().to_string();
+        run(&mut env, &format!("&fld {listed:?}")).unwrap();
+        let paths = env.pop("paths").unwrap();
+        assert!(paths
+            .into_rows()
+            .all(|path| { (path.as_string(&env, "").unwrap()).starts_with(&listed) }));
+
+        _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,470 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, VecDeque},
+};
+
+use parking_lot::Mutex;
+
+use crate::{FileMetadata, Handle, SysBackend};
+
+/// A [`SysBackend`] with an in-memory filesystem
+///
+/// Output to stdout and stderr is captured, and stdin can be provided ahead of time.
+/// Nothing touches the real filesystem, so this is useful for tests and embedding.
+///
+/// Paths use `/` as a separator. Relative paths are resolved against a virtual
+/// working directory, which starts at the root.
+#[derive(Default)]
+pub struct VirtualSys {
+    fs: Mutex<VirtualFs>,
+    stdin: Mutex<VecDeque<String>>,
+    stdout: Mutex<String>,
+    stderr: Mutex<String>,
+}
+
+struct VirtualFs {
+    entries: BTreeMap<String, Entry>,
+    cwd: String,
+    open: HashMap<Handle, OpenFile>,
+    next_handle: u64,
+}
+
+enum Entry {
+    Dir,
+    File(Vec<u8>),
+}
+
+struct OpenFile {
+    path: String,
+    pos: usize,
+}
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        VirtualFs {
+            entries: [("/".into(), Entry::Dir)].into(),
+            cwd: "/".into(),
+            open: HashMap::new(),
+            next_handle: Handle::FIRST_UNRESERVED.0,
+        }
+    }
+}
+
+impl VirtualFs {
+    /// Get the absolute, normalized form of a path
+    fn resolve(&self, path: &str) -> String {
+        let mut parts: Vec<&str> = if path.starts_with(['/', '\\']) {
+            Vec::new()
+        } else {
+            self.cwd.split('/').filter(|s| !s.is_empty()).collect()
+        };
+        for part in path.split(['/', '\\']) {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        format!("/{}", parts.join("/"))
+    }
+    fn parent(path: &str) -> Option<&str> {
+        if path == "/" {
+            return None;
+        }
+        Some(match path.rfind('/') {
+            Some(0) | None => "/",
+            Some(i) => &path[..i],
+        })
+    }
+    fn create_dir_all(&mut self, path: &str) -> Result<(), String> {
+        match self.entries.get(path) {
+            Some(Entry::Dir) => return Ok(()),
+            Some(Entry::File(_)) => return Err(format!("{path} is a file")),
+            None => {}
+        }
+        if let Some(parent) = Self::parent(path) {
+            self.create_dir_all(parent)?;
+        }
+        self.entries.insert(path.into(), Entry::Dir);
+        Ok(())
+    }
+    fn check_parent(&self, path: &str) -> Result<(), String> {
+        match Self::parent(path).map(|parent| self.entries.get(parent)) {
+            Some(Some(Entry::Dir)) => Ok(()),
+            Some(Some(Entry::File(_))) => Err(format!("Parent of {path} is a file")),
+            _ => Err(format!("Directory of {path} does not exist")),
+        }
+    }
+    fn write_file(&mut self, path: &str, contents: Vec<u8>) -> Result<(), String> {
+        self.check_parent(path)?;
+        if let Some(Entry::Dir) = self.entries.get(path) {
+            return Err(format!("{path} is a directory"));
+        }
+        self.entries.insert(path.into(), Entry::File(contents));
+        Ok(())
+    }
+    fn file(&self, path: &str) -> Result<&Vec<u8>, String> {
+        match self.entries.get(path) {
+            Some(Entry::File(contents)) => Ok(contents),
+            Some(Entry::Dir) => Err(format!("{path} is a directory")),
+            None => Err(format!("File not found: {path}")),
+        }
+    }
+    /// Get the paths of an entry and all of its descendants
+    fn subtree(&self, path: &str) -> Vec<String> {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        let descendants = (self.entries.range(prefix.clone()..))
+            .map(|(child, _)| child)
+            .take_while(|child| child.starts_with(&prefix));
+        let mut paths: Vec<String> = descendants.cloned().collect();
+        paths.insert(0, path.into());
+        paths
+    }
+    fn open(&mut self, path: String) -> Handle {
+        let handle = Handle(self.next_handle);
+        self.next_handle += 1;
+        self.open.insert(handle, OpenFile { path, pos: 0 });
+        handle
+    }
+    fn open_file(&mut self, handle: Handle) -> Result<(&mut OpenFile, &mut Vec<u8>), String> {
+        let file = (self.open.get_mut(&handle)).ok_or_else(|| "Invalid file handle".to_string())?;
+        match self.entries.get_mut(&file.path) {
+            Some(Entry::File(contents)) => Ok((file, contents)),
+            _ => Err(format!("{} no longer exists", file.path)),
+        }
+    }
+}
+
+impl VirtualSys {
+    /// Create a backend with an empty filesystem
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a file, creating its parent directories
+    pub fn with_file(self, path: &str, contents: impl Into<Vec<u8>>) -> Self {
+        {
+            let mut fs = self.fs.lock();
+            let path = fs.resolve(path);
+            if let Some(parent) = VirtualFs::parent(&path) {
+                _ = fs.create_dir_all(parent);
+            }
+            _ = fs.write_file(&path, contents.into());
+        }
+        self
+    }
+    /// Add a directory, creating its parent directories
+    pub fn with_dir(self, path: &str) -> Self {
+        _ = self.create_dir_all(path);
+        self
+    }
+    /// Provide text to be read from stdin
+    pub fn with_stdin(self, stdin: &str) -> Self {
+        self.stdin.lock().extend(stdin.lines().map(Into::into));
+        self
+    }
+    /// Create a directory and all of its missing parents
+    pub fn create_dir_all(&self, path: &str) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let path = fs.resolve(path);
+        fs.create_dir_all(&path)
+    }
+    /// Get the contents of a file
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        let fs = self.fs.lock();
+        fs.file(&fs.resolve(path)).ok().cloned()
+    }
+    /// Get the absolute paths of all files
+    pub fn files(&self) -> Vec<String> {
+        let fs = self.fs.lock();
+        (fs.entries.iter())
+            .filter(|(_, entry)| matches!(entry, Entry::File(_)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+    /// Get the output printed to stdout so far
+    pub fn stdout(&self) -> String {
+        self.stdout.lock().clone()
+    }
+    /// Get the output printed to stderr so far
+    pub fn stderr(&self) -> String {
+        self.stderr.lock().clone()
+    }
+    /// Take the output printed to stdout, clearing the buffer
+    pub fn take_stdout(&self) -> String {
+        std::mem::take(&mut *self.stdout.lock())
+    }
+    /// Take the output printed to stderr, clearing the buffer
+    pub fn take_stderr(&self) -> String {
+        std::mem::take(&mut *self.stderr.lock())
+    }
+}
+
+impl SysBackend for VirtualSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.stdout.lock().push_str(s);
+        Ok(())
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.stderr.lock().push_str(s);
+        Ok(())
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.stderr.lock().push_str(s);
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        Ok(self.stdin.lock().pop_front())
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        let fs = self.fs.lock();
+        fs.entries.contains_key(&fs.resolve(path))
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        let fs = self.fs.lock();
+        let dir = fs.resolve(path);
+        match fs.entries.get(&dir) {
+            Some(Entry::Dir) => {}
+            Some(Entry::File(_)) => return Err(format!("{path} is not a directory")),
+            None => return Err(format!("Directory not found: {path}")),
+        }
+        let prefix = if dir == "/" { dir } else { format!("{dir}/") };
+        let base = path.trim_end_matches(['/', '\\']);
+        Ok((fs.entries.range(prefix.clone()..))
+            .map(|(child, _)| child)
+            .take_while(|child| child.starts_with(&prefix))
+            .map(|child| &child[prefix.len()..])
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .map(|name| format!("{base}/{name}"))
+            .collect())
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        let fs = self.fs.lock();
+        match fs.entries.get(&fs.resolve(path)) {
+            Some(entry) => Ok(matches!(entry, Entry::File(_))),
+            None => Err(format!("File not found: {path}")),
+        }
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        let mut fs = self.fs.lock();
+        let (file, contents) = fs.open_file(handle)?;
+        let start = file.pos.min(contents.len());
+        let end = start.saturating_add(count).min(contents.len());
+        file.pos = end;
+        Ok(contents[start..end].to_vec())
+    }
+    fn write(&self, handle: Handle, bytes: &[u8]) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let (file, contents) = fs.open_file(handle)?;
+        let end = file.pos + bytes.len();
+        if contents.len() < end {
+            contents.resize(end, 0);
+        }
+        contents[file.pos..end].copy_from_slice(bytes);
+        file.pos = end;
+        Ok(())
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        let mut fs = self.fs.lock();
+        let path = fs.resolve(path);
+        fs.write_file(&path, Vec::new())?;
+        Ok(fs.open(path))
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        let mut fs = self.fs.lock();
+        let path = fs.resolve(path);
+        fs.file(&path)?;
+        Ok(fs.open(path))
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        let fs = self.fs.lock();
+        fs.file(&fs.resolve(path)).cloned()
+    }
+    fn file_write_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let path = fs.resolve(path);
+        fs.write_file(&path, contents.to_vec())
+    }
+    fn file_append_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let path = fs.resolve(path);
+        match fs.entries.get_mut(&path) {
+            Some(Entry::File(existing)) => {
+                existing.extend_from_slice(contents);
+                Ok(())
+            }
+            _ => fs.write_file(&path, contents.to_vec()),
+        }
+    }
+    fn delete_file(&self, path: &str) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let resolved = fs.resolve(path);
+        fs.file(&resolved)?;
+        fs.entries.remove(&resolved);
+        Ok(())
+    }
+    fn make_dir(&self, path: &str) -> Result<(), String> {
+        self.create_dir_all(path)
+    }
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let dir = fs.resolve(path);
+        match fs.entries.get(&dir) {
+            Some(Entry::Dir) if dir == "/" => return Err("Cannot remove the root directory".into()),
+            Some(Entry::Dir) => {}
+            Some(Entry::File(_)) => return Err(format!("{path} is not a directory")),
+            None => return Err(format!("Directory not found: {path}")),
+        }
+        for path in fs.subtree(&dir) {
+            fs.entries.remove(&path);
+        }
+        Ok(())
+    }
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let (from, to) = (fs.resolve(from), fs.resolve(to));
+        if from == to {
+            return Ok(());
+        }
+        if !fs.entries.contains_key(&from) || from == "/" {
+            return Err(format!("File not found: {from}"));
+        }
+        if to.starts_with(&format!("{from}/")) {
+            return Err(format!("Cannot move {from} into itself"));
+        }
+        fs.check_parent(&to)?;
+        if let Some(Entry::Dir) = fs.entries.get(&to) {
+            return Err(format!("{to} is a directory"));
+        }
+        for path in fs.subtree(&from) {
+            let entry = fs.entries.remove(&path).unwrap();
+            fs.entries
+                .insert(format!("{to}{}", &path[from.len()..]), entry);
+        }
+        Ok(())
+    }
+    fn copy_file(&self, from: &str, to: &str) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let (from, to) = (fs.resolve(from), fs.resolve(to));
+        let contents = fs.file(&from)?.clone();
+        fs.write_file(&to, contents)
+    }
+    fn file_metadata(&self, path: &str) -> Result<FileMetadata, String> {
+        let fs = self.fs.lock();
+        match fs.entries.get(&fs.resolve(path)) {
+            Some(entry) => Ok(FileMetadata {
+                size: match entry {
+                    Entry::File(contents) => contents.len() as u64,
+                    Entry::Dir => 0,
+                },
+                modified: None,
+                is_dir: matches!(entry, Entry::Dir),
+            }),
+            None => Err(format!("File not found: {path}")),
+        }
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.fs.lock().open.remove(&handle);
+        Ok(())
+    }
+    fn change_directory(&self, path: &str) -> Result<(), String> {
+        let mut fs = self.fs.lock();
+        let dir = fs.resolve(path);
+        match fs.entries.get(&dir) {
+            Some(Entry::Dir) => {
+                fs.cwd = dir;
+                Ok(())
+            }
+            Some(Entry::File(_)) => Err(format!("{path} is not a directory")),
+            None => Err(format!("Directory not found: {path}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{array::Array, value::Value, Uiua};
+
+    #[test]
+    fn virtual_fs() {
+        let sys = VirtualSys::new()
+            .with_file("data/in.txt", "hello")
+            .with_stdin("line");
+        let mut env = Uiua::with_backend(sys);
+        env.load_str(
+            r#"
+            &fwa "data/out.txt" ⊂∶" world" &fras "data/in.txt"
+            &p &fras "data/out.txt"
+            &p ⧻&fld "data"
+            &cd "data"
+            &p &fe "in.txt"
+            &w ⊂∶"\n" &sc 2
+            &rs 3 &fo "in.txt"
+            "#,
+        )
+        .unwrap();
+        let read = env.pop("read").unwrap();
+        assert_eq!(read.as_string(&env, "").unwrap(), "hel");
+        let sys = env.downcast_backend::<VirtualSys>().unwrap();
+        assert_eq!(sys.file("/data/out.txt").unwrap(), b"hello world");
+        assert_eq!(sys.stdout(), "hello world\n2\n1\n");
+        assert_eq!(sys.stderr(), "line\n");
+        assert_eq!(sys.files(), ["/data/in.txt", "/data/out.txt"]);
+        assert!(env.load_str(r#"&fras "missing.txt""#).is_err());
+
+        env.load_str(
+            r#"
+            &fmd "/a/b"
+            &faa "/a/b/log.txt" "one"
+            &faa "/a/b/log.txt" "two"
+            &fcp "/a/b/log.txt" "/a/copy.txt"
+            &fmv "/a/b" "/a/c"
+            &fde "/a/copy.txt"
+            ⊏0_2 &fmeta "/a/c/log.txt"
+            &frd "/a"
+            &fe "/a"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(env.pop("exists").unwrap(), 0.into());
+
+        env.load_str(
+            r#"
+            &fmd "/data/sub"
+            &fwa "/data/sub/deep.csv" ""
+            &fwa "/data/sub/notes.txt" ""
+            &fwa "/data/top.csv" ""
+            &fg "/data/**/*.csv"
+            &fwk "/data"
+            "#,
+        )
+        .unwrap();
+        let walked = env.pop("paths").unwrap();
+        let depths = env.pop("depths").unwrap();
+        let is_dir = env.pop("is dir").unwrap();
+        let globbed = env.pop("glob").unwrap();
+        let strings = |value: Value| {
+            (value.into_rows())
+                .map(|row| row.as_string(&env, "").unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strings(globbed), ["/data/sub/deep.csv", "/data/top.csv"]);
+        assert_eq!(walked.row_count(), 6);
+        assert_eq!(strings(walked)[2], "/data/sub");
+        assert_eq!(depths.as_naturals(&env, "").unwrap()[3], 2);
+        assert_eq!(is_dir.as_naturals(&env, "").unwrap()[2], 1);
+        for path in ["/data/sub/deep.csv", "/data/sub/notes.txt", "/data/top.csv"] {
+            env.load_str(&format!("&fde {path:?}")).unwrap();
+        }
+        env.load_str(r#"&frd "/data/sub""#).unwrap();
+        let metadata = Array::<f64>::from_iter([6.0, 0.0]);
+        assert_eq!(env.pop("metadata").unwrap(), metadata.into());
+        let sys = env.downcast_backend::<VirtualSys>().unwrap();
+        assert_eq!(sys.files(), ["/data/in.txt", "/data/out.txt"]);
+        assert!(env.load_str(r#"&fwa "nowhere/a.txt" "x""#).is_err());
+    }
+}
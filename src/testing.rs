@@ -0,0 +1,226 @@
+//! Test case results and reporting
+
+use std::fmt::Write;
+
+use crate::{lex::Span, UiuaError};
+
+/// The result of running a single test case
+///
+/// A test case is either a line with an `assert` in a test scope,
+/// or a whole test scope if it has no asserts.
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    /// The name of the test case
+    pub name: String,
+    /// The span of the test case's code
+    pub span: Span,
+    /// The error that caused the test to fail, if any
+    pub error: Option<UiuaError>,
+    /// How long the test took to run, in milliseconds
+    pub duration: f64,
+}
+
+impl TestResult {
+    /// Check if the test passed
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Render test results as a JUnit XML report
+pub fn junit_xml(suite: &str, results: &[TestResult]) -> String {
+    let failures = results.iter().filter(|r| !r.passed()).count();
+    let time: f64 = results.iter().map(|r| r.duration).sum::<f64>() / 1000.0;
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<testsuites tests="{}" failures="{failures}" time="{time:.3}">"#,
+        results.len()
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        r#"  <testsuite name="{}" tests="{}" failures="{failures}" time="{time:.3}">"#,
+        xml_escape(suite),
+        results.len()
+    )
+    .unwrap();
+    for result in results {
+        let location = match &result.span {
+            Span::Code(span) => {
+                let file = (span.path.as_ref())
+                    .map(|path| format!(r#" file="{}""#, xml_escape(&path.to_string_lossy())))
+                    .unwrap_or_default();
+                format!(r#"{file} line="{}""#, span.start.line)
+            }
+            Span::Builtin => String::new(),
+        };
+        write!(
+            xml,
+            r#"    <testcase name="{}" classname="{}"{location} time="{:.3}""#,
+            xml_escape(&result.name),
+            xml_escape(suite),
+            result.duration / 1000.0
+        )
+        .unwrap();
+        if let Some(error) = &result.error {
+            writeln!(xml, ">").unwrap();
+            writeln!(
+                xml,
+                r#"      <failure message="{}">{}</failure>"#,
+                xml_escape(&error.message()),
+                xml_escape(&error.show(false))
+            )
+            .unwrap();
+            writeln!(xml, "    </testcase>").unwrap();
+        } else {
+            writeln!(xml, "/>").unwrap();
+        }
+    }
+    writeln!(xml, "  </testsuite>").unwrap();
+    writeln!(xml, "</testsuites>").unwrap();
+    xml
+}
+
+/// Show the lines that differ between two strings
+///
+/// Removed lines are prefixed with `-` and added lines with `+`.
+pub fn line_diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    // Longest common subsequence table
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            writeln!(diff, "  {}", a[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            writeln!(diff, "- {}", a[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(diff, "+ {}", b[j]).unwrap();
+            j += 1;
+        }
+    }
+    diff.truncate(diff.trim_end().len());
+    diff
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run::RunMode, Uiua};
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(line_diff("a\nb\nc", "a\nb\nc"), "  a\n  b\n  c");
+        assert_eq!(line_diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c");
+        assert_eq!(line_diff("a", "a\nb"), "  a\n+ b");
+    }
+
+    fn run_tests(filter: Option<&str>) -> Vec<TestResult> {
+        let mut env = Uiua::with_native_sys()
+            .with_mode(RunMode::Test)
+            .collect_tests(true);
+        if let Some(filter) = filter {
+            env = env.with_test_filter(filter);
+        }
+        env.load_str_path(
+            "~~~\n# adding\nassert. =3 +1 2\nassert. =5 +1 2\n~~~\n~~~\n# empty\n~~~\n",
+            "tests.ua",
+        )
+        .unwrap();
+        env.take_test_results()
+    }
+
+    #[test]
+    fn collect_failures() {
+        let results = run_tests(None);
+        let names: Vec<_> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "adding: assert. =3 +1 2",
+                "adding: assert. =5 +1 2",
+                "empty"
+            ]
+        );
+        let passed: Vec<_> = results.iter().map(TestResult::passed).collect();
+        assert_eq!(passed, [true, false, true]);
+    }
+
+    #[test]
+    fn filter_tests() {
+        let results = run_tests(Some("5"));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "adding: assert. =5 +1 2");
+        assert!(run_tests(Some("nothing")).is_empty());
+    }
+
+    #[test]
+    fn snapshots() {
+        let dir = std::env::temp_dir().join(format!("uiua-snap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("grid.ua");
+        let snap = dir.join("grid.rows.snap");
+        let run = |mode: RunMode, code: &str| {
+            std::fs::write(&script, code).unwrap();
+            let mut env = Uiua::with_native_sys()
+                .with_mode(mode)
+                .with_file_path(&script);
+            env.load_file(&script)
+        };
+        let code = "~~~\n[1_2 3_4]\n+1 2\n&snap \"rows\"\n~~~\n";
+        // Snapshots are only created when testing
+        run(RunMode::All, code).unwrap();
+        assert!(!snap.exists());
+        run(RunMode::Test, code).unwrap();
+        let stored = std::fs::read_to_string(&snap).unwrap();
+        assert_eq!(stored, "╭─     \n╷ 1 2  \n  3 4  \n      ╯\n3\n");
+        // A changed stack fails in any mode
+        let changed = code.replace("+1 2", "+1 3");
+        let err = run(RunMode::All, &changed).unwrap_err().to_string();
+        assert!(err.contains("- 3\n+ 4"), "{err}");
+        assert!(run(RunMode::Test, &changed).is_err());
+        assert!(run(RunMode::Normal, "&snap \"rows\"").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn junit_report() {
+        let xml = junit_xml("tests.ua", &run_tests(None));
+        assert!(xml.contains(r#"<testsuite name="tests.ua" tests="3" failures="1""#));
+        assert!(xml.contains(
+            r#"<testcase name="adding: assert. =5 +1 2" classname="tests.ua" file="tests.ua" line="4""#
+        ));
+        assert_eq!(xml.matches("<failure ").count(), 1);
+    }
+}